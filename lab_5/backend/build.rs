@@ -0,0 +1,10 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // protox compiles the definitions in-process, so no protoc is needed to build.
+    let descriptors = protox::compile(["proto/student_management.proto"], ["proto"])?;
+    // The clients are only used by the tests, and tonic allows them to go unused.
+    tonic_build::configure()
+        .build_client(true)
+        .compile_fds(descriptors)?;
+    println!("cargo:rerun-if-changed=proto");
+    Ok(())
+}
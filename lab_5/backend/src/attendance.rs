@@ -0,0 +1,514 @@
+use actix_web::{web, HttpResponse, Responder};
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+use crate::calendar::SEMESTER;
+use crate::groups::GroupStore;
+use crate::openapi::{BadRequest, NotFound, ServerError};
+use crate::routes::database_error;
+use crate::storage::STUDENT_TABLE_NAME;
+use crate::schedule::WeekParity;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+enum AttendanceStatus {
+    Present,
+    Absent,
+    Excused,
+    Late,
+}
+
+impl AttendanceStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            AttendanceStatus::Present => "present",
+            AttendanceStatus::Absent => "absent",
+            AttendanceStatus::Excused => "excused",
+            AttendanceStatus::Late => "late",
+        }
+    }
+}
+
+#[derive(Serialize, sqlx::FromRow, ToSchema)]
+struct AttendanceRecord {
+    student_id: i32,
+    date: NaiveDate,
+    slot_id: i32,
+    status: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct AttendanceInput {
+    student_id: i32,
+    date: NaiveDate,
+    slot_id: i32,
+    status: AttendanceStatus,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct AttendanceKey {
+    student_id: i32,
+    date: NaiveDate,
+    slot_id: i32,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct StatusOverride {
+    student_id: i32,
+    status: AttendanceStatus,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct BulkAttendanceInput {
+    slot_id: i32,
+    date: NaiveDate,
+    /// Status given to every member of the group not listed in `overrides`.
+    default_status: AttendanceStatus,
+    #[serde(default)]
+    overrides: Vec<StatusOverride>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct BulkAttendanceResult {
+    marked: usize,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct AttendanceFilter {
+    student_id: Option<i32>,
+    group_id: Option<i32>,
+    slot_id: Option<i32>,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct DateRange {
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+}
+
+#[derive(Serialize, sqlx::FromRow, Default, Clone, Copy, ToSchema)]
+struct AttendanceCounts {
+    total: i64,
+    present: i64,
+    absent: i64,
+    excused: i64,
+    late: i64,
+}
+
+impl AttendanceCounts {
+    /// Share of attended classes; being late counts as attending and excused
+    /// absences are left out of the calculation entirely.
+    fn percentage(&self) -> Option<f64> {
+        let counted = self.total - self.excused;
+        match counted {
+            0 => None,
+            _ => Some(((self.present + self.late) as f64 * 10000.0 / counted as f64).round() / 100.0),
+        }
+    }
+
+    fn add(&mut self, other: &AttendanceCounts) {
+        self.total += other.total;
+        self.present += other.present;
+        self.absent += other.absent;
+        self.excused += other.excused;
+        self.late += other.late;
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct StudentCountsRow {
+    student_id: i32,
+    name: Option<String>,
+    surname: Option<String>,
+    #[sqlx(flatten)]
+    counts: AttendanceCounts,
+}
+
+#[derive(Serialize, ToSchema)]
+struct StudentSummary {
+    student_id: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    surname: Option<String>,
+    #[serde(flatten)]
+    counts: AttendanceCounts,
+    percentage: Option<f64>,
+}
+
+impl From<StudentCountsRow> for StudentSummary {
+    fn from(row: StudentCountsRow) -> Self {
+        StudentSummary {
+            student_id: row.student_id,
+            name: row.name,
+            surname: row.surname,
+            percentage: row.counts.percentage(),
+            counts: row.counts,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+struct GroupSummary {
+    group_id: i32,
+    #[serde(flatten)]
+    counts: AttendanceCounts,
+    percentage: Option<f64>,
+    students: Vec<StudentSummary>,
+}
+
+#[derive(sqlx::FromRow)]
+struct SlotInfo {
+    group_id: i32,
+    weekday: i16,
+    week_parity: String,
+}
+
+const COUNT_COLUMNS: &str = "COUNT(*) AS total,
+    COUNT(*) FILTER (WHERE a.status = 'present') AS present,
+    COUNT(*) FILTER (WHERE a.status = 'absent') AS absent,
+    COUNT(*) FILTER (WHERE a.status = 'excused') AS excused,
+    COUNT(*) FILTER (WHERE a.status = 'late') AS late";
+
+/// Makes sure `date` is a day on which the slot actually takes place.
+async fn load_slot_for_date(pool: &PgPool, slot_id: i32, date: NaiveDate) -> Result<SlotInfo, HttpResponse> {
+    let slot = match sqlx::query_as::<_, SlotInfo>(
+        "SELECT group_id, weekday, week_parity FROM schedule_slots WHERE id = $1"
+    )
+        .bind(slot_id)
+        .fetch_optional(pool)
+        .await
+    {
+        Ok(Some(slot)) => slot,
+        Ok(None) => return Err(HttpResponse::BadRequest().body(format!("Schedule slot {slot_id} does not exist"))),
+        Err(e) => return Err(database_error("Failed to fetch schedule slot", e)),
+    };
+
+    if date.weekday().number_from_monday() != slot.weekday as u32 {
+        return Err(HttpResponse::BadRequest().body(format!("Slot {slot_id} does not take place on {date}")));
+    }
+    if let Ok(semester) = SEMESTER.as_ref() {
+        let Some(week) = semester.week_of(date) else {
+            return Err(HttpResponse::BadRequest().body(format!("{date} is outside of the semester")));
+        };
+        if slot.week_parity != "both" && slot.week_parity != WeekParity::of_week(week).as_str() {
+            return Err(HttpResponse::BadRequest()
+                .body(format!("Slot {slot_id} does not take place in week {week}")));
+        }
+    }
+    Ok(slot)
+}
+
+#[utoipa::path(
+    get,
+    path = "/attendance",
+    tag = "attendance",
+    params(AttendanceFilter),
+    responses(
+        (status = 200, description = "Attendance records matching the filters", body = Vec<AttendanceRecord>),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn get_attendance(filter: web::Query<AttendanceFilter>, pool: web::Data<PgPool>) -> impl Responder {
+    log::debug!("Fetching attendance records");
+    match sqlx::query_as::<_, AttendanceRecord>(
+        "SELECT a.student_id, a.date, a.slot_id, a.status
+         FROM attendance a
+         JOIN schedule_slots s ON s.id = a.slot_id
+         WHERE ($1::INT IS NULL OR a.student_id = $1)
+           AND ($2::INT IS NULL OR s.group_id = $2)
+           AND ($3::INT IS NULL OR a.slot_id = $3)
+           AND ($4::DATE IS NULL OR a.date >= $4)
+           AND ($5::DATE IS NULL OR a.date <= $5)
+         ORDER BY a.date, s.start_time, a.student_id"
+    )
+        .bind(filter.student_id)
+        .bind(filter.group_id)
+        .bind(filter.slot_id)
+        .bind(filter.from)
+        .bind(filter.to)
+        .fetch_all(pool.get_ref())
+        .await
+    {
+        Ok(records) => {
+            log::info!("Successfully retrieved {} attendance records", records.len());
+            HttpResponse::Ok().json(records)
+        },
+        Err(e) => database_error("Failed to fetch attendance", e),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/attendance",
+    tag = "attendance",
+    request_body = AttendanceInput,
+    responses(
+        (status = 200, description = "The attendance was recorded"),
+        (status = 400, response = BadRequest),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn mark_attendance(record: web::Json<AttendanceInput>, pool: web::Data<PgPool>) -> impl Responder {
+    log::debug!("Marking student {} as {} on {}", record.student_id, record.status.as_str(), record.date);
+    let slot = match load_slot_for_date(pool.get_ref(), record.slot_id, record.date).await {
+        Ok(slot) => slot,
+        Err(response) => return response,
+    };
+
+    let query = format!("SELECT group_id FROM {} WHERE id = $1", *STUDENT_TABLE_NAME);
+    match sqlx::query_scalar::<_, Option<i32>>(&query)
+        .bind(record.student_id)
+        .fetch_optional(pool.get_ref())
+        .await
+    {
+        Ok(Some(Some(group_id))) if group_id == slot.group_id => {},
+        Ok(Some(_)) => return HttpResponse::BadRequest().body("Student does not belong to the group of this class"),
+        Ok(None) => return HttpResponse::BadRequest().body("Student does not exist"),
+        Err(e) => return database_error("Failed to fetch student", e),
+    }
+
+    match sqlx::query(
+        "INSERT INTO attendance (student_id, date, slot_id, status) VALUES ($1, $2, $3, $4)
+         ON CONFLICT (student_id, date, slot_id) DO UPDATE SET status = EXCLUDED.status"
+    )
+        .bind(record.student_id)
+        .bind(record.date)
+        .bind(record.slot_id)
+        .bind(record.status.as_str())
+        .execute(pool.get_ref())
+        .await
+    {
+        Ok(_) => {
+            log::info!("Successfully marked attendance of student id: {}", record.student_id);
+            HttpResponse::Ok().finish()
+        },
+        Err(e) => database_error("Failed to mark attendance", e),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/attendance/bulk",
+    tag = "attendance",
+    request_body = BulkAttendanceInput,
+    responses(
+        (status = 200, description = "Attendance of the whole group was recorded", body = BulkAttendanceResult),
+        (status = 400, response = BadRequest),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn mark_group_attendance(input: web::Json<BulkAttendanceInput>, pool: web::Data<PgPool>) -> impl Responder {
+    log::debug!("Marking attendance of slot {} on {}", input.slot_id, input.date);
+    let slot = match load_slot_for_date(pool.get_ref(), input.slot_id, input.date).await {
+        Ok(slot) => slot,
+        Err(response) => return response,
+    };
+
+    let query = format!("SELECT id FROM {} WHERE group_id = $1 ORDER BY id", *STUDENT_TABLE_NAME);
+    let roster: Vec<i32> = match sqlx::query_scalar(&query)
+        .bind(slot.group_id)
+        .fetch_all(pool.get_ref())
+        .await
+    {
+        Ok(roster) => roster,
+        Err(e) => return database_error("Failed to fetch group roster", e),
+    };
+
+    if let Some(outsider) = input.overrides.iter().find(|o| !roster.contains(&o.student_id)) {
+        return HttpResponse::BadRequest()
+            .body(format!("Student {} does not belong to the group of this class", outsider.student_id));
+    }
+
+    let statuses: Vec<&str> = roster
+        .iter()
+        .map(|student_id| {
+            input.overrides
+                .iter()
+                .find(|o| o.student_id == *student_id)
+                .map_or(input.default_status, |o| o.status)
+                .as_str()
+        })
+        .collect();
+
+    match sqlx::query(
+        "INSERT INTO attendance (student_id, date, slot_id, status)
+         SELECT student_id, $2, $3, status FROM UNNEST($1::INT[], $4::VARCHAR[]) AS r(student_id, status)
+         ON CONFLICT (student_id, date, slot_id) DO UPDATE SET status = EXCLUDED.status"
+    )
+        .bind(&roster)
+        .bind(input.date)
+        .bind(input.slot_id)
+        .bind(&statuses)
+        .execute(pool.get_ref())
+        .await
+    {
+        Ok(_) => {
+            log::info!("Successfully marked attendance of {} students for slot {}", roster.len(), input.slot_id);
+            HttpResponse::Ok().json(BulkAttendanceResult { marked: roster.len() })
+        },
+        Err(e) => database_error("Failed to mark attendance", e),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/attendance",
+    tag = "attendance",
+    params(AttendanceKey),
+    responses(
+        (status = 200, description = "The record was deleted"),
+        (status = 404, response = NotFound),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn delete_attendance(key: web::Query<AttendanceKey>, pool: web::Data<PgPool>) -> impl Responder {
+    log::debug!("Deleting attendance of student {} on {}", key.student_id, key.date);
+    match sqlx::query("DELETE FROM attendance WHERE student_id = $1 AND date = $2 AND slot_id = $3")
+        .bind(key.student_id)
+        .bind(key.date)
+        .bind(key.slot_id)
+        .execute(pool.get_ref())
+        .await
+    {
+        Ok(result) if result.rows_affected() > 0 => {
+            log::info!("Successfully deleted attendance record of student id: {}", key.student_id);
+            HttpResponse::Ok().finish()
+        },
+        Ok(_) => HttpResponse::NotFound().finish(),
+        Err(e) => database_error("Failed to delete attendance", e),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/attendance/summary/students/{id}",
+    tag = "attendance",
+    params(("id" = i32, Path, description = "Student id"), DateRange),
+    responses(
+        (status = 200, description = "Attendance totals of the student", body = StudentSummary),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn get_student_summary(
+    id: web::Path<i32>,
+    range: web::Query<DateRange>,
+    pool: web::Data<PgPool>
+) -> impl Responder {
+    log::debug!("Summarizing attendance of student id: {}", id);
+    let query = format!(
+        "SELECT {COUNT_COLUMNS} FROM attendance a
+         WHERE a.student_id = $1
+           AND ($2::DATE IS NULL OR a.date >= $2)
+           AND ($3::DATE IS NULL OR a.date <= $3)"
+    );
+    match sqlx::query_as::<_, AttendanceCounts>(&query)
+        .bind(id.as_ref())
+        .bind(range.from)
+        .bind(range.to)
+        .fetch_one(pool.get_ref())
+        .await
+    {
+        Ok(counts) => HttpResponse::Ok().json(StudentSummary {
+            student_id: *id,
+            name: None,
+            surname: None,
+            percentage: counts.percentage(),
+            counts,
+        }),
+        Err(e) => database_error("Failed to summarize attendance", e),
+    }
+}
+
+/// Summarizes the classes of a group, including students that have since moved elsewhere.
+#[utoipa::path(
+    get,
+    path = "/attendance/summary/groups/{id}",
+    tag = "attendance",
+    params(("id" = i32, Path, description = "Group id"), DateRange),
+    responses(
+        (status = 200, description = "Attendance totals of the group and of each student", body = GroupSummary),
+        (status = 404, response = NotFound),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn get_group_summary(
+    id: web::Path<i32>,
+    range: web::Query<DateRange>,
+    pool: web::Data<PgPool>,
+    group_store: web::Data<GroupStore>
+) -> impl Responder {
+    log::debug!("Summarizing attendance of group id: {}", id);
+    match group_store.find(*id).await {
+        Ok(Some(_)) => {},
+        Ok(None) => {
+            log::debug!("Group not found with id: {}", id);
+            return HttpResponse::NotFound().finish();
+        },
+        Err(e) => {
+            log::error!("Failed to fetch group: {}", e);
+            return HttpResponse::InternalServerError().body(e.to_string());
+        }
+    }
+
+    let query = format!(
+        "SELECT a.student_id, st.name, st.surname, {COUNT_COLUMNS}
+         FROM attendance a
+         JOIN schedule_slots s ON s.id = a.slot_id
+         LEFT JOIN {} st ON st.id = a.student_id
+         WHERE s.group_id = $1
+           AND ($2::DATE IS NULL OR a.date >= $2)
+           AND ($3::DATE IS NULL OR a.date <= $3)
+         GROUP BY a.student_id, st.name, st.surname
+         ORDER BY st.surname, st.name, a.student_id",
+        *STUDENT_TABLE_NAME
+    );
+    match sqlx::query_as::<_, StudentCountsRow>(&query)
+        .bind(id.as_ref())
+        .bind(range.from)
+        .bind(range.to)
+        .fetch_all(pool.get_ref())
+        .await
+    {
+        Ok(rows) => {
+            let mut counts = AttendanceCounts::default();
+            for row in &rows {
+                counts.add(&row.counts);
+            }
+            log::info!("Successfully summarized attendance of {} students in group {}", rows.len(), id);
+            HttpResponse::Ok().json(GroupSummary {
+                group_id: *id,
+                percentage: counts.percentage(),
+                counts,
+                students: rows.into_iter().map(StudentSummary::from).collect(),
+            })
+        },
+        Err(e) => database_error("Failed to summarize attendance", e),
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    get_attendance, mark_attendance, delete_attendance, mark_group_attendance, get_student_summary,
+    get_group_summary,
+))]
+pub(crate) struct ApiDoc;
+
+pub(crate) fn configure_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/attendance")
+            .route("", web::get().to(get_attendance))
+            .route("", web::put().to(mark_attendance))
+            .route("", web::delete().to(delete_attendance))
+            .route("/bulk", web::post().to(mark_group_attendance))
+            .route("/summary/students/{id}", web::get().to(get_student_summary))
+            .route("/summary/groups/{id}", web::get().to(get_group_summary))
+    );
+}
@@ -0,0 +1,103 @@
+use actix_web::{web, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use sqlx::{PgConnection, PgPool};
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+use crate::openapi::{BadRequest, ServerError};
+use crate::routes::database_error;
+
+/// Most entries returned by one request when no limit is given.
+const DEFAULT_LIMIT: i64 = 100;
+const MAX_LIMIT: i64 = 1000;
+
+#[derive(Serialize, sqlx::FromRow, ToSchema)]
+struct AuditEntry {
+    id: i32,
+    action: String,
+    entity_type: String,
+    entity_id: i32,
+    #[schema(value_type = Object)]
+    details: Json<serde_json::Value>,
+    recorded_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct AuditFilter {
+    action: Option<String>,
+    entity_type: Option<String>,
+    entity_id: Option<i32>,
+    /// At most 1000, 100 by default.
+    limit: Option<i64>,
+}
+
+/// Appends an entry to the audit trail as part of the caller's transaction,
+/// so the entry exists exactly when the change it describes does.
+pub(crate) async fn record(
+    conn: &mut PgConnection,
+    action: &str,
+    entity_type: &str,
+    entity_id: i32,
+    details: serde_json::Value,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO audit_log (action, entity_type, entity_id, details) VALUES ($1, $2, $3, $4)")
+        .bind(action)
+        .bind(entity_type)
+        .bind(entity_id)
+        .bind(Json(details))
+        .execute(conn)
+        .await
+        .map(|_| ())
+}
+
+#[utoipa::path(
+    get,
+    path = "/audit",
+    tag = "audit",
+    params(AuditFilter),
+    responses(
+        (status = 200, description = "Most recent entries first", body = Vec<AuditEntry>),
+        (status = 400, response = BadRequest),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn get_audit_log(filter: web::Query<AuditFilter>, pool: web::Data<PgPool>) -> impl Responder {
+    log::debug!("Fetching audit log");
+    let limit = filter.limit.unwrap_or(DEFAULT_LIMIT);
+    if !(1..=MAX_LIMIT).contains(&limit) {
+        return HttpResponse::BadRequest().body(format!("Limit must be between 1 and {MAX_LIMIT}"));
+    }
+
+    match sqlx::query_as::<_, AuditEntry>(
+        "SELECT id, action, entity_type, entity_id, details, recorded_at FROM audit_log
+         WHERE ($1::VARCHAR IS NULL OR action = $1)
+           AND ($2::VARCHAR IS NULL OR entity_type = $2)
+           AND ($3::INT IS NULL OR entity_id = $3)
+         ORDER BY id DESC LIMIT $4"
+    )
+        .bind(filter.action.as_deref())
+        .bind(filter.entity_type.as_deref())
+        .bind(filter.entity_id)
+        .bind(limit)
+        .fetch_all(pool.get_ref())
+        .await
+    {
+        Ok(entries) => {
+            log::info!("Successfully retrieved {} audit entries", entries.len());
+            HttpResponse::Ok().json(entries)
+        },
+        Err(e) => database_error("Failed to fetch audit log", e),
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    get_audit_log,
+))]
+pub(crate) struct ApiDoc;
+
+pub(crate) fn configure_routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/audit", web::get().to(get_audit_log));
+}
@@ -0,0 +1,342 @@
+use actix_web::web;
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
+
+use crate::groups::GroupStore;
+use crate::routes::GroupFilter;
+use crate::storage::{Group, IMAGES_PATH};
+
+const FORMAT_VERSION: u32 = 1;
+const MANIFEST_PATH: &str = "manifest.json";
+/// Groups are stored here whichever store they come from, so archives can be
+/// restored into either.
+const GROUPS_PATH: &str = "mongo/groups.jsonl";
+const IMAGES_PREFIX: &str = "images/";
+/// Rows are handed to the archive in chunks of about this many bytes.
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Postgres tables in restore order, and whether their `id` is backed by a sequence.
+/// Webhook deliveries are left out: restored, the ones still pending would be
+/// delivered a second time.
+const BACKUP_TABLES: &[(&str, bool)] = &[
+    ("students_blob", true),
+    ("students_fs", true),
+    ("subjects", true),
+    ("teachers", true),
+    ("rooms", true),
+    ("schedule_slots", true),
+    ("attendance", false),
+    ("assessments", true),
+    ("scores", false),
+    ("student_status_history", true),
+    ("audit_log", true),
+    ("webhook_subscriptions", true),
+    ("sagas", true),
+];
+
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    format_version: u32,
+    created_at: DateTime<Utc>,
+    /// Row count per Postgres table.
+    tables: BTreeMap<String, usize>,
+    groups: usize,
+    images: usize,
+    /// SHA-256 of every archive entry except the manifest itself.
+    checksums: BTreeMap<String, String>,
+}
+
+fn table_path(table: &str) -> String {
+    format!("postgres/{table}.jsonl")
+}
+
+/// Wraps the archive so every entry is hashed while it is written.
+struct BackupWriter {
+    archive: ZipWriter<File>,
+    checksums: BTreeMap<String, String>,
+    current: Option<(String, Sha256)>,
+}
+
+impl BackupWriter {
+    fn start_entry(&mut self, name: &str) -> Result<(), String> {
+        self.finish_entry();
+        self.archive.start_file(name, SimpleFileOptions::default()).map_err(|e| e.to_string())?;
+        self.current = Some((name.to_string(), Sha256::new()));
+        Ok(())
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), String> {
+        if let Some((_, hasher)) = &mut self.current {
+            hasher.update(data);
+        }
+        self.archive.write_all(data).map_err(|e| e.to_string())
+    }
+
+    fn finish_entry(&mut self) {
+        if let Some((name, hasher)) = self.current.take() {
+            self.checksums.insert(name, hex::encode(hasher.finalize()));
+        }
+    }
+}
+
+/// Runs `write` on a blocking thread, like the export archive is written.
+async fn write_backup(
+    mut writer: BackupWriter,
+    write: impl FnOnce(&mut BackupWriter) -> Result<(), String> + Send + 'static
+) -> Result<BackupWriter, String> {
+    web::block(move || write(&mut writer).map(|()| writer)).await.map_err(|e| e.to_string())?
+}
+
+/// Runs `read` on a blocking thread and hands the archive back with its result.
+async fn read_archive<T: Send + 'static>(
+    mut archive: ZipArchive<File>,
+    read: impl FnOnce(&mut ZipArchive<File>) -> Result<T, String> + Send + 'static
+) -> Result<(ZipArchive<File>, T), String> {
+    web::block(move || read(&mut archive).map(|value| (archive, value))).await.map_err(|e| e.to_string())?
+}
+
+pub(crate) async fn create_backup(
+    pool: &PgPool,
+    group_store: &GroupStore,
+    archive_path: &Path
+) -> Result<(), String> {
+    log::info!("Creating backup at {}", archive_path.display());
+    let path = archive_path.to_path_buf();
+    let file = web::block(move || File::create(path))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("Failed to create archive: {e}"))?;
+    let mut writer = BackupWriter {
+        archive: ZipWriter::new(file),
+        checksums: BTreeMap::new(),
+        current: None,
+    };
+    let mut tables = BTreeMap::new();
+
+    // A single repeatable-read snapshot keeps both student tables consistent with each other.
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+    sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    for (table, _) in BACKUP_TABLES {
+        writer = write_backup(writer, move |writer| writer.start_entry(&table_path(table))).await?;
+        let query = format!("SELECT row_to_json(t)::TEXT FROM {table} t");
+        let mut rows = sqlx::query_scalar::<_, String>(&query).fetch(&mut *tx);
+        let mut count = 0;
+        let mut chunk = Vec::new();
+        while let Some(row) = rows.try_next().await.map_err(|e| e.to_string())? {
+            chunk.extend_from_slice(row.as_bytes());
+            chunk.push(b'\n');
+            count += 1;
+            if chunk.len() >= CHUNK_SIZE {
+                let data = std::mem::take(&mut chunk);
+                writer = write_backup(writer, move |writer| writer.write(&data)).await?;
+            }
+        }
+        writer = write_backup(writer, move |writer| writer.write(&chunk)).await?;
+        log::info!("Backed up {} rows of {}", count, table);
+        tables.insert(table.to_string(), count);
+    }
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    let groups = group_store.find_all(&GroupFilter::default(), None).await.map_err(|e| e.to_string())?;
+    let mut lines = Vec::new();
+    for group in &groups {
+        serde_json::to_writer(&mut lines, group).map_err(|e| e.to_string())?;
+        lines.push(b'\n');
+    }
+    writer = write_backup(writer, move |writer| {
+        writer.start_entry(GROUPS_PATH)?;
+        writer.write(&lines)
+    }).await?;
+    let groups = groups.len();
+    log::info!("Backed up {} groups", groups);
+
+    let mut images = 0;
+    if tokio::fs::metadata(&*IMAGES_PATH).await.is_ok_and(|metadata| metadata.is_dir()) {
+        let mut entries = tokio::fs::read_dir(&*IMAGES_PATH).await.map_err(|e| e.to_string())?;
+        while let Some(entry) = entries.next_entry().await.map_err(|e| e.to_string())? {
+            if !entry.file_type().await.map_err(|e| e.to_string())?.is_file() {
+                continue;
+            }
+            let data = tokio::fs::read(entry.path()).await.map_err(|e| e.to_string())?;
+            let name = format!("{}{}", IMAGES_PREFIX, entry.file_name().to_string_lossy());
+            writer = write_backup(writer, move |writer| {
+                writer.start_entry(&name)?;
+                writer.write(&data)
+            }).await?;
+            images += 1;
+        }
+    }
+    log::info!("Backed up {} image files", images);
+
+    web::block(move || {
+        writer.finish_entry();
+        let manifest = Manifest {
+            format_version: FORMAT_VERSION,
+            created_at: Utc::now(),
+            tables,
+            groups,
+            images,
+            checksums: writer.checksums,
+        };
+        let mut archive = writer.archive;
+        archive.start_file(MANIFEST_PATH, SimpleFileOptions::default()).map_err(|e| e.to_string())?;
+        serde_json::to_writer_pretty(&mut archive, &manifest).map_err(|e| e.to_string())?;
+        archive.finish().map(drop).map_err(|e| e.to_string())
+    }).await.map_err(|e| e.to_string())??;
+
+    log::info!("Backup completed: {}", archive_path.display());
+    Ok(())
+}
+
+fn read_entry(archive: &mut ZipArchive<File>, name: &str) -> Result<Vec<u8>, String> {
+    let mut entry = archive.by_name(name).map_err(|e| format!("Missing archive entry {name}: {e}"))?;
+    let mut data = Vec::new();
+    entry.read_to_end(&mut data).map_err(|e| e.to_string())?;
+    Ok(data)
+}
+
+fn read_manifest(archive: &mut ZipArchive<File>) -> Result<Manifest, String> {
+    let manifest: Manifest = serde_json::from_slice(&read_entry(archive, MANIFEST_PATH)?)
+        .map_err(|e| format!("Invalid manifest: {e}"))?;
+    if manifest.format_version != FORMAT_VERSION {
+        return Err(format!(
+            "Unsupported backup format version {} (expected {})",
+            manifest.format_version, FORMAT_VERSION
+        ));
+    }
+
+    let names: Vec<String> = archive.file_names().map(str::to_string).collect();
+    for name in names.iter().filter(|name| name.as_str() != MANIFEST_PATH) {
+        if !manifest.checksums.contains_key(name) {
+            return Err(format!("Archive entry {name} is not listed in the manifest"));
+        }
+    }
+    for (name, expected) in &manifest.checksums {
+        let actual = hex::encode(Sha256::digest(read_entry(archive, name)?));
+        if &actual != expected {
+            return Err(format!("Checksum mismatch for {name}"));
+        }
+    }
+    Ok(manifest)
+}
+
+fn read_lines(archive: &mut ZipArchive<File>, name: &str) -> Result<Vec<String>, String> {
+    let entry = archive.by_name(name).map_err(|e| format!("Missing archive entry {name}: {e}"))?;
+    BufReader::new(entry)
+        .lines()
+        .filter(|line| !matches!(line, Ok(line) if line.is_empty()))
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())
+}
+
+/// Points `image_path` at the configured images directory, which may differ
+/// from the one the backup was taken on.
+fn relocate_image_path(row: &str) -> Result<String, String> {
+    let mut value: serde_json::Value = serde_json::from_str(row).map_err(|e| e.to_string())?;
+    if let Some(serde_json::Value::String(path)) = value.get_mut("image_path") {
+        if let Some(file_name) = Path::new(path.as_str()).file_name() {
+            *path = IMAGES_PATH.join(file_name).to_string_lossy().into_owned();
+        }
+    }
+    Ok(value.to_string())
+}
+
+/// Restores an archive made by [`create_backup`]. Rows, groups and images that
+/// already exist are left untouched, so running it twice is harmless.
+pub(crate) async fn restore_backup(
+    pool: &PgPool,
+    group_store: &GroupStore,
+    archive_path: &Path
+) -> Result<(), String> {
+    log::info!("Restoring backup from {}", archive_path.display());
+    let path = archive_path.to_path_buf();
+    let (archive, manifest) = web::block(move || {
+        let file = File::open(path).map_err(|e| format!("Failed to open archive: {e}"))?;
+        let mut archive = ZipArchive::new(file).map_err(|e| format!("Invalid archive: {e}"))?;
+        let manifest = read_manifest(&mut archive)?;
+        Ok::<_, String>((archive, manifest))
+    }).await.map_err(|e| e.to_string())??;
+    log::info!("Verified backup from {} ({} entries)", manifest.created_at, manifest.checksums.len());
+
+    // Groups go first: in Postgres, students, classes and assessments refer to them.
+    let (mut archive, lines) = read_archive(archive, |archive| read_lines(archive, GROUPS_PATH)).await?;
+    let groups = lines
+        .iter()
+        .map(|line| serde_json::from_str::<Group>(line).map_err(|e| format!("Invalid group entry: {e}")))
+        .collect::<Result<Vec<_>, _>>()?;
+    let restored = group_store.insert_missing(&groups).await.map_err(|e| e.to_string())?;
+    log::info!("Restored {} new groups", restored);
+
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+    for (table, serial_id) in BACKUP_TABLES {
+        if !manifest.tables.contains_key(*table) {
+            log::warn!("Backup has no data for table {}, skipping", table);
+            continue;
+        }
+        let insert = format!(
+            "INSERT INTO {table} SELECT * FROM json_populate_record(NULL::{table}, $1::json) ON CONFLICT DO NOTHING"
+        );
+        let rows;
+        (archive, rows) = read_archive(archive, move |archive| read_lines(archive, &table_path(table))).await?;
+        let mut restored = 0;
+        for row in rows {
+            let row = match *table {
+                "students_fs" => relocate_image_path(&row)?,
+                _ => row,
+            };
+            let result = sqlx::query(&insert)
+                .bind(row)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| format!("Failed to restore {table}: {e}"))?;
+            restored += result.rows_affected();
+        }
+        if *serial_id {
+            sqlx::query(&format!(
+                "SELECT setval(pg_get_serial_sequence('{table}', 'id'), COALESCE(MAX(id), 0) + 1, false) FROM {table}"
+            ))
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        log::info!("Restored {} new rows into {}", restored, table);
+    }
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    tokio::fs::create_dir_all(&*IMAGES_PATH).await.map_err(|e| e.to_string())?;
+    let image_entries: Vec<String> = manifest.checksums.keys()
+        .filter(|name| name.starts_with(IMAGES_PREFIX))
+        .cloned()
+        .collect();
+    let mut restored = 0;
+    for name in image_entries {
+        let Some(file_name) = Path::new(&name[IMAGES_PREFIX.len()..]).file_name() else {
+            continue;
+        };
+        let target = IMAGES_PATH.join(file_name);
+        if tokio::fs::try_exists(&target).await.map_err(|e| e.to_string())? {
+            continue;
+        }
+        let data;
+        (archive, data) = read_archive(archive, move |archive| read_entry(archive, &name)).await?;
+        tokio::fs::write(&target, data).await.map_err(|e| e.to_string())?;
+        restored += 1;
+    }
+    log::info!("Restored {} new image files", restored);
+
+    log::info!("Restore completed");
+    Ok(())
+}
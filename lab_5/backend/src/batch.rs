@@ -0,0 +1,385 @@
+use actix_web::{web, HttpResponse, Responder};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::{Acquire, PgConnection, PgPool};
+use utoipa::{OpenApi, ToSchema};
+
+use std::collections::HashMap;
+
+use crate::events::{self, Event, EventKind};
+use crate::groups::GroupStore;
+use crate::openapi::ServerError;
+use crate::profile::{self, StudentProfile};
+use crate::translit::transliterate;
+use crate::routes::{database_error, error_parts, present, store_error, Field, GroupFilter, StudentForm};
+use crate::saga::{self, PendingSaga, Saga};
+use crate::storage::{group_occupancy, student_group, Group, StorageType, STORAGE_TYPE, STUDENT_TABLE_NAME};
+use crate::students::{bind_student_form, STUDENT_FORM_ASSIGNMENTS};
+use crate::webhooks;
+
+const MAX_BATCH_SIZE: usize = 1000;
+
+#[derive(Deserialize, ToSchema)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum BatchOperation {
+    Create {
+        name: String,
+        surname: String,
+        group_id: i32,
+        #[serde(flatten)]
+        profile: StudentProfile,
+    },
+    Update {
+        id: i32,
+        name: String,
+        surname: String,
+        group_id: i32,
+        #[serde(flatten)]
+        profile: ProfileUpdate,
+    },
+    Delete { id: i32 },
+    Transfer { id: i32, group_id: i32 },
+}
+
+/// Profile fields of an update. Those left out keep their value, those sent as
+/// `null` are cleared.
+#[derive(Deserialize, ToSchema)]
+struct ProfileUpdate {
+    #[serde(default, deserialize_with = "present")]
+    #[schema(value_type = Option<String>)]
+    patronymic: Field<String>,
+    #[serde(default, deserialize_with = "present")]
+    #[schema(value_type = Option<String>)]
+    email: Field<String>,
+    #[serde(default, deserialize_with = "present")]
+    #[schema(value_type = Option<String>)]
+    phone: Field<String>,
+    #[serde(default, deserialize_with = "present")]
+    #[schema(value_type = Option<NaiveDate>)]
+    birth_date: Field<NaiveDate>,
+    #[serde(default, deserialize_with = "present")]
+    #[schema(value_type = Option<String>)]
+    record_book_number: Field<String>,
+}
+
+impl ProfileUpdate {
+    /// The new profile and the columns of the fields that were left out.
+    fn split(&self) -> (StudentProfile, Vec<&'static str>) {
+        let sent = [
+            self.patronymic.is_some(),
+            self.email.is_some(),
+            self.phone.is_some(),
+            self.birth_date.is_some(),
+            self.record_book_number.is_some(),
+        ];
+        let profile = StudentProfile {
+            patronymic: self.patronymic.clone().flatten(),
+            email: self.email.clone().flatten(),
+            phone: self.phone.clone().flatten(),
+            birth_date: self.birth_date.flatten(),
+            record_book_number: self.record_book_number.clone().flatten(),
+        };
+        (profile, profile::unsent_columns(sent))
+    }
+}
+
+impl BatchOperation {
+    fn name(&self) -> &'static str {
+        match self {
+            BatchOperation::Create { .. } => "create",
+            BatchOperation::Update { .. } => "update",
+            BatchOperation::Delete { .. } => "delete",
+            BatchOperation::Transfer { .. } => "transfer",
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct BatchRequest {
+    #[serde(default)]
+    continue_on_error: bool,
+    operations: Vec<BatchOperation>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+enum ItemStatus {
+    Ok,
+    Failed,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ItemResult {
+    index: usize,
+    op: &'static str,
+    status: ItemStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct BatchResponse {
+    committed: bool,
+    results: Vec<ItemResult>,
+}
+
+/// Message of a failed operation. Constraint violations are explained like
+/// [`database_error`] does; other database errors are only logged.
+fn operation_error(context: &str, e: sqlx::Error) -> String {
+    match error_parts(database_error(context, e)) {
+        (status, _) if status.is_server_error() => format!("{context}: database error"),
+        (_, message) => message,
+    }
+}
+
+fn validate_names(name: &str, surname: &str) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("Name must not be empty".to_string());
+    }
+    if surname.trim().is_empty() {
+        return Err("Surname must not be empty".to_string());
+    }
+    Ok(())
+}
+
+async fn ensure_group_accepts(
+    conn: &mut PgConnection,
+    groups: &HashMap<i32, Group>,
+    group_id: i32,
+    student_id: Option<i32>
+) -> Result<(), String> {
+    let group = groups.get(&group_id).ok_or(format!("Group {group_id} does not exist"))?;
+    if let Some(max_capacity) = group.max_capacity {
+        let (count, already_member) = group_occupancy(&mut *conn, group_id, student_id)
+            .await
+            .map_err(|e| operation_error("Failed to check group capacity", e))?;
+        if !already_member && count >= i64::from(max_capacity) {
+            return Err(format!("Group {} is at full capacity ({} students)", group.name, max_capacity));
+        }
+    }
+    Ok(())
+}
+
+async fn apply_operation(
+    conn: &mut PgConnection,
+    groups: &HashMap<i32, Group>,
+    operation: &BatchOperation
+) -> Result<(i32, Vec<PendingSaga>, Event), String> {
+    match operation {
+        BatchOperation::Create { name, surname, group_id, profile } => {
+            validate_names(name, surname)?;
+            let profile = profile.clone().normalize()?;
+            ensure_group_accepts(conn, groups, *group_id, None).await?;
+            let id: i32 = sqlx::query_scalar(&format!(
+                "INSERT INTO {} (name, surname, group_id, patronymic, email, phone, birth_date, record_book_number,
+                 name_latin, surname_latin)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) RETURNING id",
+                *STUDENT_TABLE_NAME
+            ))
+                .bind(name)
+                .bind(surname)
+                .bind(group_id)
+                .bind(&profile.patronymic)
+                .bind(&profile.email)
+                .bind(&profile.phone)
+                .bind(profile.birth_date)
+                .bind(&profile.record_book_number)
+                .bind(transliterate(name))
+                .bind(transliterate(surname))
+                .fetch_one(&mut *conn)
+                .await
+                .map_err(|e| operation_error("Failed to create student", e))?;
+            Ok((id, Vec::new(), Event::student(EventKind::StudentCreated, id, [Some(*group_id)])))
+        },
+        BatchOperation::Update { id, name, surname, group_id, profile } => {
+            validate_names(name, surname)?;
+            let (profile, kept_fields) = profile.split();
+            let form = StudentForm {
+                name: name.clone(),
+                surname: surname.clone(),
+                group_id: Some(*group_id),
+                profile: profile.normalize()?,
+                kept_fields,
+                ..Default::default()
+            };
+            ensure_group_accepts(conn, groups, *group_id, Some(*id)).await?;
+            let previous_group = student_group(&mut *conn, *id)
+                .await
+                .map_err(|e| operation_error("Failed to update student", e))?
+                .flatten();
+            let result = bind_student_form(sqlx::query(&format!(
+                "UPDATE {} SET {STUDENT_FORM_ASSIGNMENTS} WHERE id = $12",
+                *STUDENT_TABLE_NAME
+            )), &form)
+                .bind(&form.kept_fields)
+                .bind(id)
+                .execute(&mut *conn)
+                .await
+                .map_err(|e| operation_error("Failed to update student", e))?;
+            match result.rows_affected() {
+                0 => Err(format!("Student {id} not found")),
+                _ => Ok((
+                    *id,
+                    release_leader(conn, *id).await?,
+                    Event::student(EventKind::StudentUpdated, *id, [previous_group, Some(*group_id)]),
+                )),
+            }
+        },
+        BatchOperation::Transfer { id, group_id } => {
+            ensure_group_accepts(conn, groups, *group_id, Some(*id)).await?;
+            let previous_group = student_group(&mut *conn, *id)
+                .await
+                .map_err(|e| operation_error("Failed to transfer student", e))?
+                .flatten();
+            let result = sqlx::query(&format!("UPDATE {} SET group_id = $1 WHERE id = $2", *STUDENT_TABLE_NAME))
+                .bind(group_id)
+                .bind(id)
+                .execute(&mut *conn)
+                .await
+                .map_err(|e| operation_error("Failed to transfer student", e))?;
+            match result.rows_affected() {
+                0 => Err(format!("Student {id} not found")),
+                _ => Ok((
+                    *id,
+                    release_leader(conn, *id).await?,
+                    Event::student(EventKind::StudentUpdated, *id, [previous_group, Some(*group_id)]),
+                )),
+            }
+        },
+        BatchOperation::Delete { id } => {
+            let image_column = match *STORAGE_TYPE {
+                StorageType::Blob => "NULL::VARCHAR",
+                StorageType::Filesystem => "image_path",
+            };
+            let deleted: Option<(Option<String>, Option<i32>)> = sqlx::query_as(&format!(
+                "DELETE FROM {} WHERE id = $1 RETURNING {}, group_id",
+                *STUDENT_TABLE_NAME, image_column
+            ))
+                .bind(id)
+                .fetch_optional(&mut *conn)
+                .await
+                .map_err(|e| operation_error("Failed to delete student", e))?;
+            match deleted {
+                None => Err(format!("Student {id} not found")),
+                Some((image_path, previous_group)) => {
+                    let mut sagas = vec![Saga::ReleaseLeader { student_id: *id }];
+                    if let Some(path) = image_path {
+                        sagas.push(Saga::ReplaceImage { new_path: None, old_path: Some(path) });
+                    }
+                    let sagas = saga::begin_all(conn, sagas)
+                        .await
+                        .map_err(|e| operation_error("Failed to delete student", e))?;
+                    Ok((*id, sagas, Event::student(EventKind::StudentDeleted, *id, [previous_group])))
+                },
+            }
+        },
+    }
+}
+
+/// Records that the student may no longer lead a group other than their own.
+async fn release_leader(conn: &mut PgConnection, id: i32) -> Result<Vec<PendingSaga>, String> {
+    saga::begin_all(conn, vec![Saga::ReleaseLeader { student_id: id }])
+        .await
+        .map_err(|e| operation_error("Failed to release group leadership", e))
+}
+
+/// Runs create, update, delete and transfer operations in a single Postgres
+/// transaction. By default the whole batch is rolled back on the first failure;
+/// with `continue_on_error` every operation gets its own savepoint instead.
+#[utoipa::path(
+    post,
+    path = "/students/batch",
+    tag = "students",
+    request_body = BatchRequest,
+    responses(
+        (status = 200, description = "Every operation succeeded, or failures were skipped with `continue_on_error`", body = BatchResponse),
+        (status = 400, description = "An operation failed and the batch was rolled back", body = BatchResponse),
+        (status = 500, response = ServerError)
+    )
+)]
+pub(crate) async fn execute_batch(
+    batch: web::Json<BatchRequest>,
+    pool: web::Data<PgPool>,
+    group_store: web::Data<GroupStore>
+) -> impl Responder {
+    log::debug!("Processing batch of {} student operations", batch.operations.len());
+    if batch.operations.len() > MAX_BATCH_SIZE {
+        return HttpResponse::BadRequest()
+            .body(format!("A batch may contain at most {} operations", MAX_BATCH_SIZE));
+    }
+
+    let groups: HashMap<i32, Group> = match group_store.find_all(&GroupFilter::default(), None).await {
+        Ok(groups) => groups.into_iter().map(|group| (group.id, group)).collect(),
+        Err(e) => return store_error("Failed to fetch groups", e),
+    };
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return database_error("Failed to start transaction", e),
+    };
+
+    let mut results = Vec::with_capacity(batch.operations.len());
+    let mut sagas = Vec::new();
+    let mut pending_events = Vec::new();
+    for (index, operation) in batch.operations.iter().enumerate() {
+        let outcome = if batch.continue_on_error {
+            let mut savepoint = match tx.begin().await {
+                Ok(savepoint) => savepoint,
+                Err(e) => return database_error("Failed to create savepoint", e),
+            };
+            match apply_operation(&mut savepoint, &groups, operation).await {
+                Ok(applied) => savepoint.commit()
+                    .await
+                    .map(|_| applied)
+                    .map_err(|e| operation_error("Failed to apply operation", e)),
+                Err(e) => {
+                    if let Err(rollback_err) = savepoint.rollback().await {
+                        log::error!("Failed to rollback savepoint: {}", rollback_err);
+                    }
+                    Err(e)
+                }
+            }
+        } else {
+            apply_operation(&mut tx, &groups, operation).await
+        };
+
+        match outcome {
+            Ok((id, operation_sagas, event)) => {
+                sagas.extend(operation_sagas);
+                pending_events.push(event);
+                results.push(ItemResult { index, op: operation.name(), status: ItemStatus::Ok, id: Some(id), error: None });
+            },
+            Err(e) => {
+                log::warn!("Batch operation {} ({}) failed: {}", index, operation.name(), e);
+                results.push(ItemResult { index, op: operation.name(), status: ItemStatus::Failed, id: None, error: Some(e) });
+                if !batch.continue_on_error {
+                    if let Err(rollback_err) = tx.rollback().await {
+                        log::error!("Failed to rollback transaction: {}", rollback_err);
+                    }
+                    return HttpResponse::BadRequest().json(BatchResponse { committed: false, results });
+                }
+            }
+        }
+    }
+
+    if let Err(e) = webhooks::enqueue(&mut *tx, &pending_events).await {
+        if let Err(rollback_err) = tx.rollback().await {
+            log::error!("Failed to rollback transaction: {}", rollback_err);
+        }
+        return database_error("Failed to queue webhooks", e);
+    }
+    if let Err(e) = tx.commit().await {
+        return database_error("Failed to commit transaction", e);
+    }
+    saga::finish_all(sagas, pool.get_ref(), group_store.get_ref()).await;
+    pending_events.into_iter().for_each(events::publish);
+
+    log::info!("Successfully executed batch of {} student operations", results.len());
+    HttpResponse::Ok().json(BatchResponse { committed: true, results })
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(execute_batch))]
+pub(crate) struct ApiDoc;
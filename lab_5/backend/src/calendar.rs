@@ -0,0 +1,238 @@
+use actix_web::{web, HttpResponse, Responder};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Utc};
+use lazy_static::lazy_static;
+use sqlx::PgPool;
+use utoipa::OpenApi;
+
+use crate::groups::GroupStore;
+use crate::openapi::{NotFound, ServerError};
+use crate::routes::database_error;
+use crate::storage::{Group, STUDENT_TABLE_NAME};
+use crate::schedule::{fetch_group_schedule, ScheduleEntry};
+
+const PRODUCT_ID: &str = "-//lab_5//Student Management System//UK";
+/// RFC 5545 limits content lines to 75 octets, excluding the line break.
+const MAX_LINE_OCTETS: usize = 75;
+
+pub(crate) struct Semester {
+    pub(crate) start: NaiveDate,
+    pub(crate) end: NaiveDate,
+}
+
+impl Semester {
+    /// Monday of the first week of the semester, which is always an odd week.
+    pub(crate) fn first_monday(&self) -> NaiveDate {
+        self.start - Duration::days(i64::from(self.start.weekday().num_days_from_monday()))
+    }
+
+    /// 1-based week of the semester that `date` falls into.
+    pub(crate) fn week_of(&self, date: NaiveDate) -> Option<u32> {
+        if date < self.start || date > self.end {
+            return None;
+        }
+        Some(((date - self.first_monday()).num_days() / 7) as u32 + 1)
+    }
+}
+
+fn parse_semester() -> Result<Semester, String> {
+    let parse = |name: &str| {
+        let value = std::env::var(name).map_err(|_| format!("{name} must be set"))?;
+        NaiveDate::parse_from_str(&value, "%Y-%m-%d").map_err(|e| format!("{name} is not a valid date: {e}"))
+    };
+    let semester = Semester { start: parse("SEMESTER_START")?, end: parse("SEMESTER_END")? };
+    match semester.start <= semester.end {
+        true => Ok(semester),
+        false => Err("SEMESTER_START must not be after SEMESTER_END".to_string()),
+    }
+}
+
+lazy_static! {
+    pub(crate) static ref SEMESTER: Result<Semester, String> = parse_semester();
+}
+
+fn escape_text(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Appends a content line, folding it so no physical line exceeds 75 octets.
+fn push_line(calendar: &mut String, line: &str) {
+    let mut octets = 0;
+    for character in line.chars() {
+        let width = character.len_utf8();
+        if octets + width > MAX_LINE_OCTETS {
+            calendar.push_str("\r\n ");
+            octets = 1;
+        }
+        calendar.push(character);
+        octets += width;
+    }
+    calendar.push_str("\r\n");
+}
+
+fn format_local(date_time: NaiveDateTime) -> String {
+    date_time.format("%Y%m%dT%H%M%S").to_string()
+}
+
+/// First date on which a slot takes place, honouring its weekday and week parity.
+fn first_occurrence(semester: &Semester, entry: &ScheduleEntry) -> NaiveDate {
+    let step = match entry.week_parity.as_str() {
+        "both" => 7,
+        _ => 14,
+    };
+    let mut date = semester.first_monday() + Duration::days(i64::from(entry.weekday) - 1);
+    if entry.week_parity == "even" {
+        date += Duration::days(7);
+    }
+    while date < semester.start {
+        date += Duration::days(step);
+    }
+    date
+}
+
+fn render_calendar(semester: &Semester, name: &str, entries: &[ScheduleEntry]) -> String {
+    let mut calendar = String::new();
+    push_line(&mut calendar, "BEGIN:VCALENDAR");
+    push_line(&mut calendar, "VERSION:2.0");
+    push_line(&mut calendar, &format!("PRODID:{PRODUCT_ID}"));
+    push_line(&mut calendar, "CALSCALE:GREGORIAN");
+    push_line(&mut calendar, "METHOD:PUBLISH");
+    push_line(&mut calendar, &format!("X-WR-CALNAME:{}", escape_text(name)));
+
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    let until = format_local(semester.end.and_hms_opt(23, 59, 59).expect("valid time"));
+    for entry in entries {
+        let date = first_occurrence(semester, entry);
+        if date > semester.end {
+            continue;
+        }
+        let interval = match entry.week_parity.as_str() {
+            "both" => 1,
+            _ => 2,
+        };
+        push_line(&mut calendar, "BEGIN:VEVENT");
+        push_line(&mut calendar, &format!("UID:schedule-slot-{}@lab_5", entry.id));
+        push_line(&mut calendar, &format!("DTSTAMP:{stamp}"));
+        push_line(&mut calendar, &format!("DTSTART:{}", format_local(date.and_time(entry.start_time))));
+        push_line(&mut calendar, &format!("DTEND:{}", format_local(date.and_time(entry.end_time))));
+        push_line(&mut calendar, &format!("RRULE:FREQ=WEEKLY;INTERVAL={interval};UNTIL={until}"));
+        push_line(&mut calendar, &format!("SUMMARY:{}", escape_text(&entry.subject_name)));
+        push_line(&mut calendar, &format!("LOCATION:{}", escape_text(&entry.room_name)));
+        push_line(&mut calendar, &format!(
+            "DESCRIPTION:{}",
+            escape_text(&format!("{} {}", entry.teacher_name, entry.teacher_surname))
+        ));
+        push_line(&mut calendar, "END:VEVENT");
+    }
+
+    push_line(&mut calendar, "END:VCALENDAR");
+    calendar
+}
+
+async fn calendar_response(pool: &PgPool, group: &Group) -> HttpResponse {
+    let semester = match SEMESTER.as_ref() {
+        Ok(semester) => semester,
+        Err(e) => {
+            log::error!("Semester is not configured: {}", e);
+            return HttpResponse::InternalServerError().body(format!("Semester is not configured: {e}"));
+        }
+    };
+
+    match fetch_group_schedule(pool, group.id, None).await {
+        Ok(entries) => {
+            log::info!("Successfully rendered {} schedule entries of group id: {}", entries.len(), group.id);
+            HttpResponse::Ok()
+                .content_type("text/calendar; charset=utf-8")
+                .body(render_calendar(semester, &group.name, &entries))
+        },
+        Err(e) => database_error("Failed to fetch group schedule", e),
+    }
+}
+
+async fn find_group(group_store: &GroupStore, group_id: i32) -> Result<Option<Group>, HttpResponse> {
+    group_store.find(group_id).await.map_err(|e| {
+        log::error!("Failed to fetch group: {}", e);
+        HttpResponse::InternalServerError().body(e.to_string())
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/groups/{id}/schedule.ics",
+    tag = "schedule",
+    params(("id" = i32, Path, description = "Group id")),
+    responses(
+        (status = 200, description = "iCalendar feed with a weekly event per class for the whole semester", content_type = "text/calendar", body = String),
+        (status = 404, response = NotFound),
+        (status = 500, response = ServerError)
+    )
+)]
+pub(crate) async fn get_group_calendar(
+    id: web::Path<i32>,
+    pool: web::Data<PgPool>,
+    group_store: web::Data<GroupStore>
+) -> impl Responder {
+    log::debug!("Rendering calendar of group id: {}", id);
+    match find_group(group_store.get_ref(), *id).await {
+        Ok(Some(group)) => calendar_response(pool.get_ref(), &group).await,
+        Ok(None) => {
+            log::debug!("Group not found with id: {}", id);
+            HttpResponse::NotFound().finish()
+        },
+        Err(response) => response,
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/students/{id}/schedule.ics",
+    tag = "schedule",
+    params(("id" = i32, Path, description = "Student id")),
+    responses(
+        (status = 200, description = "iCalendar feed with a weekly event per class for the whole semester", content_type = "text/calendar", body = String),
+        (status = 404, description = "The student does not exist or is not a member of any group"),
+        (status = 500, response = ServerError)
+    )
+)]
+pub(crate) async fn get_student_calendar(
+    id: web::Path<i32>,
+    pool: web::Data<PgPool>,
+    group_store: web::Data<GroupStore>
+) -> impl Responder {
+    log::debug!("Rendering calendar of student id: {}", id);
+    let query = format!("SELECT group_id FROM {} WHERE id = $1", *STUDENT_TABLE_NAME);
+    let group_id = match sqlx::query_scalar::<_, Option<i32>>(&query)
+        .bind(id.as_ref())
+        .fetch_optional(pool.get_ref())
+        .await
+    {
+        Ok(Some(Some(group_id))) => group_id,
+        Ok(Some(None)) => {
+            log::debug!("Student {} is not a member of any group", id);
+            return HttpResponse::NotFound().body("Student is not a member of any group");
+        },
+        Ok(None) => {
+            log::debug!("Student not found with id: {}", id);
+            return HttpResponse::NotFound().finish();
+        },
+        Err(e) => return database_error("Failed to fetch student", e),
+    };
+
+    match find_group(group_store.get_ref(), group_id).await {
+        Ok(Some(group)) => calendar_response(pool.get_ref(), &group).await,
+        Ok(None) => {
+            log::debug!("Group {} of student {} not found", group_id, id);
+            HttpResponse::NotFound().finish()
+        },
+        Err(response) => response,
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    get_group_calendar, get_student_calendar,
+))]
+pub(crate) struct ApiDoc;
@@ -0,0 +1,365 @@
+use actix_web::{web, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{PgConnection, PgPool};
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+use std::collections::BTreeMap;
+
+use crate::audit;
+use crate::events::{self, Event, EventKind};
+use crate::groups::GroupStore;
+use crate::openapi::{BadRequest, NotFound, ServerError};
+use crate::routes::database_error;
+use crate::saga::{self, Saga};
+use crate::storage::{StorageType, Student, STORAGE_TYPE, STUDENT_COLUMNS, STUDENT_TABLE_NAME};
+use crate::translit::{match_key, normalize_name};
+use crate::webhooks;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct DuplicateFilter {
+    group_id: Option<i32>,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+enum MatchKind {
+    /// The names differ at most in case, spacing or apostrophes.
+    Exact,
+    /// The names only match once transliterated, e.g. "Іван" and "Ivan".
+    Transliterated,
+}
+
+#[derive(Serialize, ToSchema)]
+struct DuplicateCandidates {
+    group_id: i32,
+    match_kind: MatchKind,
+    students: Vec<Student>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct MergeRequest {
+    /// Student that is merged into the one in the path and then deleted.
+    duplicate_id: i32,
+}
+
+#[derive(Serialize, Default)]
+struct MergeSummary {
+    attendance: u64,
+    scores: u64,
+    status_history: u64,
+    photo: bool,
+}
+
+fn full_name(student: &Student) -> String {
+    normalize_name(&format!("{} {}", student.name, student.surname))
+}
+
+fn conflicts<T: PartialEq>(left: &Option<T>, right: &Option<T>) -> bool {
+    matches!((left, right), (Some(left), Some(right)) if left != right)
+}
+
+/// Two students with matching names are still different people when their
+/// patronymics, dates of birth or record book numbers are known and disagree.
+fn may_be_same_person(left: &Student, right: &Student) -> bool {
+    let patronymic_key = |student: &Student| student.profile.patronymic.as_deref().map(match_key);
+    !conflicts(&patronymic_key(left), &patronymic_key(right))
+        && !conflicts(&left.profile.birth_date, &right.profile.birth_date)
+        && !conflicts(&left.profile.record_book_number, &right.profile.record_book_number)
+}
+
+/// Clusters students of the same group whose names share a [`match_key`].
+fn find_duplicates(students: Vec<Student>) -> Vec<DuplicateCandidates> {
+    let mut by_key: BTreeMap<(i32, String, String), Vec<Vec<Student>>> = BTreeMap::new();
+    for student in students {
+        let Some(group_id) = student.group_id else { continue };
+        let clusters = by_key
+            .entry((group_id, match_key(&student.surname), match_key(&student.name)))
+            .or_default();
+        match clusters.iter_mut().find(|cluster| cluster.iter().all(|other| may_be_same_person(other, &student))) {
+            Some(cluster) => cluster.push(student),
+            None => clusters.push(vec![student]),
+        }
+    }
+
+    by_key
+        .into_iter()
+        .flat_map(|((group_id, _, _), clusters)| clusters.into_iter().map(move |cluster| (group_id, cluster)))
+        .filter(|(_, cluster)| cluster.len() > 1)
+        .map(|(group_id, students)| {
+            let name = full_name(&students[0]);
+            let match_kind = if students.iter().all(|student| full_name(student) == name) {
+                MatchKind::Exact
+            } else {
+                MatchKind::Transliterated
+            };
+            DuplicateCandidates { group_id, match_kind, students }
+        })
+        .collect()
+}
+
+#[utoipa::path(
+    get,
+    path = "/students/duplicates",
+    tag = "students",
+    params(DuplicateFilter),
+    responses(
+        (status = 200, description = "Sets of students of one group whose names match, possibly in different scripts", body = Vec<DuplicateCandidates>),
+        (status = 500, response = ServerError)
+    )
+)]
+pub(crate) async fn get_duplicates(filter: web::Query<DuplicateFilter>, pool: web::Data<PgPool>) -> impl Responder {
+    log::debug!("Searching for duplicate students");
+    let query = format!(
+        "SELECT {STUDENT_COLUMNS} FROM {} WHERE group_id IS NOT NULL AND ($1::INT IS NULL OR group_id = $1) ORDER BY id",
+        *STUDENT_TABLE_NAME
+    );
+    match sqlx::query_as::<_, Student>(&query)
+        .bind(filter.group_id)
+        .fetch_all(pool.get_ref())
+        .await
+    {
+        Ok(students) => {
+            let duplicates = find_duplicates(students);
+            log::info!("Found {} sets of duplicate students", duplicates.len());
+            HttpResponse::Ok().json(duplicates)
+        },
+        Err(e) => database_error("Failed to fetch students", e),
+    }
+}
+
+/// Copies the duplicate's photo to the kept student unless the latter already has one.
+async fn move_photo(conn: &mut PgConnection, keep_id: i32, duplicate_id: i32) -> Result<bool, sqlx::Error> {
+    let image_column = match *STORAGE_TYPE {
+        StorageType::Blob => "image_data",
+        StorageType::Filesystem => "image_path",
+    };
+    let query = format!(
+        "UPDATE {table} AS kept SET {image_column} = duplicate.{image_column}, image_type = duplicate.image_type
+         FROM {table} AS duplicate
+         WHERE kept.id = $1 AND duplicate.id = $2
+           AND kept.{image_column} IS NULL AND duplicate.{image_column} IS NOT NULL",
+        table = *STUDENT_TABLE_NAME
+    );
+    sqlx::query(&query)
+        .bind(keep_id)
+        .bind(duplicate_id)
+        .execute(conn)
+        .await
+        .map(|result| result.rows_affected() > 0)
+}
+
+/// Hands the duplicate's attendance, scores and status history over to the kept
+/// student. Where both have a record for the same class or assessment, the kept
+/// student's record wins; the rest is removed with the duplicate.
+async fn move_records(conn: &mut PgConnection, keep_id: i32, duplicate_id: i32) -> Result<MergeSummary, sqlx::Error> {
+    let attendance = sqlx::query(
+        "UPDATE attendance AS moved SET student_id = $1
+         WHERE moved.student_id = $2 AND NOT EXISTS (
+             SELECT 1 FROM attendance AS kept
+             WHERE kept.student_id = $1 AND kept.date = moved.date AND kept.slot_id = moved.slot_id
+         )"
+    )
+        .bind(keep_id)
+        .bind(duplicate_id)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+
+    let scores = sqlx::query(
+        "UPDATE scores AS moved SET student_id = $1
+         WHERE moved.student_id = $2 AND NOT EXISTS (
+             SELECT 1 FROM scores AS kept
+             WHERE kept.student_id = $1 AND kept.assessment_id = moved.assessment_id
+         )"
+    )
+        .bind(keep_id)
+        .bind(duplicate_id)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+
+    let status_history = sqlx::query("UPDATE student_status_history SET student_id = $1 WHERE student_id = $2")
+        .bind(keep_id)
+        .bind(duplicate_id)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+
+    let photo = move_photo(conn, keep_id, duplicate_id).await?;
+    Ok(MergeSummary { attendance, scores, status_history, photo })
+}
+
+/// Merges a duplicate into the student in the path: records and photo move
+/// over, profile fields the kept student is missing are filled in from the
+/// duplicate, and the duplicate is deleted.
+#[utoipa::path(
+    post,
+    path = "/students/{id}/merge",
+    tag = "students",
+    params(("id" = i32, Path, description = "Kept student id")),
+    request_body = MergeRequest,
+    responses(
+        (status = 200, description = "The kept student after the merge", body = Student),
+        (status = 400, response = BadRequest),
+        (status = 404, response = NotFound),
+        (status = 409, description = "The students belong to different groups", body = String),
+        (status = 500, response = ServerError)
+    )
+)]
+pub(crate) async fn merge_students(
+    id: web::Path<i32>,
+    merge: web::Json<MergeRequest>,
+    pool: web::Data<PgPool>,
+    group_store: web::Data<GroupStore>
+) -> impl Responder {
+    let (keep_id, duplicate_id) = (*id, merge.duplicate_id);
+    log::debug!("Merging student id: {} into student id: {}", duplicate_id, keep_id);
+    if keep_id == duplicate_id {
+        return HttpResponse::BadRequest().body("A student cannot be merged into itself");
+    }
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            log::error!("Failed to start transaction: {}", e);
+            return HttpResponse::InternalServerError()
+                .body(format!("Failed to start transaction: {}", e));
+        }
+    };
+
+    let query = format!(
+        "SELECT {STUDENT_COLUMNS} FROM {} WHERE id = ANY($1) ORDER BY id FOR UPDATE",
+        *STUDENT_TABLE_NAME
+    );
+    let students = match sqlx::query_as::<_, Student>(&query)
+        .bind([keep_id, duplicate_id].as_slice())
+        .fetch_all(&mut *tx)
+        .await
+    {
+        Ok(students) => students,
+        Err(e) => return database_error("Failed to fetch students", e),
+    };
+    let (Some(kept), Some(duplicate)) = (
+        students.iter().find(|student| student.id == keep_id),
+        students.iter().find(|student| student.id == duplicate_id),
+    ) else {
+        log::debug!("Student not found for merge of {} into {}", duplicate_id, keep_id);
+        return HttpResponse::NotFound().finish();
+    };
+    if kept.group_id != duplicate.group_id {
+        return HttpResponse::Conflict().body("Only students of the same group can be merged");
+    }
+
+    let duplicate_image_path: Option<String> = match *STORAGE_TYPE {
+        StorageType::Blob => None,
+        StorageType::Filesystem => {
+            let query = format!("SELECT image_path FROM {} WHERE id = $1", *STUDENT_TABLE_NAME);
+            match sqlx::query_scalar(&query).bind(duplicate_id).fetch_one(&mut *tx).await {
+                Ok(path) => path,
+                Err(e) => return database_error("Failed to fetch image path", e),
+            }
+        },
+    };
+
+    let summary = match move_records(&mut tx, keep_id, duplicate_id).await {
+        Ok(summary) => summary,
+        Err(e) => {
+            if let Err(rollback_err) = tx.rollback().await {
+                log::error!("Failed to rollback transaction: {}", rollback_err);
+            }
+            return database_error("Failed to move records of the duplicate", e);
+        }
+    };
+
+    // The duplicate goes first so its record book number is free for the kept student.
+    let query = format!("DELETE FROM {} WHERE id = $1", *STUDENT_TABLE_NAME);
+    if let Err(e) = sqlx::query(&query).bind(duplicate_id).execute(&mut *tx).await {
+        if let Err(rollback_err) = tx.rollback().await {
+            log::error!("Failed to rollback transaction: {}", rollback_err);
+        }
+        return database_error("Failed to delete the duplicate", e);
+    }
+
+    let query = format!(
+        "UPDATE {} SET patronymic = COALESCE(patronymic, $2), email = COALESCE(email, $3),
+             phone = COALESCE(phone, $4), birth_date = COALESCE(birth_date, $5),
+             record_book_number = COALESCE(record_book_number, $6)
+         WHERE id = $1
+         RETURNING {STUDENT_COLUMNS}",
+        *STUDENT_TABLE_NAME
+    );
+    let merged = match sqlx::query_as::<_, Student>(&query)
+        .bind(keep_id)
+        .bind(&duplicate.profile.patronymic)
+        .bind(&duplicate.profile.email)
+        .bind(&duplicate.profile.phone)
+        .bind(duplicate.profile.birth_date)
+        .bind(&duplicate.profile.record_book_number)
+        .fetch_one(&mut *tx)
+        .await
+    {
+        Ok(merged) => merged,
+        Err(e) => {
+            if let Err(rollback_err) = tx.rollback().await {
+                log::error!("Failed to rollback transaction: {}", rollback_err);
+            }
+            return database_error("Failed to update the merged student", e);
+        }
+    };
+
+    let details = json!({ "merged_student": duplicate, "moved": &summary });
+    if let Err(e) = audit::record(&mut tx, "student.merge", "student", keep_id, details).await {
+        if let Err(rollback_err) = tx.rollback().await {
+            log::error!("Failed to rollback transaction: {}", rollback_err);
+        }
+        return database_error("Failed to record the merge", e);
+    }
+
+    let merge_events = [
+        Event::student(EventKind::StudentDeleted, duplicate_id, [merged.group_id]),
+        Event::student(EventKind::StudentUpdated, keep_id, [merged.group_id]),
+    ];
+    if let Err(e) = webhooks::enqueue(&mut *tx, &merge_events).await {
+        if let Err(rollback_err) = tx.rollback().await {
+            log::error!("Failed to rollback transaction: {}", rollback_err);
+        }
+        return database_error("Failed to queue webhooks", e);
+    }
+
+    // The photo file of the duplicate is deleted unless the kept student took it over.
+    let mut sagas = vec![Saga::HandOverLeadership { from: duplicate_id, to: keep_id }];
+    if let Some(path) = duplicate_image_path {
+        sagas.push(Saga::ReplaceImage { new_path: None, old_path: Some(path) });
+    }
+    let sagas = match saga::begin_all(&mut tx, sagas).await {
+        Ok(sagas) => sagas,
+        Err(e) => {
+            if let Err(rollback_err) = tx.rollback().await {
+                log::error!("Failed to rollback transaction: {}", rollback_err);
+            }
+            return database_error("Failed to record the merge", e);
+        }
+    };
+
+    if let Err(e) = tx.commit().await {
+        log::error!("Failed to commit transaction: {}", e);
+        return HttpResponse::InternalServerError()
+            .body(format!("Failed to commit transaction: {}", e));
+    }
+    saga::finish_all(sagas, pool.get_ref(), group_store.get_ref()).await;
+
+    log::info!(
+        "Merged student id: {} into student id: {} ({} attendance records, {} scores, {} status changes)",
+        duplicate_id, keep_id, summary.attendance, summary.scores, summary.status_history
+    );
+    merge_events.into_iter().for_each(events::publish);
+    HttpResponse::Ok().json(merged)
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    get_duplicates, merge_students,
+))]
+pub(crate) struct ApiDoc;
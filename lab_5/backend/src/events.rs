@@ -0,0 +1,183 @@
+use actix_web::http::header;
+use actix_web::web::Bytes;
+use actix_web::{web, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::{self, error::RecvError};
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::students::StudentStore;
+
+/// Events buffered per subscriber; a subscriber that falls further behind is told to resync.
+const CHANNEL_CAPACITY: usize = 256;
+/// Proxies close idle connections, so an idle stream still sends a comment this often.
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+#[derive(Serialize, Clone, Copy, Debug, ToSchema)]
+pub(crate) enum EventKind {
+    #[serde(rename = "student.created")]
+    StudentCreated,
+    #[serde(rename = "student.updated")]
+    StudentUpdated,
+    #[serde(rename = "student.deleted")]
+    StudentDeleted,
+    #[serde(rename = "group.created")]
+    GroupCreated,
+    #[serde(rename = "group.updated")]
+    GroupUpdated,
+    #[serde(rename = "group.deleted")]
+    GroupDeleted,
+}
+
+impl EventKind {
+    pub(crate) const ALL: [EventKind; 6] = [
+        EventKind::StudentCreated,
+        EventKind::StudentUpdated,
+        EventKind::StudentDeleted,
+        EventKind::GroupCreated,
+        EventKind::GroupUpdated,
+        EventKind::GroupDeleted,
+    ];
+
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            EventKind::StudentCreated => "student.created",
+            EventKind::StudentUpdated => "student.updated",
+            EventKind::StudentDeleted => "student.deleted",
+            EventKind::GroupCreated => "group.created",
+            EventKind::GroupUpdated => "group.updated",
+            EventKind::GroupDeleted => "group.deleted",
+        }
+    }
+}
+
+impl FromStr for EventKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        EventKind::ALL
+            .into_iter()
+            .find(|kind| kind.as_str() == value)
+            .ok_or_else(|| format!("Unknown event type: {value}"))
+    }
+}
+
+/// A change to a student or a group. It only names what changed; clients fetch
+/// the new state themselves.
+#[derive(Serialize, Clone, Debug, ToSchema)]
+pub(crate) struct Event {
+    /// Also sent as the name of the server-sent event.
+    #[serde(rename = "type")]
+    pub(crate) kind: EventKind,
+    /// Id of the student or the group.
+    pub(crate) id: i32,
+    /// Groups the change is visible in: the group itself, or the groups a
+    /// student left and joined.
+    pub(crate) groups: Vec<i32>,
+    pub(crate) occurred_at: DateTime<Utc>,
+}
+
+impl Event {
+    /// An event about a student, concerning every group in `groups` that is set.
+    pub(crate) fn student(kind: EventKind, id: i32, groups: impl IntoIterator<Item = Option<i32>>) -> Self {
+        let mut groups: Vec<i32> = groups.into_iter().flatten().collect();
+        groups.dedup();
+        Event { kind, id, groups, occurred_at: Utc::now() }
+    }
+
+    pub(crate) fn group(kind: EventKind, id: i32) -> Self {
+        Event { kind, id, groups: vec![id], occurred_at: Utc::now() }
+    }
+}
+
+lazy_static! {
+    static ref EVENTS: broadcast::Sender<Event> = broadcast::channel(CHANNEL_CAPACITY).0;
+}
+
+/// Sends an event to every open stream. Call it once the change is committed;
+/// changes made in a transaction queue their webhooks with [`crate::webhooks::enqueue`]
+/// before committing.
+pub(crate) fn publish(event: Event) {
+    log::debug!("Publishing {} event for id: {}", event.kind.as_str(), event.id);
+    // Sending only fails when nobody is listening.
+    EVENTS.send(event).ok();
+}
+
+/// Announces a change made outside a transaction: queues its webhooks and sends
+/// it to every open stream. The change itself stands even if queueing fails.
+pub(crate) async fn emit(student_store: &StudentStore, event: Event) {
+    if let Err(e) = student_store.queue_webhooks(&event).await {
+        log::error!("Failed to queue webhooks for {} event of id {}: {}", event.kind.as_str(), event.id, e);
+    }
+    publish(event);
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct EventFilter {
+    /// Only events that concern this group.
+    group_id: Option<i32>,
+}
+
+fn encode(event: &Event) -> Bytes {
+    let data = serde_json::to_string(event).expect("events serialize to JSON");
+    Bytes::from(format!("event: {}\ndata: {}\n\n", event.kind.as_str(), data))
+}
+
+/// Streams changes to students and groups as server-sent events. Each event is
+/// named after its type and carries the [`Event`] as JSON. A `resync` event
+/// means events were dropped because the client fell behind, and it should
+/// reload everything it shows.
+#[utoipa::path(
+    get,
+    path = "/events",
+    tag = "events",
+    params(EventFilter),
+    responses(
+        (status = 200, description = "Endless stream of `student.created`, `student.updated`, `student.deleted`, \
+            `group.created`, `group.updated` and `group.deleted` events", content_type = "text/event-stream", body = Event)
+    )
+)]
+async fn stream_events(filter: web::Query<EventFilter>) -> impl Responder {
+    let group_id = filter.group_id;
+    log::debug!("Opening event stream for group: {:?}", group_id);
+
+    let events = futures::stream::unfold(EVENTS.subscribe(), move |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) if group_id.is_none_or(|group_id| event.groups.contains(&group_id)) => {
+                    return Some((encode(&event), receiver));
+                },
+                Ok(_) => {},
+                Err(RecvError::Lagged(skipped)) => {
+                    log::warn!("Event stream fell behind by {} events", skipped);
+                    return Some((Bytes::from_static(b"event: resync\ndata: {}\n\n"), receiver));
+                },
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    // The first tick fires at once, so clients see the stream open right away.
+    let keep_alive = futures::stream::unfold(actix_web::rt::time::interval(KEEP_ALIVE_INTERVAL), |mut interval| async {
+        interval.tick().await;
+        Some((Bytes::from_static(b": keep-alive\n\n"), interval))
+    });
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .streaming(futures::stream::select(events, keep_alive).map(Ok::<_, actix_web::Error>))
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(stream_events))]
+pub(crate) struct ApiDoc;
+
+pub(crate) fn configure_routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/events", web::get().to(stream_events));
+}
@@ -0,0 +1,482 @@
+use actix_web::{web, HttpResponse, Responder};
+use actix_web::http::header::{Charset, ContentDisposition, DispositionParam, DispositionType, ExtendedValue};
+use actix_web::web::Bytes;
+use chrono::NaiveDate;
+use futures::channel::mpsc;
+use futures::{SinkExt, TryStreamExt};
+use rust_xlsxwriter::Workbook;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Row};
+use tokio::io::AsyncReadExt;
+use utoipa::{IntoParams, OpenApi, ToSchema};
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use crate::groups::GroupStore;
+use crate::openapi::{Binary, NotFound, ServerError};
+use crate::routes::GroupFilter;
+use crate::storage::{Group, StorageType, Student, STORAGE_TYPE, STUDENT_COLUMNS, STUDENT_TABLE_NAME};
+use crate::translit::transliterate;
+
+/// Number of rows serialized before a chunk is handed over to the response stream.
+const ROWS_PER_CHUNK: usize = 256;
+const FILE_CHUNK_SIZE: usize = 64 * 1024;
+
+type ChunkSender = mpsc::Sender<Result<Bytes, actix_web::Error>>;
+type ArchiveWriter = ZipWriter<std::fs::File>;
+
+#[derive(Deserialize, Clone, Copy, ToSchema)]
+#[serde(rename_all = "lowercase")]
+enum ExportFormat {
+    Csv,
+    Json,
+    Xlsx,
+}
+
+impl ExportFormat {
+    fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Json => "application/json",
+            ExportFormat::Xlsx => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+            ExportFormat::Xlsx => "xlsx",
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct ExportOptions {
+    format: ExportFormat,
+    /// Export only this group.
+    group_id: Option<i32>,
+}
+
+const EXPORT_HEADER: [&str; 13] = [
+    "id", "name", "surname", "name_latin", "surname_latin", "patronymic", "group_id", "group_name", "email", "phone", "birth_date",
+    "record_book_number", "status",
+];
+
+/// Field order must match [`EXPORT_HEADER`].
+#[derive(Serialize, ToSchema)]
+struct ExportRow<'a> {
+    id: i32,
+    name: &'a str,
+    surname: &'a str,
+    name_latin: Option<&'a str>,
+    surname_latin: Option<&'a str>,
+    patronymic: Option<&'a str>,
+    group_id: Option<i32>,
+    group_name: &'a str,
+    email: Option<&'a str>,
+    phone: Option<&'a str>,
+    birth_date: Option<NaiveDate>,
+    record_book_number: Option<&'a str>,
+    status: &'a str,
+}
+
+impl<'a> ExportRow<'a> {
+    fn new(student: &'a Student, group_names: &'a HashMap<i32, String>) -> Self {
+        ExportRow {
+            id: student.id,
+            name: &student.name,
+            surname: &student.surname,
+            name_latin: student.name_latin.as_deref(),
+            surname_latin: student.surname_latin.as_deref(),
+            patronymic: student.profile.patronymic.as_deref(),
+            group_id: student.group_id,
+            group_name: student.group_id
+                .and_then(|group_id| group_names.get(&group_id))
+                .map(String::as_str)
+                .unwrap_or(""),
+            email: student.profile.email.as_deref(),
+            phone: student.profile.phone.as_deref(),
+            birth_date: student.profile.birth_date,
+            record_book_number: student.profile.record_book_number.as_deref(),
+            status: &student.status,
+        }
+    }
+
+    /// Values as spreadsheet cells, in the order of [`EXPORT_HEADER`].
+    fn cells(&self) -> [String; 13] {
+        [
+            self.id.to_string(),
+            self.name.to_string(),
+            self.surname.to_string(),
+            self.name_latin.unwrap_or("").to_string(),
+            self.surname_latin.unwrap_or("").to_string(),
+            self.patronymic.unwrap_or("").to_string(),
+            self.group_id.map(|group_id| group_id.to_string()).unwrap_or_default(),
+            self.group_name.to_string(),
+            self.email.unwrap_or("").to_string(),
+            self.phone.unwrap_or("").to_string(),
+            self.birth_date.map(|date| date.to_string()).unwrap_or_default(),
+            self.record_book_number.unwrap_or("").to_string(),
+            self.status.to_string(),
+        ]
+    }
+}
+
+/// Names a file in UTF-8 as `filename*`, with a Latin spelling as `filename` for
+/// clients that only understand ASCII.
+fn attachment(filename: String) -> ContentDisposition {
+    let fallback = transliterate(&filename)
+        .chars()
+        .map(|c| if c.is_ascii_graphic() && c != '"' && c != '\\' { c } else { '_' })
+        .collect();
+    let mut parameters = vec![DispositionParam::Filename(fallback)];
+    if !filename.is_ascii() {
+        parameters.push(DispositionParam::FilenameExt(ExtendedValue {
+            charset: Charset::Ext("UTF-8".to_string()),
+            language_tag: None,
+            value: filename.into_bytes(),
+        }));
+    }
+    ContentDisposition { disposition: DispositionType::Attachment, parameters }
+}
+
+fn students_query() -> String {
+    format!(
+        "SELECT {STUDENT_COLUMNS} FROM {} WHERE $1::INT IS NULL OR group_id = $1 ORDER BY id",
+        *STUDENT_TABLE_NAME
+    )
+}
+
+fn headerless_csv_writer() -> csv::Writer<Vec<u8>> {
+    csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(Vec::new())
+}
+
+/// Hands out everything written so far and starts a fresh writer.
+fn take_csv(csv_writer: &mut csv::Writer<Vec<u8>>) -> Result<Vec<u8>, String> {
+    std::mem::replace(csv_writer, headerless_csv_writer())
+        .into_inner()
+        .map_err(|e| e.to_string())
+}
+
+/// Streams students row by row from Postgres into `sender`, serialized as CSV or JSON.
+async fn stream_rows(
+    pool: PgPool,
+    format: ExportFormat,
+    group_id: Option<i32>,
+    group_names: HashMap<i32, String>,
+    sender: &mut ChunkSender
+) -> Result<(), String> {
+    let query = students_query();
+    let mut students = sqlx::query_as::<_, Student>(&query)
+        .bind(group_id)
+        .fetch(&pool);
+
+    let mut csv_writer = headerless_csv_writer();
+    let mut buffer = Vec::new();
+    match format {
+        ExportFormat::Csv => csv_writer
+            .write_record(EXPORT_HEADER)
+            .map_err(|e| e.to_string())?,
+        ExportFormat::Json => buffer.push(b'['),
+        ExportFormat::Xlsx => unreachable!("XLSX exports are not streamed"),
+    }
+
+    let mut rows = 0;
+    while let Some(student) = students.try_next().await.map_err(|e| e.to_string())? {
+        let row = ExportRow::new(&student, &group_names);
+        match format {
+            ExportFormat::Csv => csv_writer.serialize(&row).map_err(|e| e.to_string())?,
+            _ => {
+                if rows > 0 {
+                    buffer.push(b',');
+                }
+                serde_json::to_writer(&mut buffer, &row).map_err(|e| e.to_string())?;
+            }
+        }
+        rows += 1;
+
+        if rows % ROWS_PER_CHUNK == 0 {
+            if let ExportFormat::Csv = format {
+                buffer = take_csv(&mut csv_writer)?;
+            }
+            sender.send(Ok(Bytes::from(std::mem::take(&mut buffer))))
+                .await
+                .map_err(|_| "Client disconnected".to_string())?;
+        }
+    }
+
+    match format {
+        ExportFormat::Csv => buffer = take_csv(&mut csv_writer)?,
+        _ => buffer.push(b']'),
+    }
+    sender.send(Ok(Bytes::from(buffer)))
+        .await
+        .map_err(|_| "Client disconnected".to_string())?;
+
+    log::info!("Successfully exported {} students", rows);
+    Ok(())
+}
+
+/// XLSX is a ZIP container whose directory is written last, so the workbook
+/// cannot be streamed and is assembled in memory instead.
+async fn build_xlsx(
+    pool: &PgPool,
+    group_id: Option<i32>,
+    group_names: &HashMap<i32, String>
+) -> Result<Vec<u8>, String> {
+    let query = students_query();
+    let mut students = sqlx::query_as::<_, Student>(&query)
+        .bind(group_id)
+        .fetch(pool);
+
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet();
+    for (column, title) in (0u16..).zip(EXPORT_HEADER) {
+        worksheet.write_string(0, column, title).map_err(|e| e.to_string())?;
+    }
+
+    let mut row_index = 1;
+    while let Some(student) = students.try_next().await.map_err(|e| e.to_string())? {
+        let row = ExportRow::new(&student, group_names);
+        for (column, cell) in (0u16..).zip(row.cells()) {
+            let written = match (column, row.group_id) {
+                (0, _) => worksheet.write_number(row_index, column, row.id),
+                (6, Some(group_id)) => worksheet.write_number(row_index, column, group_id),
+                _ => worksheet.write_string(row_index, column, cell),
+            };
+            written.map_err(|e| e.to_string())?;
+        }
+        row_index += 1;
+    }
+
+    workbook.save_to_buffer().map_err(|e| e.to_string())
+}
+
+#[utoipa::path(
+    get,
+    path = "/export",
+    tag = "export",
+    params(ExportOptions),
+    responses(
+        (status = 200, description = "Students with their group names, streamed as an attachment", content(
+            (String = "text/csv"),
+            (Vec<ExportRow> = "application/json"),
+            (Binary = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet")
+        )),
+        (status = 404, response = NotFound),
+        (status = 500, response = ServerError)
+    )
+)]
+pub(crate) async fn export_students(
+    options: web::Query<ExportOptions>,
+    pool: web::Data<PgPool>,
+    group_store: web::Data<GroupStore>
+) -> impl Responder {
+    log::debug!("Exporting students as {}", options.format.extension());
+    let groups = match group_store.find_all(&GroupFilter::default(), None).await {
+        Ok(groups) => groups,
+        Err(e) => {
+            log::error!("Failed to fetch groups: {}", e);
+            return HttpResponse::InternalServerError().body(e.to_string());
+        }
+    };
+
+    let file_stem = match options.group_id {
+        Some(group_id) => match groups.iter().find(|group| group.id == group_id) {
+            Some(group) => format!("students_{}", group.name),
+            None => {
+                log::debug!("Group not found with id: {}", group_id);
+                return HttpResponse::NotFound().finish();
+            }
+        },
+        None => "students".to_string(),
+    };
+    let group_names: HashMap<i32, String> = groups.into_iter().map(|group| (group.id, group.name)).collect();
+    let disposition = attachment(format!("{}.{}", file_stem, options.format.extension()));
+
+    if let ExportFormat::Xlsx = options.format {
+        return match build_xlsx(pool.get_ref(), options.group_id, &group_names).await {
+            Ok(workbook) => HttpResponse::Ok()
+                .content_type(options.format.content_type())
+                .insert_header(disposition)
+                .body(workbook),
+            Err(e) => {
+                log::error!("Failed to build XLSX export: {}", e);
+                HttpResponse::InternalServerError().body(e)
+            }
+        };
+    }
+
+    let (mut sender, receiver) = mpsc::channel(4);
+    let pool = pool.get_ref().clone();
+    let (format, group_id) = (options.format, options.group_id);
+    actix_web::rt::spawn(async move {
+        if let Err(e) = stream_rows(pool, format, group_id, group_names, &mut sender).await {
+            log::error!("Student export aborted: {}", e);
+            // The headers are gone already; an error aborts the response, so the
+            // client sees a broken transfer rather than a file that merely ends early.
+            sender.send(Err(actix_web::error::ErrorInternalServerError(e))).await.ok();
+        }
+    });
+
+    HttpResponse::Ok()
+        .content_type(options.format.content_type())
+        .insert_header(disposition)
+        .streaming(receiver)
+}
+
+fn image_extension(image_type: &str) -> &'static str {
+    match image_type {
+        "image/png" => "png",
+        _ => "jpg",
+    }
+}
+
+/// Runs `write` on the archive in a blocking thread, which zip and file I/O need.
+async fn write_archive(
+    mut archive: ArchiveWriter,
+    write: impl FnOnce(&mut ArchiveWriter) -> Result<(), String> + Send + 'static
+) -> Result<ArchiveWriter, String> {
+    web::block(move || write(&mut archive).map(|()| archive)).await.map_err(|e| e.to_string())?
+}
+
+/// Starts a file in the archive and writes `data` to it.
+async fn add_to_archive(archive: ArchiveWriter, name: String, data: Vec<u8>) -> Result<ArchiveWriter, String> {
+    write_archive(archive, move |archive| {
+        archive.start_file(name, SimpleFileOptions::default()).map_err(|e| e.to_string())?;
+        archive.write_all(&data).map_err(|e| e.to_string())
+    }).await
+}
+
+/// Writes the archive into an anonymous temporary file, so photos never have to
+/// be held in memory all at once.
+async fn build_archive(pool: &PgPool, groups: &[Group]) -> Result<std::fs::File, String> {
+    let file = web::block(tempfile::tempfile).await.map_err(|e| e.to_string())?.map_err(|e| e.to_string())?;
+    let groups_json = serde_json::to_vec_pretty(groups).map_err(|e| e.to_string())?;
+    let mut archive = add_to_archive(ZipWriter::new(file), "groups.json".to_string(), groups_json).await?;
+
+    let group_names: HashMap<i32, String> = groups.iter().map(|group| (group.id, group.name.clone())).collect();
+    let query = students_query();
+    let mut students = sqlx::query_as::<_, Student>(&query)
+        .bind(None::<i32>)
+        .fetch(pool);
+
+    let mut csv_writer = headerless_csv_writer();
+    csv_writer.write_record(EXPORT_HEADER).map_err(|e| e.to_string())?;
+    archive = add_to_archive(archive, "students.csv".to_string(), take_csv(&mut csv_writer)?).await?;
+    let mut student_count = 0;
+    while let Some(student) = students.try_next().await.map_err(|e| e.to_string())? {
+        csv_writer.serialize(ExportRow::new(&student, &group_names)).map_err(|e| e.to_string())?;
+        student_count += 1;
+        if student_count % ROWS_PER_CHUNK == 0 {
+            let rows = take_csv(&mut csv_writer)?;
+            archive = write_archive(archive, move |archive| archive.write_all(&rows).map_err(|e| e.to_string())).await?;
+        }
+    }
+    let rows = take_csv(&mut csv_writer)?;
+    archive = write_archive(archive, move |archive| archive.write_all(&rows).map_err(|e| e.to_string())).await?;
+    drop(students);
+
+    let photo_column = match *STORAGE_TYPE {
+        StorageType::Blob => "image_data",
+        StorageType::Filesystem => "image_path",
+    };
+    let query = format!(
+        "SELECT id, {}, image_type FROM {} WHERE image_type IS NOT NULL ORDER BY id",
+        photo_column, *STUDENT_TABLE_NAME
+    );
+    let mut photos = sqlx::query(&query).fetch(pool);
+    let mut photo_count = 0;
+    while let Some(row) = photos.try_next().await.map_err(|e| e.to_string())? {
+        let id: i32 = row.get("id");
+        let image_type: String = row.get("image_type");
+        let image_data = match *STORAGE_TYPE {
+            StorageType::Blob => row.get::<Option<Vec<u8>>, _>("image_data"),
+            StorageType::Filesystem => match row.get::<Option<String>, _>("image_path") {
+                Some(path) => match tokio::fs::read(&path).await {
+                    Ok(data) => Some(data),
+                    Err(e) => {
+                        log::warn!("Skipping missing photo of student {}: {}", id, e);
+                        None
+                    }
+                },
+                None => None,
+            },
+        };
+        if let Some(image_data) = image_data {
+            let name = format!("photos/{}.{}", id, image_extension(&image_type));
+            archive = add_to_archive(archive, name, image_data).await?;
+            photo_count += 1;
+        }
+    }
+
+    log::info!("Archived {} students, {} groups and {} photos", student_count, groups.len(), photo_count);
+    web::block(move || archive.finish()).await.map_err(|e| e.to_string())?.map_err(|e| e.to_string())
+}
+
+#[utoipa::path(
+    get,
+    path = "/export/archive",
+    tag = "export",
+    responses(
+        (status = 200, description = "ZIP archive with students, groups and photos", content_type = "application/zip", body = Binary),
+        (status = 500, response = ServerError)
+    )
+)]
+pub(crate) async fn export_archive(
+    pool: web::Data<PgPool>,
+    group_store: web::Data<GroupStore>
+) -> impl Responder {
+    log::debug!("Building export archive");
+    let groups = match group_store.find_all(&GroupFilter::default(), None).await {
+        Ok(groups) => groups,
+        Err(e) => {
+            log::error!("Failed to fetch groups: {}", e);
+            return HttpResponse::InternalServerError().body(e.to_string());
+        }
+    };
+
+    let mut file = match build_archive(pool.get_ref(), &groups).await {
+        Ok(file) => tokio::fs::File::from_std(file),
+        Err(e) => {
+            log::error!("Failed to build export archive: {}", e);
+            return HttpResponse::InternalServerError().body(e);
+        }
+    };
+    if let Err(e) = tokio::io::AsyncSeekExt::rewind(&mut file).await {
+        log::error!("Failed to rewind export archive: {}", e);
+        return HttpResponse::InternalServerError().body(e.to_string());
+    }
+
+    let (mut sender, receiver): (ChunkSender, _) = mpsc::channel(4);
+    actix_web::rt::spawn(async move {
+        let mut chunk = vec![0; FILE_CHUNK_SIZE];
+        loop {
+            let message = match file.read(&mut chunk).await {
+                Ok(0) => break,
+                Ok(read) => Ok(Bytes::copy_from_slice(&chunk[..read])),
+                Err(e) => Err(actix_web::error::ErrorInternalServerError(e)),
+            };
+            let failed = message.is_err();
+            if sender.send(message).await.is_err() || failed {
+                break;
+            }
+        }
+    });
+
+    HttpResponse::Ok()
+        .content_type("application/zip")
+        .insert_header(attachment("backup.zip".to_string()))
+        .streaming(receiver)
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(export_students, export_archive))]
+pub(crate) struct ApiDoc;
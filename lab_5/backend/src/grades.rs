@@ -0,0 +1,701 @@
+use actix_web::{web, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+use std::collections::{HashMap, HashSet};
+
+use crate::groups::GroupStore;
+use crate::openapi::{BadRequest, NotFound, ServerError};
+use crate::routes::database_error;
+use crate::storage::{Student, STUDENT_COLUMNS, STUDENT_TABLE_NAME};
+use crate::schedule::{group_exists, require_non_empty};
+
+#[derive(Serialize, sqlx::FromRow, ToSchema)]
+struct Assessment {
+    id: i32,
+    subject_id: i32,
+    group_id: i32,
+    name: String,
+    weight: f64,
+    max_score: f64,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct AssessmentInput {
+    subject_id: i32,
+    group_id: i32,
+    name: String,
+    weight: f64,
+    max_score: f64,
+}
+
+/// The subject and group of an assessment are fixed once it is created.
+#[derive(Deserialize, ToSchema)]
+struct AssessmentUpdate {
+    name: String,
+    weight: f64,
+    max_score: f64,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct AssessmentFilter {
+    group_id: Option<i32>,
+    subject_id: Option<i32>,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct ScoreInput {
+    student_id: i32,
+    score: f64,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct ScoresInput {
+    scores: Vec<ScoreInput>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct GradebookQuery {
+    subject_id: i32,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, ToSchema)]
+enum Ects {
+    A,
+    B,
+    C,
+    D,
+    E,
+    FX,
+    F,
+}
+
+impl Ects {
+    /// Converts a final grade on the 100-point scale, rounded to whole points.
+    fn from_points(points: f64) -> Self {
+        match points.round() as i64 {
+            90.. => Ects::A,
+            82..=89 => Ects::B,
+            74..=81 => Ects::C,
+            64..=73 => Ects::D,
+            60..=63 => Ects::E,
+            35..=59 => Ects::FX,
+            _ => Ects::F,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+struct FinalGrade {
+    final_score: f64,
+    ects: Ects,
+}
+
+#[derive(Serialize, ToSchema)]
+struct GradebookRow {
+    student_id: i32,
+    name: String,
+    surname: String,
+    /// Scores in the same order as the gradebook's assessments.
+    scores: Vec<Option<f64>>,
+    #[serde(flatten)]
+    grade: Option<FinalGrade>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct Gradebook {
+    group_id: i32,
+    subject_id: i32,
+    subject_name: String,
+    assessments: Vec<Assessment>,
+    students: Vec<GradebookRow>,
+}
+
+#[derive(sqlx::FromRow)]
+struct TranscriptRow {
+    id: i32,
+    subject_id: i32,
+    subject_name: String,
+    name: String,
+    weight: f64,
+    max_score: f64,
+    score: Option<f64>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct TranscriptAssessment {
+    id: i32,
+    name: String,
+    weight: f64,
+    max_score: f64,
+    score: Option<f64>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct TranscriptSubject {
+    subject_id: i32,
+    subject_name: String,
+    #[serde(flatten)]
+    grade: Option<FinalGrade>,
+    assessments: Vec<TranscriptAssessment>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct Transcript {
+    student_id: i32,
+    name: String,
+    surname: String,
+    subjects: Vec<TranscriptSubject>,
+}
+
+/// Weighted share of the maximum score on the 100-point scale. Missing scores
+/// count as zero; there is no grade at all for a subject without assessments.
+fn final_grade(items: impl Iterator<Item = (f64, f64, Option<f64>)>) -> Option<FinalGrade> {
+    let (earned, total_weight) = items.fold((0.0, 0.0), |(earned, total), (weight, max_score, score)| {
+        (earned + weight * score.unwrap_or(0.0) / max_score, total + weight)
+    });
+    if total_weight <= 0.0 {
+        return None;
+    }
+    let final_score = (earned / total_weight * 10000.0).round() / 100.0;
+    Some(FinalGrade { final_score, ects: Ects::from_points(final_score) })
+}
+
+fn validate_assessment(name: &str, weight: f64, max_score: f64) -> Result<(), HttpResponse> {
+    require_non_empty(name, "Assessment name")?;
+    if !weight.is_finite() || weight <= 0.0 {
+        return Err(HttpResponse::BadRequest().body("Weight must be a positive number"));
+    }
+    if !max_score.is_finite() || max_score <= 0.0 {
+        return Err(HttpResponse::BadRequest().body("Maximum score must be a positive number"));
+    }
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/assessments",
+    tag = "grades",
+    params(AssessmentFilter),
+    responses(
+        (status = 200, description = "Assessments matching the filters", body = Vec<Assessment>),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn get_assessments(filter: web::Query<AssessmentFilter>, pool: web::Data<PgPool>) -> impl Responder {
+    log::debug!("Fetching assessments");
+    match sqlx::query_as::<_, Assessment>(
+        "SELECT id, subject_id, group_id, name, weight, max_score FROM assessments
+         WHERE ($1::INT IS NULL OR group_id = $1) AND ($2::INT IS NULL OR subject_id = $2)
+         ORDER BY group_id, subject_id, id"
+    )
+        .bind(filter.group_id)
+        .bind(filter.subject_id)
+        .fetch_all(pool.get_ref())
+        .await
+    {
+        Ok(assessments) => {
+            log::info!("Successfully retrieved {} assessments", assessments.len());
+            HttpResponse::Ok().json(assessments)
+        },
+        Err(e) => database_error("Failed to fetch assessments", e),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/assessments",
+    tag = "grades",
+    request_body = AssessmentInput,
+    responses(
+        (status = 200, description = "The created assessment", body = Assessment),
+        (status = 400, response = BadRequest),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn create_assessment(
+    assessment: web::Json<AssessmentInput>,
+    pool: web::Data<PgPool>,
+    group_store: web::Data<GroupStore>
+) -> impl Responder {
+    log::debug!("Creating new assessment: {}", assessment.name);
+    if let Err(response) = validate_assessment(&assessment.name, assessment.weight, assessment.max_score) {
+        return response;
+    }
+    match group_exists(group_store.get_ref(), assessment.group_id).await {
+        Ok(true) => {},
+        Ok(false) => return HttpResponse::BadRequest().body(format!("Group {} does not exist", assessment.group_id)),
+        Err(response) => return response,
+    }
+
+    match sqlx::query_as::<_, Assessment>(
+        "INSERT INTO assessments (subject_id, group_id, name, weight, max_score) VALUES ($1, $2, $3, $4, $5)
+         RETURNING id, subject_id, group_id, name, weight, max_score"
+    )
+        .bind(assessment.subject_id)
+        .bind(assessment.group_id)
+        .bind(assessment.name.trim())
+        .bind(assessment.weight)
+        .bind(assessment.max_score)
+        .fetch_one(pool.get_ref())
+        .await
+    {
+        Ok(assessment) => {
+            log::info!("Successfully created new assessment with id: {}", assessment.id);
+            HttpResponse::Ok().json(assessment)
+        },
+        Err(e) => database_error("Failed to create assessment", e),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/assessments/{id}",
+    tag = "grades",
+    params(("id" = i32, Path, description = "Assessment id")),
+    request_body = AssessmentUpdate,
+    responses(
+        (status = 200, description = "The assessment was updated"),
+        (status = 400, response = BadRequest),
+        (status = 404, response = NotFound),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn update_assessment(
+    id: web::Path<i32>,
+    assessment: web::Json<AssessmentUpdate>,
+    pool: web::Data<PgPool>
+) -> impl Responder {
+    log::debug!("Updating assessment id: {}", id);
+    if let Err(response) = validate_assessment(&assessment.name, assessment.weight, assessment.max_score) {
+        return response;
+    }
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            log::error!("Failed to start transaction: {}", e);
+            return HttpResponse::InternalServerError()
+                .body(format!("Failed to start transaction: {}", e));
+        }
+    };
+
+    // Locking the assessment keeps scores from being recorded while the new maximum is checked.
+    match sqlx::query_scalar::<_, i32>("SELECT id FROM assessments WHERE id = $1 FOR UPDATE")
+        .bind(id.as_ref())
+        .fetch_optional(&mut *tx)
+        .await
+    {
+        Ok(Some(_)) => {},
+        Ok(None) => {
+            log::debug!("Assessment not found with id: {}", id);
+            return HttpResponse::NotFound().finish();
+        },
+        Err(e) => return database_error("Failed to fetch assessment", e),
+    }
+
+    match sqlx::query_scalar::<_, Option<f64>>("SELECT MAX(score) FROM scores WHERE assessment_id = $1")
+        .bind(id.as_ref())
+        .fetch_one(&mut *tx)
+        .await
+    {
+        Ok(Some(highest)) if highest > assessment.max_score => {
+            log::warn!("Cannot lower maximum score of assessment {} below recorded score {}", id, highest);
+            return HttpResponse::BadRequest()
+                .body(format!("Maximum score cannot be lower than an already recorded score of {highest}"));
+        },
+        Ok(_) => {},
+        Err(e) => return database_error("Failed to fetch scores", e),
+    }
+
+    if let Err(e) = sqlx::query("UPDATE assessments SET name = $1, weight = $2, max_score = $3 WHERE id = $4")
+        .bind(assessment.name.trim())
+        .bind(assessment.weight)
+        .bind(assessment.max_score)
+        .bind(id.as_ref())
+        .execute(&mut *tx)
+        .await
+    {
+        if let Err(rollback_err) = tx.rollback().await {
+            log::error!("Failed to rollback transaction: {}", rollback_err);
+        }
+        return database_error("Failed to update assessment", e);
+    }
+
+    match tx.commit().await {
+        Ok(_) => {
+            log::info!("Successfully updated assessment id: {}", id);
+            HttpResponse::Ok().finish()
+        },
+        Err(e) => {
+            log::error!("Failed to commit transaction: {}", e);
+            HttpResponse::InternalServerError().body(format!("Failed to commit transaction: {}", e))
+        }
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/assessments/{id}",
+    tag = "grades",
+    params(("id" = i32, Path, description = "Assessment id")),
+    responses(
+        (status = 200, description = "The assessment and its scores were deleted"),
+        (status = 404, response = NotFound),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn delete_assessment(id: web::Path<i32>, pool: web::Data<PgPool>) -> impl Responder {
+    log::debug!("Attempting to delete assessment with id: {}", id);
+    match sqlx::query("DELETE FROM assessments WHERE id = $1")
+        .bind(id.as_ref())
+        .execute(pool.get_ref())
+        .await
+    {
+        Ok(result) if result.rows_affected() > 0 => {
+            log::info!("Successfully deleted assessment with id: {}", id);
+            HttpResponse::Ok().finish()
+        },
+        Ok(_) => {
+            log::debug!("Assessment not found with id: {}", id);
+            HttpResponse::NotFound().finish()
+        },
+        Err(e) => database_error("Failed to delete assessment", e),
+    }
+}
+
+/// Records scores of several students at once; existing scores are overwritten.
+#[utoipa::path(
+    put,
+    path = "/assessments/{id}/scores",
+    tag = "grades",
+    params(("id" = i32, Path, description = "Assessment id")),
+    request_body = ScoresInput,
+    responses(
+        (status = 200, description = "The scores were recorded"),
+        (status = 400, response = BadRequest),
+        (status = 404, response = NotFound),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn set_scores(
+    id: web::Path<i32>,
+    input: web::Json<ScoresInput>,
+    pool: web::Data<PgPool>
+) -> impl Responder {
+    log::debug!("Recording {} scores for assessment id: {}", input.scores.len(), id);
+    let mut students = HashSet::new();
+    if let Some(repeated) = input.scores.iter().find(|s| !students.insert(s.student_id)) {
+        return HttpResponse::BadRequest()
+            .body(format!("Student {} appears more than once", repeated.student_id));
+    }
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            log::error!("Failed to start transaction: {}", e);
+            return HttpResponse::InternalServerError()
+                .body(format!("Failed to start transaction: {}", e));
+        }
+    };
+
+    let (group_id, max_score) = match sqlx::query_as::<_, (i32, f64)>(
+        "SELECT group_id, max_score FROM assessments WHERE id = $1 FOR SHARE"
+    )
+        .bind(id.as_ref())
+        .fetch_optional(&mut *tx)
+        .await
+    {
+        Ok(Some(assessment)) => assessment,
+        Ok(None) => {
+            log::debug!("Assessment not found with id: {}", id);
+            return HttpResponse::NotFound().finish();
+        },
+        Err(e) => return database_error("Failed to fetch assessment", e),
+    };
+
+    if let Some(invalid) = input.scores.iter().find(|s| !s.score.is_finite() || s.score < 0.0 || s.score > max_score) {
+        return HttpResponse::BadRequest()
+            .body(format!("Score of student {} must be between 0 and {}", invalid.student_id, max_score));
+    }
+
+    let query = format!("SELECT id FROM {} WHERE group_id = $1", *STUDENT_TABLE_NAME);
+    let roster: Vec<i32> = match sqlx::query_scalar(&query)
+        .bind(group_id)
+        .fetch_all(&mut *tx)
+        .await
+    {
+        Ok(roster) => roster,
+        Err(e) => return database_error("Failed to fetch group roster", e),
+    };
+    if let Some(outsider) = input.scores.iter().find(|s| !roster.contains(&s.student_id)) {
+        return HttpResponse::BadRequest()
+            .body(format!("Student {} does not belong to the group of this assessment", outsider.student_id));
+    }
+
+    let student_ids: Vec<i32> = input.scores.iter().map(|s| s.student_id).collect();
+    let scores: Vec<f64> = input.scores.iter().map(|s| s.score).collect();
+    if let Err(e) = sqlx::query(
+        "INSERT INTO scores (assessment_id, student_id, score)
+         SELECT $1, student_id, score FROM UNNEST($2::INT[], $3::DOUBLE PRECISION[]) AS s(student_id, score)
+         ON CONFLICT (assessment_id, student_id) DO UPDATE SET score = EXCLUDED.score"
+    )
+        .bind(id.as_ref())
+        .bind(&student_ids)
+        .bind(&scores)
+        .execute(&mut *tx)
+        .await
+    {
+        if let Err(rollback_err) = tx.rollback().await {
+            log::error!("Failed to rollback transaction: {}", rollback_err);
+        }
+        return database_error("Failed to record scores", e);
+    }
+
+    match tx.commit().await {
+        Ok(_) => {
+            log::info!("Successfully recorded {} scores for assessment id: {}", scores.len(), id);
+            HttpResponse::Ok().finish()
+        },
+        Err(e) => {
+            log::error!("Failed to commit transaction: {}", e);
+            HttpResponse::InternalServerError().body(format!("Failed to commit transaction: {}", e))
+        }
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/assessments/{id}/scores/{student_id}",
+    tag = "grades",
+    params(("id" = i32, Path, description = "Assessment id"), ("student_id" = i32, Path, description = "Student id")),
+    responses(
+        (status = 200, description = "The score was deleted"),
+        (status = 404, response = NotFound),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn delete_score(path: web::Path<(i32, i32)>, pool: web::Data<PgPool>) -> impl Responder {
+    let (assessment_id, student_id) = path.into_inner();
+    log::debug!("Deleting score of student {} for assessment {}", student_id, assessment_id);
+    match sqlx::query("DELETE FROM scores WHERE assessment_id = $1 AND student_id = $2")
+        .bind(assessment_id)
+        .bind(student_id)
+        .execute(pool.get_ref())
+        .await
+    {
+        Ok(result) if result.rows_affected() > 0 => {
+            log::info!("Successfully deleted score of student {} for assessment {}", student_id, assessment_id);
+            HttpResponse::Ok().finish()
+        },
+        Ok(_) => HttpResponse::NotFound().finish(),
+        Err(e) => database_error("Failed to delete score", e),
+    }
+}
+
+/// Matrix of the group's current students against the assessments of one subject.
+#[utoipa::path(
+    get,
+    path = "/groups/{id}/gradebook",
+    tag = "grades",
+    params(("id" = i32, Path, description = "Group id"), GradebookQuery),
+    responses(
+        (status = 200, description = "Scores and final grades of every student of the group in one subject", body = Gradebook),
+        (status = 400, response = BadRequest),
+        (status = 404, response = NotFound),
+        (status = 500, response = ServerError)
+    )
+)]
+pub(crate) async fn get_group_gradebook(
+    id: web::Path<i32>,
+    query: web::Query<GradebookQuery>,
+    pool: web::Data<PgPool>,
+    group_store: web::Data<GroupStore>
+) -> impl Responder {
+    log::debug!("Building gradebook of group {} for subject {}", id, query.subject_id);
+    match group_exists(group_store.get_ref(), *id).await {
+        Ok(true) => {},
+        Ok(false) => {
+            log::debug!("Group not found with id: {}", id);
+            return HttpResponse::NotFound().finish();
+        },
+        Err(response) => return response,
+    }
+
+    let subject_name = match sqlx::query_scalar::<_, String>("SELECT name FROM subjects WHERE id = $1")
+        .bind(query.subject_id)
+        .fetch_optional(pool.get_ref())
+        .await
+    {
+        Ok(Some(name)) => name,
+        Ok(None) => return HttpResponse::BadRequest().body(format!("Subject {} does not exist", query.subject_id)),
+        Err(e) => return database_error("Failed to fetch subject", e),
+    };
+
+    let assessments = match sqlx::query_as::<_, Assessment>(
+        "SELECT id, subject_id, group_id, name, weight, max_score FROM assessments
+         WHERE group_id = $1 AND subject_id = $2 ORDER BY id"
+    )
+        .bind(id.as_ref())
+        .bind(query.subject_id)
+        .fetch_all(pool.get_ref())
+        .await
+    {
+        Ok(assessments) => assessments,
+        Err(e) => return database_error("Failed to fetch assessments", e),
+    };
+
+    let assessment_ids: Vec<i32> = assessments.iter().map(|a| a.id).collect();
+    let scores: HashMap<(i32, i32), f64> = match sqlx::query_as::<_, (i32, i32, f64)>(
+        "SELECT assessment_id, student_id, score FROM scores WHERE assessment_id = ANY($1)"
+    )
+        .bind(&assessment_ids)
+        .fetch_all(pool.get_ref())
+        .await
+    {
+        Ok(rows) => rows.into_iter().map(|(assessment_id, student_id, score)| ((assessment_id, student_id), score)).collect(),
+        Err(e) => return database_error("Failed to fetch scores", e),
+    };
+
+    let query_str = format!(
+        "SELECT {STUDENT_COLUMNS} FROM {} WHERE group_id = $1 ORDER BY surname, name",
+        *STUDENT_TABLE_NAME
+    );
+    let students = match sqlx::query_as::<_, Student>(&query_str)
+        .bind(id.as_ref())
+        .fetch_all(pool.get_ref())
+        .await
+    {
+        Ok(students) => students,
+        Err(e) => return database_error("Failed to fetch students", e),
+    };
+
+    let rows = students
+        .into_iter()
+        .map(|student| {
+            let student_scores: Vec<Option<f64>> = assessments
+                .iter()
+                .map(|a| scores.get(&(a.id, student.id)).copied())
+                .collect();
+            let grade = final_grade(
+                assessments.iter().zip(&student_scores).map(|(a, score)| (a.weight, a.max_score, *score))
+            );
+            GradebookRow {
+                student_id: student.id,
+                name: student.name,
+                surname: student.surname,
+                scores: student_scores,
+                grade,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    log::info!("Successfully built gradebook of {} students in group {}", rows.len(), id);
+    HttpResponse::Ok().json(Gradebook {
+        group_id: *id,
+        subject_id: query.subject_id,
+        subject_name,
+        assessments,
+        students: rows,
+    })
+}
+
+/// Final grades of a student in every subject of their group, plus any
+/// subject in which they were scored before moving to another group.
+#[utoipa::path(
+    get,
+    path = "/students/{id}/transcript",
+    tag = "grades",
+    params(("id" = i32, Path, description = "Student id")),
+    responses(
+        (status = 200, description = "Scores and final grades of the student in every subject", body = Transcript),
+        (status = 404, response = NotFound),
+        (status = 500, response = ServerError)
+    )
+)]
+pub(crate) async fn get_student_transcript(id: web::Path<i32>, pool: web::Data<PgPool>) -> impl Responder {
+    log::debug!("Building transcript of student id: {}", id);
+    let query = format!("SELECT {STUDENT_COLUMNS} FROM {} WHERE id = $1", *STUDENT_TABLE_NAME);
+    let student = match sqlx::query_as::<_, Student>(&query)
+        .bind(id.as_ref())
+        .fetch_optional(pool.get_ref())
+        .await
+    {
+        Ok(Some(student)) => student,
+        Ok(None) => {
+            log::debug!("Student not found with id: {}", id);
+            return HttpResponse::NotFound().finish();
+        },
+        Err(e) => return database_error("Failed to fetch student", e),
+    };
+
+    let rows = match sqlx::query_as::<_, TranscriptRow>(
+        "SELECT a.id, a.subject_id, sub.name AS subject_name, a.name, a.weight, a.max_score, sc.score
+         FROM assessments a
+         JOIN subjects sub ON sub.id = a.subject_id
+         LEFT JOIN scores sc ON sc.assessment_id = a.id AND sc.student_id = $1
+         WHERE a.group_id = $2 OR sc.score IS NOT NULL
+         ORDER BY sub.name, a.subject_id, a.id"
+    )
+        .bind(student.id)
+        .bind(student.group_id)
+        .fetch_all(pool.get_ref())
+        .await
+    {
+        Ok(rows) => rows,
+        Err(e) => return database_error("Failed to fetch assessments", e),
+    };
+
+    let mut subjects: Vec<TranscriptSubject> = Vec::new();
+    for row in rows {
+        let assessment = TranscriptAssessment {
+            id: row.id,
+            name: row.name,
+            weight: row.weight,
+            max_score: row.max_score,
+            score: row.score,
+        };
+        match subjects.last_mut() {
+            Some(subject) if subject.subject_id == row.subject_id => subject.assessments.push(assessment),
+            _ => subjects.push(TranscriptSubject {
+                subject_id: row.subject_id,
+                subject_name: row.subject_name,
+                grade: None,
+                assessments: vec![assessment],
+            }),
+        }
+    }
+    for subject in &mut subjects {
+        subject.grade = final_grade(subject.assessments.iter().map(|a| (a.weight, a.max_score, a.score)));
+    }
+
+    log::info!("Successfully built transcript of student id: {} with {} subjects", id, subjects.len());
+    HttpResponse::Ok().json(Transcript {
+        student_id: student.id,
+        name: student.name,
+        surname: student.surname,
+        subjects,
+    })
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    get_assessments, create_assessment, update_assessment, delete_assessment, set_scores,
+    delete_score, get_group_gradebook, get_student_transcript,
+))]
+pub(crate) struct ApiDoc;
+
+pub(crate) fn configure_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/assessments")
+            .route("", web::get().to(get_assessments))
+            .route("", web::post().to(create_assessment))
+            .route("/{id}", web::put().to(update_assessment))
+            .route("/{id}", web::delete().to(delete_assessment))
+            .route("/{id}/scores", web::put().to(set_scores))
+            .route("/{id}/scores/{student_id}", web::delete().to(delete_score))
+    );
+}
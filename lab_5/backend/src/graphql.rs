@@ -0,0 +1,472 @@
+use actix_web::{web, HttpResponse, Responder};
+use async_graphql::dataloader::{DataLoader, Loader};
+use async_graphql::http::GraphiQLSource;
+use async_graphql::{
+    Context, EmptySubscription, Enum, ErrorExtensions, InputObject, MaybeUndefined, Object, Result, Schema, ID,
+};
+use async_graphql_actix_web::{GraphQLRequest, GraphQLResponse};
+use chrono::NaiveDate;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::groups::GroupStore;
+use crate::profile::{self, StudentProfile, StudentStatus};
+use crate::routes::{self, GroupFilter, GroupInput, StudentForm};
+use crate::storage::{Group, StoreError, Student, StudyForm};
+use crate::students::StudentStore;
+
+pub(crate) type ApiSchema = Schema<QueryRoot, MutationRoot, EmptySubscription>;
+
+/// Deepest query accepted. GraphiQL's introspection query, the deepest one it
+/// sends, is this deep.
+const MAX_DEPTH: usize = 15;
+/// Most fields a query may select, counting every field once. GraphiQL's
+/// introspection query selects about 230.
+const MAX_COMPLEXITY: usize = 300;
+
+/// Turns an error response of the shared REST logic into a GraphQL error with
+/// the same message, keeping the HTTP status as the `status` extension.
+fn rest_error(response: HttpResponse) -> async_graphql::Error {
+    let (status, message) = routes::error_parts(response);
+    async_graphql::Error::new(message).extend_with(|_, extensions| extensions.set("status", status.as_u16()))
+}
+
+fn parse_id(id: &ID) -> Result<i32> {
+    id.parse().map_err(|_| async_graphql::Error::new(format!("Invalid id: {}", id.as_str())))
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+#[graphql(name = "StudyForm", remote = "crate::storage::StudyForm")]
+enum StudyFormValue {
+    FullTime,
+    PartTime,
+    Distance,
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+#[graphql(name = "StudentStatus", remote = "crate::profile::StudentStatus")]
+enum StudentStatusValue {
+    Active,
+    AcademicLeave,
+    Expelled,
+    Graduated,
+}
+
+struct StudentLoader(Arc<StudentStore>);
+
+impl Loader<i32> for StudentLoader {
+    type Value = Student;
+    type Error = Arc<StoreError>;
+
+    async fn load(&self, ids: &[i32]) -> Result<HashMap<i32, Student>, Self::Error> {
+        let students = self.0.find_many(ids).await?;
+        Ok(students.into_iter().map(|student| (student.id, student)).collect())
+    }
+}
+
+/// Members of each requested group, fetched with a single query for all of them.
+struct MembersLoader(Arc<StudentStore>);
+
+impl Loader<i32> for MembersLoader {
+    type Value = Vec<Student>;
+    type Error = Arc<StoreError>;
+
+    async fn load(&self, group_ids: &[i32]) -> Result<HashMap<i32, Vec<Student>>, Self::Error> {
+        let mut members: HashMap<i32, Vec<Student>> = HashMap::new();
+        for student in self.0.find_group_members(group_ids).await? {
+            if let Some(group_id) = student.group_id {
+                members.entry(group_id).or_default().push(student);
+            }
+        }
+        Ok(members)
+    }
+}
+
+/// MIME type of the photo of each requested student that has one.
+struct PhotoLoader(Arc<StudentStore>);
+
+impl Loader<i32> for PhotoLoader {
+    type Value = String;
+    type Error = Arc<StoreError>;
+
+    async fn load(&self, ids: &[i32]) -> Result<HashMap<i32, String>, Self::Error> {
+        Ok(self.0.find_photo_types(ids).await?.into_iter().collect())
+    }
+}
+
+struct GroupLoader(Arc<GroupStore>);
+
+impl Loader<i32> for GroupLoader {
+    type Value = Group;
+    type Error = Arc<StoreError>;
+
+    async fn load(&self, ids: &[i32]) -> Result<HashMap<i32, Group>, Self::Error> {
+        let groups = self.0.find_many(ids).await?;
+        Ok(groups.into_iter().map(|group| (group.id, group)).collect())
+    }
+}
+
+struct StudentNode(Student);
+
+#[Object(name = "Student")]
+impl StudentNode {
+    async fn id(&self) -> ID {
+        self.0.id.into()
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn surname(&self) -> &str {
+        &self.0.surname
+    }
+
+    /// Official Latin spelling of the name.
+    async fn name_latin(&self) -> Option<&str> {
+        self.0.name_latin.as_deref()
+    }
+
+    async fn surname_latin(&self) -> Option<&str> {
+        self.0.surname_latin.as_deref()
+    }
+
+    async fn patronymic(&self) -> Option<&str> {
+        self.0.profile.patronymic.as_deref()
+    }
+
+    async fn email(&self) -> Option<&str> {
+        self.0.profile.email.as_deref()
+    }
+
+    async fn phone(&self) -> Option<&str> {
+        self.0.profile.phone.as_deref()
+    }
+
+    async fn birth_date(&self) -> Option<NaiveDate> {
+        self.0.profile.birth_date
+    }
+
+    async fn record_book_number(&self) -> Option<&str> {
+        self.0.profile.record_book_number.as_deref()
+    }
+
+    async fn status(&self) -> Result<StudentStatusValue> {
+        let status: StudentStatus = self.0.status.parse()?;
+        Ok(status.into())
+    }
+
+    /// Graduated and expelled students no longer belong to a group.
+    async fn group(&self, ctx: &Context<'_>) -> Result<Option<GroupNode>> {
+        let Some(group_id) = self.0.group_id else {
+            return Ok(None);
+        };
+        let group = ctx.data_unchecked::<DataLoader<GroupLoader>>().load_one(group_id).await?;
+        Ok(group.map(GroupNode))
+    }
+
+    /// Path of the photo relative to the API host, if the student has one.
+    async fn photo_url(&self, ctx: &Context<'_>) -> Result<Option<String>> {
+        let photo_type = ctx.data_unchecked::<DataLoader<PhotoLoader>>().load_one(self.0.id).await?;
+        Ok(photo_type.map(|_| format!("/api/v1/students/image/{}", self.0.id)))
+    }
+}
+
+struct GroupNode(Group);
+
+#[Object(name = "Group")]
+impl GroupNode {
+    async fn id(&self) -> ID {
+        self.0.id.into()
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn faculty(&self) -> Option<&str> {
+        self.0.faculty.as_deref()
+    }
+
+    async fn specialty_code(&self) -> Option<&str> {
+        self.0.specialty_code.as_deref()
+    }
+
+    async fn admission_year(&self) -> Option<i32> {
+        self.0.admission_year
+    }
+
+    async fn study_form(&self) -> Option<StudyFormValue> {
+        self.0.study_form.map(Into::into)
+    }
+
+    async fn max_capacity(&self) -> Option<i32> {
+        self.0.max_capacity
+    }
+
+    async fn leader(&self, ctx: &Context<'_>) -> Result<Option<StudentNode>> {
+        let Some(leader_id) = self.0.leader_id else {
+            return Ok(None);
+        };
+        let leader = ctx.data_unchecked::<DataLoader<StudentLoader>>().load_one(leader_id).await?;
+        Ok(leader.map(StudentNode))
+    }
+
+    async fn students(&self, ctx: &Context<'_>) -> Result<Vec<StudentNode>> {
+        let members = ctx.data_unchecked::<DataLoader<MembersLoader>>().load_one(self.0.id).await?;
+        Ok(members.unwrap_or_default().into_iter().map(StudentNode).collect())
+    }
+
+    async fn student_count(&self, ctx: &Context<'_>) -> Result<usize> {
+        let members = ctx.data_unchecked::<DataLoader<MembersLoader>>().load_one(self.0.id).await?;
+        Ok(members.map_or(0, |members| members.len()))
+    }
+}
+
+/// An update leaves the fields it does not send as they are, and clears those
+/// it sends as `null`.
+#[derive(InputObject)]
+struct GroupFields {
+    name: String,
+    faculty: MaybeUndefined<String>,
+    specialty_code: MaybeUndefined<String>,
+    admission_year: MaybeUndefined<i32>,
+    study_form: MaybeUndefined<StudyFormValue>,
+    max_capacity: MaybeUndefined<i32>,
+}
+
+impl From<GroupFields> for GroupInput {
+    fn from(fields: GroupFields) -> Self {
+        GroupInput {
+            name: fields.name,
+            faculty: fields.faculty.into(),
+            specialty_code: fields.specialty_code.into(),
+            admission_year: fields.admission_year.into(),
+            study_form: fields.study_form.map_value(StudyForm::from).into(),
+            max_capacity: fields.max_capacity.into(),
+        }
+    }
+}
+
+/// Photos are only uploaded through the REST API, which accepts multipart forms.
+#[derive(InputObject)]
+struct StudentFields {
+    name: String,
+    surname: String,
+    /// Required for a new student. An update leaving this out keeps the group; `null`
+    /// removes the student from it.
+    group_id: MaybeUndefined<ID>,
+    /// Profile fields an update leaves out keep their value; `null` clears them.
+    patronymic: MaybeUndefined<String>,
+    email: MaybeUndefined<String>,
+    phone: MaybeUndefined<String>,
+    birth_date: MaybeUndefined<NaiveDate>,
+    record_book_number: MaybeUndefined<String>,
+}
+
+impl StudentFields {
+    fn into_form(self) -> Result<StudentForm> {
+        let mut kept_fields = vec![];
+        if self.group_id.is_undefined() {
+            kept_fields.push("group_id");
+        }
+        let sent = [
+            !self.patronymic.is_undefined(),
+            !self.email.is_undefined(),
+            !self.phone.is_undefined(),
+            !self.birth_date.is_undefined(),
+            !self.record_book_number.is_undefined(),
+        ];
+        Ok(StudentForm {
+            name: self.name,
+            surname: self.surname,
+            group_id: self.group_id.take().as_ref().map(parse_id).transpose()?,
+            profile: StudentProfile {
+                patronymic: self.patronymic.take(),
+                email: self.email.take(),
+                phone: self.phone.take(),
+                birth_date: self.birth_date.take(),
+                record_book_number: self.record_book_number.take(),
+            },
+            kept_fields: kept_fields.into_iter().chain(profile::unsent_columns(sent)).collect(),
+            ..Default::default()
+        })
+    }
+}
+
+pub(crate) struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Students with the given status whose names contain every word of `search`,
+    /// in Cyrillic or Latin.
+    async fn students(
+        &self,
+        ctx: &Context<'_>,
+        status: Option<StudentStatusValue>,
+        search: Option<String>,
+    ) -> Result<Vec<StudentNode>> {
+        let student_store = ctx.data_unchecked::<Arc<StudentStore>>();
+        let students = student_store.find_all(status.map(Into::into), search.as_deref()).await?;
+        Ok(students.into_iter().map(StudentNode).collect())
+    }
+
+    async fn student(&self, ctx: &Context<'_>, id: ID) -> Result<Option<StudentNode>> {
+        let student = ctx.data_unchecked::<DataLoader<StudentLoader>>().load_one(parse_id(&id)?).await?;
+        Ok(student.map(StudentNode))
+    }
+
+    async fn groups(
+        &self,
+        ctx: &Context<'_>,
+        faculty: Option<String>,
+        specialty_code: Option<String>,
+        admission_year: Option<i32>,
+        study_form: Option<StudyFormValue>,
+    ) -> Result<Vec<GroupNode>> {
+        let filter = GroupFilter { faculty, specialty_code, admission_year, study_form: study_form.map(Into::into) };
+        let groups = ctx.data_unchecked::<Arc<GroupStore>>().find_all(&filter, None).await?;
+        Ok(groups.into_iter().map(GroupNode).collect())
+    }
+
+    async fn group(&self, ctx: &Context<'_>, id: ID) -> Result<Option<GroupNode>> {
+        let group = ctx.data_unchecked::<DataLoader<GroupLoader>>().load_one(parse_id(&id)?).await?;
+        Ok(group.map(GroupNode))
+    }
+}
+
+/// Mutations run the same validation and storage code as the REST handlers.
+pub(crate) struct MutationRoot;
+
+impl MutationRoot {
+    async fn fetch_student(ctx: &Context<'_>, id: i32) -> Result<StudentNode> {
+        let student = ctx.data_unchecked::<Arc<StudentStore>>().find(id).await?;
+        student.map(StudentNode).ok_or_else(|| async_graphql::Error::new(format!("Student {id} does not exist")))
+    }
+
+    async fn fetch_group(ctx: &Context<'_>, id: i32) -> Result<GroupNode> {
+        let group = ctx.data_unchecked::<Arc<GroupStore>>().find(id).await?;
+        group.map(GroupNode).ok_or_else(|| async_graphql::Error::new(format!("Group {id} does not exist")))
+    }
+}
+
+#[Object]
+impl MutationRoot {
+    async fn create_student(&self, ctx: &Context<'_>, input: StudentFields) -> Result<StudentNode> {
+        let student_store = ctx.data_unchecked::<Arc<StudentStore>>().as_ref();
+        let group_store = ctx.data_unchecked::<Arc<GroupStore>>().as_ref();
+        let id = match routes::add_student(student_store, group_store, input.into_form()?).await {
+            Ok(id) => id,
+            Err(response) => return Err(rest_error(response)),
+        };
+        Self::fetch_student(ctx, id).await
+    }
+
+    /// Replaces every field of the student; the photo is kept.
+    async fn update_student(&self, ctx: &Context<'_>, id: ID, input: StudentFields) -> Result<StudentNode> {
+        let id = parse_id(&id)?;
+        let student_store = ctx.data_unchecked::<Arc<StudentStore>>().as_ref();
+        let group_store = ctx.data_unchecked::<Arc<GroupStore>>().as_ref();
+        if let Err(response) = routes::edit_student(student_store, group_store, id, input.into_form()?).await {
+            return Err(rest_error(response));
+        }
+        Self::fetch_student(ctx, id).await
+    }
+
+    async fn delete_student(&self, ctx: &Context<'_>, id: ID) -> Result<ID> {
+        let student_store = ctx.data_unchecked::<Arc<StudentStore>>().as_ref();
+        let group_store = ctx.data_unchecked::<Arc<GroupStore>>().as_ref();
+        if let Err(response) = routes::remove_student(student_store, group_store, parse_id(&id)?).await {
+            return Err(rest_error(response));
+        }
+        Ok(id)
+    }
+
+    async fn create_group(&self, ctx: &Context<'_>, input: GroupFields) -> Result<GroupNode> {
+        let student_store = ctx.data_unchecked::<Arc<StudentStore>>().as_ref();
+        let group_store = ctx.data_unchecked::<Arc<GroupStore>>().as_ref();
+        match routes::add_group(group_store, student_store, &input.into()).await {
+            Ok(group) => Ok(GroupNode(group)),
+            Err(response) => Err(rest_error(response)),
+        }
+    }
+
+    async fn update_group(&self, ctx: &Context<'_>, id: ID, input: GroupFields) -> Result<GroupNode> {
+        let id = parse_id(&id)?;
+        let student_store = ctx.data_unchecked::<Arc<StudentStore>>().as_ref();
+        let group_store = ctx.data_unchecked::<Arc<GroupStore>>().as_ref();
+        if let Err(response) = routes::edit_group(group_store, student_store, id, &input.into()).await {
+            return Err(rest_error(response));
+        }
+        Self::fetch_group(ctx, id).await
+    }
+
+    async fn delete_group(&self, ctx: &Context<'_>, id: ID) -> Result<ID> {
+        let student_store = ctx.data_unchecked::<Arc<StudentStore>>().as_ref();
+        let group_store = ctx.data_unchecked::<Arc<GroupStore>>().as_ref();
+        if let Err(response) = routes::remove_group(group_store, student_store, parse_id(&id)?).await {
+            return Err(rest_error(response));
+        }
+        Ok(id)
+    }
+
+    async fn set_group_leader(&self, ctx: &Context<'_>, group_id: ID, student_id: ID) -> Result<GroupNode> {
+        let group_id = parse_id(&group_id)?;
+        let student_store = ctx.data_unchecked::<Arc<StudentStore>>().as_ref();
+        let group_store = ctx.data_unchecked::<Arc<GroupStore>>().as_ref();
+        if let Err(response) = routes::assign_group_leader(group_store, student_store, group_id, parse_id(&student_id)?).await {
+            return Err(rest_error(response));
+        }
+        Self::fetch_group(ctx, group_id).await
+    }
+
+    async fn clear_group_leader(&self, ctx: &Context<'_>, group_id: ID) -> Result<GroupNode> {
+        let group_id = parse_id(&group_id)?;
+        let student_store = ctx.data_unchecked::<Arc<StudentStore>>().as_ref();
+        let group_store = ctx.data_unchecked::<Arc<GroupStore>>().as_ref();
+        if let Err(response) = routes::clear_group_leader(group_store, student_store, group_id).await {
+            return Err(rest_error(response));
+        }
+        Self::fetch_group(ctx, group_id).await
+    }
+}
+
+async fn execute(
+    schema: web::Data<ApiSchema>,
+    request: GraphQLRequest,
+    student_store: web::Data<StudentStore>,
+    group_store: web::Data<GroupStore>
+) -> GraphQLResponse {
+    let student_store = student_store.into_inner();
+    let group_store = group_store.into_inner();
+    // Loaders cache per request, so every request starts from fresh data.
+    let request = request.into_inner()
+        .data(DataLoader::new(StudentLoader(student_store.clone()), actix_web::rt::spawn))
+        .data(DataLoader::new(MembersLoader(student_store.clone()), actix_web::rt::spawn))
+        .data(DataLoader::new(PhotoLoader(student_store.clone()), actix_web::rt::spawn))
+        .data(DataLoader::new(GroupLoader(group_store.clone()), actix_web::rt::spawn))
+        .data(student_store)
+        .data(group_store);
+    schema.execute(request).await.into()
+}
+
+async fn graphiql() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(GraphiQLSource::build().endpoint("/graphql").finish())
+}
+
+pub(crate) fn configure_routes(cfg: &mut web::ServiceConfig) {
+    let schema = Schema::build(QueryRoot, MutationRoot, EmptySubscription)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish();
+    cfg.app_data(web::Data::new(schema));
+    cfg.service(
+        web::resource("/graphql")
+            .route(web::post().to(execute))
+            .route(web::get().to(graphiql))
+    );
+}
+
@@ -0,0 +1,411 @@
+//! Where groups live. They are kept in Mongo unless `GROUP_STORE=postgres`, in
+//! which case they share Postgres with the students, classes and assessments
+//! that refer to them, and foreign keys guard those references.
+
+use async_trait::async_trait;
+use futures::TryStreamExt;
+use mongodb::bson::{doc, Document};
+use mongodb::options::{FindOneOptions, FindOptions, UpdateOptions};
+use mongodb::Collection;
+use sqlx::{PgConnection, PgPool};
+
+use crate::routes::{GroupFilter, GroupInput};
+use crate::storage::{Group, StoreError, StudyForm};
+
+/// The group store the server was started with.
+pub(crate) type GroupStore = dyn GroupRepository;
+
+#[async_trait]
+pub(crate) trait GroupRepository: Send + Sync {
+    /// Groups matching `filter` ordered by id; `page` is `(offset, limit)`.
+    async fn find_all(&self, filter: &GroupFilter, page: Option<(u64, i64)>) -> Result<Vec<Group>, StoreError>;
+
+    async fn find_many(&self, ids: &[i32]) -> Result<Vec<Group>, StoreError>;
+
+    async fn find(&self, id: i32) -> Result<Option<Group>, StoreError>;
+
+    /// Stores a new group under the next free id.
+    async fn insert(&self, group: &GroupInput) -> Result<Group, StoreError>;
+
+    /// Returns whether the group exists.
+    async fn update(&self, id: i32, group: &GroupInput) -> Result<bool, StoreError>;
+
+    /// Deleting a group that does not exist is not an error.
+    async fn delete(&self, id: i32) -> Result<(), StoreError>;
+
+    /// Deletes the group on `conn`, so that the deletion commits or rolls back
+    /// with the transaction it runs in. Returns `false` without deleting anything
+    /// when groups are not kept in Postgres.
+    async fn delete_in_postgres(&self, _conn: &mut PgConnection, _id: i32) -> Result<bool, StoreError> {
+        Ok(false)
+    }
+
+    /// Returns whether the group exists.
+    async fn set_leader(&self, id: i32, leader_id: Option<i32>) -> Result<bool, StoreError>;
+
+    /// Unsets the leader of every group led by `student_id`, except `keep_group`,
+    /// where the student still is a member. Returns how many groups changed.
+    async fn clear_leader_for_student(&self, student_id: i32, keep_group: Option<i32>) -> Result<u64, StoreError>;
+
+    async fn hand_over_leadership(&self, from: i32, to: i32) -> Result<(), StoreError>;
+
+    /// Adds those of `groups` whose id is free and returns how many were added.
+    async fn insert_missing(&self, groups: &[Group]) -> Result<u64, StoreError>;
+
+    /// Makes the store hold exactly `groups`.
+    async fn replace_all(&self, groups: &[Group]) -> Result<(), StoreError>;
+}
+
+pub(crate) struct MongoGroups(pub(crate) Collection<Group>);
+
+fn filter_document(filter: &GroupFilter) -> Document {
+    let mut document = Document::new();
+    if let Some(faculty) = &filter.faculty {
+        document.insert("faculty", faculty);
+    }
+    if let Some(specialty_code) = &filter.specialty_code {
+        document.insert("specialty_code", specialty_code);
+    }
+    if let Some(admission_year) = filter.admission_year {
+        document.insert("admission_year", admission_year);
+    }
+    if let Some(study_form) = filter.study_form {
+        document.insert("study_form", study_form.as_str());
+    }
+    document
+}
+
+#[async_trait]
+impl GroupRepository for MongoGroups {
+    async fn find_all(&self, filter: &GroupFilter, page: Option<(u64, i64)>) -> Result<Vec<Group>, StoreError> {
+        let (skip, limit) = page.unzip();
+        let options = FindOptions::builder().sort(doc! { "id": 1 }).skip(skip).limit(limit).build();
+        Ok(self.0.find(filter_document(filter), options).await?.try_collect().await?)
+    }
+
+    async fn find_many(&self, ids: &[i32]) -> Result<Vec<Group>, StoreError> {
+        Ok(self.0.find(doc! { "id": { "$in": ids } }, None).await?.try_collect().await?)
+    }
+
+    async fn find(&self, id: i32) -> Result<Option<Group>, StoreError> {
+        Ok(self.0.find_one(doc! { "id": id }, None).await?)
+    }
+
+    async fn insert(&self, group: &GroupInput) -> Result<Group, StoreError> {
+        let last = self.0.find_one(None, FindOneOptions::builder().sort(doc! { "id": -1 }).build()).await?;
+        let new_group = Group {
+            id: last.map_or(0, |group| group.id + 1),
+            name: group.name.clone(),
+            leader_id: None,
+            faculty: group.faculty.clone().flatten(),
+            specialty_code: group.specialty_code.clone().flatten(),
+            admission_year: group.admission_year.flatten(),
+            study_form: group.study_form.flatten(),
+            max_capacity: group.max_capacity.flatten(),
+        };
+        self.0.insert_one(&new_group, None).await?;
+        Ok(new_group)
+    }
+
+    async fn update(&self, id: i32, group: &GroupInput) -> Result<bool, StoreError> {
+        let mut changes = doc! { "name": &group.name };
+        if let Some(faculty) = &group.faculty {
+            changes.insert("faculty", faculty);
+        }
+        if let Some(specialty_code) = &group.specialty_code {
+            changes.insert("specialty_code", specialty_code);
+        }
+        if let Some(admission_year) = group.admission_year {
+            changes.insert("admission_year", admission_year);
+        }
+        if let Some(study_form) = group.study_form {
+            changes.insert("study_form", study_form.map(StudyForm::as_str));
+        }
+        if let Some(max_capacity) = group.max_capacity {
+            changes.insert("max_capacity", max_capacity);
+        }
+        let result = self.0.update_one(doc! { "id": id }, doc! { "$set": changes }, None).await?;
+        Ok(result.matched_count > 0)
+    }
+
+    async fn delete(&self, id: i32) -> Result<(), StoreError> {
+        self.0.delete_one(doc! { "id": id }, None).await?;
+        Ok(())
+    }
+
+    async fn set_leader(&self, id: i32, leader_id: Option<i32>) -> Result<bool, StoreError> {
+        let result = self.0.update_one(doc! { "id": id }, doc! { "$set": { "leader_id": leader_id } }, None).await?;
+        Ok(result.matched_count > 0)
+    }
+
+    async fn clear_leader_for_student(&self, student_id: i32, keep_group: Option<i32>) -> Result<u64, StoreError> {
+        let filter = match keep_group {
+            Some(group_id) => doc! { "leader_id": student_id, "id": { "$ne": group_id } },
+            None => doc! { "leader_id": student_id },
+        };
+        let result = self.0.update_many(filter, doc! { "$set": { "leader_id": null } }, None).await?;
+        Ok(result.modified_count)
+    }
+
+    async fn hand_over_leadership(&self, from: i32, to: i32) -> Result<(), StoreError> {
+        self.0.update_many(doc! { "leader_id": from }, doc! { "$set": { "leader_id": to } }, None).await?;
+        Ok(())
+    }
+
+    async fn insert_missing(&self, groups: &[Group]) -> Result<u64, StoreError> {
+        let mut inserted = 0;
+        for group in groups {
+            let result = self.0.update_one(
+                doc! { "id": group.id },
+                doc! { "$setOnInsert": mongodb::bson::to_document(group).map_err(mongodb::error::Error::from)? },
+                UpdateOptions::builder().upsert(true).build()
+            ).await?;
+            if result.upserted_id.is_some() {
+                inserted += 1;
+            }
+        }
+        Ok(inserted)
+    }
+
+    async fn replace_all(&self, groups: &[Group]) -> Result<(), StoreError> {
+        self.0.delete_many(doc! {}, None).await?;
+        if !groups.is_empty() {
+            self.0.insert_many(groups, None).await?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) struct PostgresGroups(pub(crate) PgPool);
+
+const GROUP_COLUMNS: &str = "id, name, leader_id, faculty, specialty_code, admission_year, study_form, max_capacity";
+
+/// Tables whose `group_id` refers to a group, and the foreign keys that guard
+/// them while groups are kept in Postgres.
+const GROUP_REFERENCES: [(&str, &str); 4] = [
+    ("students_blob", "students_blob_group_id_fkey"),
+    ("students_fs", "students_fs_group_id_fkey"),
+    ("schedule_slots", "schedule_slots_group_id_fkey"),
+    ("assessments", "assessments_group_id_fkey"),
+];
+
+#[async_trait]
+impl GroupRepository for PostgresGroups {
+    async fn find_all(&self, filter: &GroupFilter, page: Option<(u64, i64)>) -> Result<Vec<Group>, StoreError> {
+        let (offset, limit) = page.unzip();
+        let query = format!(
+            "SELECT {GROUP_COLUMNS} FROM groups
+             WHERE ($1::VARCHAR IS NULL OR faculty = $1) AND ($2::VARCHAR IS NULL OR specialty_code = $2)
+                 AND ($3::INT IS NULL OR admission_year = $3) AND ($4::VARCHAR IS NULL OR study_form = $4)
+             ORDER BY id LIMIT $5 OFFSET $6"
+        );
+        Ok(sqlx::query_as::<_, Group>(&query)
+            .bind(&filter.faculty)
+            .bind(&filter.specialty_code)
+            .bind(filter.admission_year)
+            .bind(filter.study_form)
+            .bind(limit)
+            .bind(offset.map_or(0, |offset| offset as i64))
+            .fetch_all(&self.0)
+            .await?)
+    }
+
+    async fn find_many(&self, ids: &[i32]) -> Result<Vec<Group>, StoreError> {
+        Ok(sqlx::query_as::<_, Group>(&format!("SELECT {GROUP_COLUMNS} FROM groups WHERE id = ANY($1)"))
+            .bind(ids)
+            .fetch_all(&self.0)
+            .await?)
+    }
+
+    async fn find(&self, id: i32) -> Result<Option<Group>, StoreError> {
+        Ok(sqlx::query_as::<_, Group>(&format!("SELECT {GROUP_COLUMNS} FROM groups WHERE id = $1"))
+            .bind(id)
+            .fetch_optional(&self.0)
+            .await?)
+    }
+
+    async fn insert(&self, group: &GroupInput) -> Result<Group, StoreError> {
+        let mut tx = self.0.begin().await?;
+        // Ids are assigned one past the largest, as in Mongo; the lock keeps
+        // concurrent inserts from taking the same one.
+        sqlx::query("LOCK TABLE groups IN SHARE ROW EXCLUSIVE MODE").execute(&mut *tx).await?;
+        let query = format!(
+            "INSERT INTO groups (id, name, faculty, specialty_code, admission_year, study_form, max_capacity)
+             SELECT COALESCE(MAX(id) + 1, 0), $1, $2, $3, $4, $5, $6 FROM groups
+             RETURNING {GROUP_COLUMNS}"
+        );
+        let new_group = sqlx::query_as::<_, Group>(&query)
+            .bind(&group.name)
+            .bind(group.faculty.as_ref().and_then(Option::as_ref))
+            .bind(group.specialty_code.as_ref().and_then(Option::as_ref))
+            .bind(group.admission_year.flatten())
+            .bind(group.study_form.flatten())
+            .bind(group.max_capacity.flatten())
+            .fetch_one(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(new_group)
+    }
+
+    async fn update(&self, id: i32, group: &GroupInput) -> Result<bool, StoreError> {
+        // Fields missing from `$8` were not sent and keep their value.
+        let result = sqlx::query(
+            "UPDATE groups
+             SET name = $2,
+                 faculty = CASE WHEN 'faculty' = ANY($8) THEN $3 ELSE faculty END,
+                 specialty_code = CASE WHEN 'specialty_code' = ANY($8) THEN $4 ELSE specialty_code END,
+                 admission_year = CASE WHEN 'admission_year' = ANY($8) THEN $5 ELSE admission_year END,
+                 study_form = CASE WHEN 'study_form' = ANY($8) THEN $6 ELSE study_form END,
+                 max_capacity = CASE WHEN 'max_capacity' = ANY($8) THEN $7 ELSE max_capacity END
+             WHERE id = $1"
+        )
+            .bind(id)
+            .bind(&group.name)
+            .bind(group.faculty.as_ref().and_then(Option::as_ref))
+            .bind(group.specialty_code.as_ref().and_then(Option::as_ref))
+            .bind(group.admission_year.flatten())
+            .bind(group.study_form.flatten())
+            .bind(group.max_capacity.flatten())
+            .bind(group.sent_fields())
+            .execute(&self.0)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn delete(&self, id: i32) -> Result<(), StoreError> {
+        sqlx::query("DELETE FROM groups WHERE id = $1").bind(id).execute(&self.0).await?;
+        Ok(())
+    }
+
+    async fn delete_in_postgres(&self, conn: &mut PgConnection, id: i32) -> Result<bool, StoreError> {
+        sqlx::query("DELETE FROM groups WHERE id = $1").bind(id).execute(conn).await?;
+        Ok(true)
+    }
+
+    async fn set_leader(&self, id: i32, leader_id: Option<i32>) -> Result<bool, StoreError> {
+        let result = sqlx::query("UPDATE groups SET leader_id = $2 WHERE id = $1")
+            .bind(id)
+            .bind(leader_id)
+            .execute(&self.0)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn clear_leader_for_student(&self, student_id: i32, keep_group: Option<i32>) -> Result<u64, StoreError> {
+        let result = sqlx::query("UPDATE groups SET leader_id = NULL WHERE leader_id = $1 AND id IS DISTINCT FROM $2")
+            .bind(student_id)
+            .bind(keep_group)
+            .execute(&self.0)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn hand_over_leadership(&self, from: i32, to: i32) -> Result<(), StoreError> {
+        sqlx::query("UPDATE groups SET leader_id = $2 WHERE leader_id = $1")
+            .bind(from)
+            .bind(to)
+            .execute(&self.0)
+            .await?;
+        Ok(())
+    }
+
+    async fn insert_missing(&self, groups: &[Group]) -> Result<u64, StoreError> {
+        let mut inserted = 0;
+        let mut tx = self.0.begin().await?;
+        for group in groups {
+            inserted += insert_group(&mut tx, group, "ON CONFLICT (id) DO NOTHING").await?;
+        }
+        tx.commit().await?;
+        Ok(inserted)
+    }
+
+    async fn replace_all(&self, groups: &[Group]) -> Result<(), StoreError> {
+        let mut tx = self.0.begin().await?;
+        for group in groups {
+            let upsert = "ON CONFLICT (id) DO UPDATE SET name = EXCLUDED.name, leader_id = EXCLUDED.leader_id,
+                faculty = EXCLUDED.faculty, specialty_code = EXCLUDED.specialty_code,
+                admission_year = EXCLUDED.admission_year, study_form = EXCLUDED.study_form,
+                max_capacity = EXCLUDED.max_capacity";
+            insert_group(&mut tx, group, upsert).await?;
+        }
+        // Fails while the foreign keys are in place and something still refers
+        // to a group that is not among `groups`.
+        let ids: Vec<i32> = groups.iter().map(|group| group.id).collect();
+        sqlx::query("DELETE FROM groups WHERE id <> ALL($1)").bind(&ids).execute(&mut *tx).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+async fn insert_group(conn: &mut sqlx::PgConnection, group: &Group, on_conflict: &str) -> Result<u64, sqlx::Error> {
+    let query = format!(
+        "INSERT INTO groups ({GROUP_COLUMNS}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) {on_conflict}"
+    );
+    let result = sqlx::query(&query)
+        .bind(group.id)
+        .bind(&group.name)
+        .bind(group.leader_id)
+        .bind(&group.faculty)
+        .bind(&group.specialty_code)
+        .bind(group.admission_year)
+        .bind(group.study_form)
+        .bind(group.max_capacity)
+        .execute(conn)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+/// Adds the foreign keys from `group_id` columns to `groups` that are missing.
+/// Fails if anything refers to a group Postgres does not have.
+pub(crate) async fn add_foreign_keys(pool: &PgPool) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    for (table, constraint) in GROUP_REFERENCES {
+        let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = $1)")
+            .bind(constraint)
+            .fetch_one(&mut *tx)
+            .await?;
+        if !exists {
+            let query = format!(
+                "ALTER TABLE {table} ADD CONSTRAINT {constraint} FOREIGN KEY (group_id) REFERENCES groups (id)"
+            );
+            sqlx::query(&query).execute(&mut *tx).await?;
+            log::info!("Added foreign key {}", constraint);
+        }
+    }
+    tx.commit().await
+}
+
+/// Drops the foreign keys to `groups`, which Postgres cannot keep once groups
+/// move to Mongo.
+pub(crate) async fn drop_foreign_keys(pool: &PgPool) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    for (table, constraint) in GROUP_REFERENCES {
+        sqlx::query(&format!("ALTER TABLE {table} DROP CONSTRAINT IF EXISTS {constraint}"))
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await
+}
+
+/// Moves groups to the store named by `target`, `mongo` or `postgres`. The
+/// target ends up holding exactly the groups of the other store, which is left
+/// as it was. Foreign keys are added when groups move to Postgres and dropped
+/// when they leave it.
+pub(crate) async fn migrate(pool: &PgPool, mongo: &MongoGroups, target: &str) -> Result<(), String> {
+    let postgres = PostgresGroups(pool.clone());
+    let (source, destination): (&GroupStore, &GroupStore) = match target {
+        "postgres" => (mongo, &postgres),
+        "mongo" => (&postgres, mongo),
+        _ => return Err(format!("Unknown group store: {target}")),
+    };
+    let groups = source.find_all(&GroupFilter::default(), None).await.map_err(|e| e.to_string())?;
+    destination.replace_all(&groups).await.map_err(|e| format!("Failed to copy groups: {e}"))?;
+    match target {
+        "postgres" => add_foreign_keys(pool)
+            .await
+            .map_err(|e| format!("Failed to reference groups in Postgres, some group is missing: {e}"))?,
+        _ => drop_foreign_keys(pool).await.map_err(|e| e.to_string())?,
+    }
+    log::info!("Copied {} groups to {}; set GROUP_STORE={} and restart the server", groups.len(), target, target);
+    Ok(())
+}
@@ -0,0 +1,483 @@
+//! gRPC counterpart of the student and group endpoints for internal services.
+//! Every call runs the same validation and storage code as the REST handlers.
+
+// `tonic::Status` is large, but it is the error type every service method returns.
+#![allow(clippy::result_large_err)]
+
+use actix_web::http::StatusCode;
+use actix_web::HttpResponse;
+use chrono::NaiveDate;
+use futures::Stream;
+use tonic::{Request, Response, Status, Streaming};
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use crate::groups::GroupStore;
+use crate::profile::{self, StudentProfile};
+use crate::routes::{self, GroupFilter, GroupInput, StudentForm};
+use crate::storage::{Group, StoreError, Student, StudyForm};
+use crate::students::StudentStore;
+
+mod proto {
+    tonic::include_proto!("student_management.v1");
+}
+
+use proto::group_service_server::{GroupService, GroupServiceServer};
+use proto::photo_chunk::Part;
+use proto::student_service_server::{StudentService, StudentServiceServer};
+
+/// Size of the data messages a photo is streamed in.
+const PHOTO_CHUNK_SIZE: usize = 64 * 1024;
+/// Largest photo an upload may stream; the upload is cut off once it sends more.
+const MAX_PHOTO_SIZE: usize = 5 * 1024 * 1024;
+
+/// Translates an error response of the shared REST logic into the closest gRPC status.
+fn rest_status(response: HttpResponse) -> Status {
+    let (status, message) = routes::error_parts(response);
+    match status {
+        StatusCode::BAD_REQUEST => Status::invalid_argument(message),
+        StatusCode::NOT_FOUND => Status::not_found(message),
+        StatusCode::CONFLICT => Status::failed_precondition(message),
+        _ => Status::internal(message),
+    }
+}
+
+fn store_status(e: StoreError) -> Status {
+    log::error!("Store error in gRPC call: {}", e);
+    Status::internal(e.to_string())
+}
+
+fn student_status(status: profile::StudentStatus) -> proto::StudentStatus {
+    match status {
+        profile::StudentStatus::Active => proto::StudentStatus::Active,
+        profile::StudentStatus::AcademicLeave => proto::StudentStatus::AcademicLeave,
+        profile::StudentStatus::Expelled => proto::StudentStatus::Expelled,
+        profile::StudentStatus::Graduated => proto::StudentStatus::Graduated,
+    }
+}
+
+/// `None` for an unspecified status, which means "any" in filters.
+fn parse_student_status(value: i32) -> Result<Option<profile::StudentStatus>, Status> {
+    match proto::StudentStatus::try_from(value) {
+        Ok(proto::StudentStatus::Unspecified) => Ok(None),
+        Ok(proto::StudentStatus::Active) => Ok(Some(profile::StudentStatus::Active)),
+        Ok(proto::StudentStatus::AcademicLeave) => Ok(Some(profile::StudentStatus::AcademicLeave)),
+        Ok(proto::StudentStatus::Expelled) => Ok(Some(profile::StudentStatus::Expelled)),
+        Ok(proto::StudentStatus::Graduated) => Ok(Some(profile::StudentStatus::Graduated)),
+        Err(_) => Err(Status::invalid_argument(format!("Unknown student status: {value}"))),
+    }
+}
+
+/// `None` for an unspecified study form.
+fn parse_study_form(value: i32) -> Result<Option<StudyForm>, Status> {
+    match proto::StudyForm::try_from(value) {
+        Ok(proto::StudyForm::Unspecified) => Ok(None),
+        Ok(proto::StudyForm::FullTime) => Ok(Some(StudyForm::FullTime)),
+        Ok(proto::StudyForm::PartTime) => Ok(Some(StudyForm::PartTime)),
+        Ok(proto::StudyForm::Distance) => Ok(Some(StudyForm::Distance)),
+        Err(_) => Err(Status::invalid_argument(format!("Unknown study form: {value}"))),
+    }
+}
+
+fn study_form(value: Option<StudyForm>) -> proto::StudyForm {
+    match value {
+        None => proto::StudyForm::Unspecified,
+        Some(StudyForm::FullTime) => proto::StudyForm::FullTime,
+        Some(StudyForm::PartTime) => proto::StudyForm::PartTime,
+        Some(StudyForm::Distance) => proto::StudyForm::Distance,
+    }
+}
+
+impl From<Student> for proto::Student {
+    fn from(student: Student) -> Self {
+        // Statuses are written by this server only, so an unknown one cannot occur.
+        let status = student.status.parse().map_or(proto::StudentStatus::Unspecified, student_status);
+        proto::Student {
+            id: student.id,
+            name: student.name,
+            surname: student.surname,
+            name_latin: student.name_latin,
+            surname_latin: student.surname_latin,
+            group_id: student.group_id,
+            patronymic: student.profile.patronymic,
+            email: student.profile.email,
+            phone: student.profile.phone,
+            birth_date: student.profile.birth_date.map(|date| date.format("%Y-%m-%d").to_string()),
+            record_book_number: student.profile.record_book_number,
+            status: status.into(),
+        }
+    }
+}
+
+impl From<Group> for proto::Group {
+    fn from(group: Group) -> Self {
+        proto::Group {
+            id: group.id,
+            name: group.name,
+            leader_id: group.leader_id,
+            faculty: group.faculty,
+            specialty_code: group.specialty_code,
+            admission_year: group.admission_year,
+            study_form: study_form(group.study_form).into(),
+            max_capacity: group.max_capacity,
+        }
+    }
+}
+
+/// Group and profile fields left unset are not sent: an update keeps their value.
+fn student_form(input: proto::StudentInput) -> Result<StudentForm, Status> {
+    let mut kept_fields = vec![];
+    if input.group_id.is_none() {
+        kept_fields.push("group_id");
+    }
+    let sent = [
+        input.patronymic.is_some(),
+        input.email.is_some(),
+        input.phone.is_some(),
+        input.birth_date.is_some(),
+        input.record_book_number.is_some(),
+    ];
+    let birth_date = match input.birth_date.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(date) => Some(
+            NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .map_err(|e| Status::invalid_argument(format!("Invalid date of birth: {e}")))?,
+        ),
+    };
+    Ok(StudentForm {
+        name: input.name,
+        surname: input.surname,
+        group_id: input.group_id,
+        profile: StudentProfile {
+            patronymic: input.patronymic,
+            email: input.email,
+            phone: input.phone,
+            birth_date,
+            record_book_number: input.record_book_number,
+        },
+        kept_fields: kept_fields.into_iter().chain(profile::unsent_columns(sent)).collect(),
+        ..Default::default()
+    })
+}
+
+/// Fields left unset are not sent: an update keeps their value.
+fn group_input(input: proto::GroupInput) -> Result<GroupInput, Status> {
+    Ok(GroupInput {
+        name: input.name,
+        faculty: input.faculty.map(Some),
+        specialty_code: input.specialty_code.map(Some),
+        admission_year: input.admission_year.map(Some),
+        study_form: parse_study_form(input.study_form)?.map(Some),
+        max_capacity: input.max_capacity.map(Some),
+    })
+}
+
+struct StudentApi {
+    students: Arc<StudentStore>,
+    group_store: Arc<GroupStore>,
+}
+
+impl StudentApi {
+    async fn fetch_student(&self, id: i32) -> Result<Student, Status> {
+        self.students.find(id)
+            .await
+            .map_err(store_status)?
+            .ok_or_else(|| Status::not_found(format!("Student {id} does not exist")))
+    }
+}
+
+type PhotoStream = Pin<Box<dyn Stream<Item = Result<proto::PhotoChunk, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl StudentService for StudentApi {
+    async fn list_students(
+        &self,
+        request: Request<proto::ListStudentsRequest>
+    ) -> Result<Response<proto::StudentList>, Status> {
+        let request = request.into_inner();
+        let status = parse_student_status(request.status)?;
+        let students = self.students.find_all(status, request.search.as_deref())
+            .await
+            .map_err(store_status)?;
+        log::debug!("Listed {} students over gRPC", students.len());
+        Ok(Response::new(proto::StudentList { students: students.into_iter().map(Into::into).collect() }))
+    }
+
+    async fn get_student(&self, request: Request<proto::StudentId>) -> Result<Response<proto::Student>, Status> {
+        Ok(Response::new(self.fetch_student(request.into_inner().id).await?.into()))
+    }
+
+    async fn create_student(&self, request: Request<proto::StudentInput>) -> Result<Response<proto::Student>, Status> {
+        let form = student_form(request.into_inner())?;
+        let id = routes::add_student(self.students.as_ref(), self.group_store.as_ref(), form).await.map_err(rest_status)?;
+        Ok(Response::new(self.fetch_student(id).await?.into()))
+    }
+
+    async fn update_student(
+        &self,
+        request: Request<proto::UpdateStudentRequest>
+    ) -> Result<Response<proto::Student>, Status> {
+        let request = request.into_inner();
+        let input = request.student.ok_or_else(|| Status::invalid_argument("student is required"))?;
+        routes::edit_student(self.students.as_ref(), self.group_store.as_ref(), request.id, student_form(input)?)
+            .await
+            .map_err(rest_status)?;
+        Ok(Response::new(self.fetch_student(request.id).await?.into()))
+    }
+
+    async fn delete_student(&self, request: Request<proto::StudentId>) -> Result<Response<()>, Status> {
+        routes::remove_student(self.students.as_ref(), self.group_store.as_ref(), request.into_inner().id)
+            .await
+            .map_err(rest_status)?;
+        Ok(Response::new(()))
+    }
+
+    async fn upload_photo(&self, request: Request<Streaming<proto::PhotoChunk>>) -> Result<Response<()>, Status> {
+        let mut stream = request.into_inner();
+        let header = match stream.message().await?.and_then(|chunk| chunk.part) {
+            Some(Part::Header(header)) => header,
+            _ => return Err(Status::invalid_argument("The first message must be the photo header")),
+        };
+        routes::check_photo_type(&header.content_type).map_err(rest_status)?;
+        let student = self.fetch_student(header.student_id).await?;
+
+        let mut image_data = Vec::new();
+        while let Some(chunk) = stream.message().await? {
+            match chunk.part {
+                Some(Part::Data(data)) if image_data.len() + data.len() > MAX_PHOTO_SIZE => {
+                    return Err(Status::resource_exhausted(format!(
+                        "A photo may be at most {} MB",
+                        MAX_PHOTO_SIZE / 1024 / 1024
+                    )));
+                },
+                Some(Part::Data(data)) => image_data.extend_from_slice(&data),
+                _ => return Err(Status::invalid_argument("Only photo data may follow the header")),
+            }
+        }
+        routes::validate_photo(&image_data, &header.content_type).map_err(rest_status)?;
+        log::debug!("Received {} byte photo for student id: {}", image_data.len(), student.id);
+
+        let form = StudentForm {
+            name: student.name,
+            surname: student.surname,
+            group_id: student.group_id,
+            profile: student.profile,
+            image_data: Some((image_data, header.content_type)),
+            ..Default::default()
+        };
+        routes::edit_student(self.students.as_ref(), self.group_store.as_ref(), student.id, form)
+            .await
+            .map_err(rest_status)?;
+        Ok(Response::new(()))
+    }
+
+    type DownloadPhotoStream = PhotoStream;
+
+    async fn download_photo(&self, request: Request<proto::StudentId>) -> Result<Response<PhotoStream>, Status> {
+        let id = request.into_inner().id;
+        let (image_data, content_type) = self.students.load_photo(id)
+            .await
+            .map_err(store_status)?
+            .ok_or_else(|| Status::not_found("The student does not exist or has no photo"))?;
+
+        let header = proto::PhotoChunk { part: Some(Part::Header(proto::PhotoHeader { student_id: id, content_type })) };
+        let chunks: Vec<_> = std::iter::once(header)
+            .chain(image_data.chunks(PHOTO_CHUNK_SIZE).map(|data| proto::PhotoChunk { part: Some(Part::Data(data.to_vec())) }))
+            .map(Ok)
+            .collect();
+        Ok(Response::new(Box::pin(futures::stream::iter(chunks))))
+    }
+}
+
+struct GroupApi {
+    students: Arc<StudentStore>,
+    group_store: Arc<GroupStore>,
+}
+
+impl GroupApi {
+    async fn fetch_group(&self, id: i32) -> Result<Group, Status> {
+        self.group_store.find(id)
+            .await
+            .map_err(store_status)?
+            .ok_or_else(|| Status::not_found(format!("Group {id} does not exist")))
+    }
+}
+
+#[tonic::async_trait]
+impl GroupService for GroupApi {
+    async fn list_groups(&self, request: Request<proto::ListGroupsRequest>) -> Result<Response<proto::GroupList>, Status> {
+        let request = request.into_inner();
+        let filter = GroupFilter {
+            faculty: request.faculty,
+            specialty_code: request.specialty_code,
+            admission_year: request.admission_year,
+            study_form: parse_study_form(request.study_form)?,
+        };
+        let groups = self.group_store.find_all(&filter, None)
+            .await
+            .map_err(store_status)?;
+        log::debug!("Listed {} groups over gRPC", groups.len());
+        Ok(Response::new(proto::GroupList { groups: groups.into_iter().map(Into::into).collect() }))
+    }
+
+    async fn get_group(&self, request: Request<proto::GroupId>) -> Result<Response<proto::Group>, Status> {
+        Ok(Response::new(self.fetch_group(request.into_inner().id).await?.into()))
+    }
+
+    async fn create_group(&self, request: Request<proto::GroupInput>) -> Result<Response<proto::Group>, Status> {
+        let input = group_input(request.into_inner())?;
+        let group = routes::add_group(self.group_store.as_ref(), self.students.as_ref(), &input).await.map_err(rest_status)?;
+        Ok(Response::new(group.into()))
+    }
+
+    async fn update_group(&self, request: Request<proto::UpdateGroupRequest>) -> Result<Response<proto::Group>, Status> {
+        let request = request.into_inner();
+        let input = request.group.ok_or_else(|| Status::invalid_argument("group is required"))?;
+        routes::edit_group(self.group_store.as_ref(), self.students.as_ref(), request.id, &group_input(input)?)
+            .await
+            .map_err(rest_status)?;
+        Ok(Response::new(self.fetch_group(request.id).await?.into()))
+    }
+
+    async fn delete_group(&self, request: Request<proto::GroupId>) -> Result<Response<()>, Status> {
+        routes::remove_group(self.group_store.as_ref(), self.students.as_ref(), request.into_inner().id)
+            .await
+            .map_err(rest_status)?;
+        Ok(Response::new(()))
+    }
+
+    async fn list_members(&self, request: Request<proto::GroupId>) -> Result<Response<proto::StudentList>, Status> {
+        let id = request.into_inner().id;
+        let group = self.fetch_group(id).await?;
+        let students = self.students.find_group_members(&[group.id])
+            .await
+            .map_err(store_status)?;
+        Ok(Response::new(proto::StudentList { students: students.into_iter().map(Into::into).collect() }))
+    }
+
+    async fn set_leader(&self, request: Request<proto::SetLeaderRequest>) -> Result<Response<proto::Group>, Status> {
+        let request = request.into_inner();
+        routes::assign_group_leader(self.group_store.as_ref(), self.students.as_ref(), request.group_id, request.student_id)
+            .await
+            .map_err(rest_status)?;
+        Ok(Response::new(self.fetch_group(request.group_id).await?.into()))
+    }
+
+    async fn clear_leader(&self, request: Request<proto::GroupId>) -> Result<Response<proto::Group>, Status> {
+        let id = request.into_inner().id;
+        routes::clear_group_leader(self.group_store.as_ref(), self.students.as_ref(), id).await.map_err(rest_status)?;
+        Ok(Response::new(self.fetch_group(id).await?.into()))
+    }
+}
+
+/// Serves `StudentService` and `GroupService` on `port` until `shutdown` completes.
+pub(crate) async fn serve(
+    port: u16,
+    students: Arc<StudentStore>,
+    group_store: Arc<GroupStore>,
+    shutdown: impl Future<Output = ()>
+) -> Result<(), tonic::transport::Error> {
+    let address = SocketAddr::from(([0, 0, 0, 0], port));
+    log::info!("Starting gRPC server on port {}", port);
+    tonic::transport::Server::builder()
+        .add_service(StudentServiceServer::new(StudentApi { students: students.clone(), group_store: group_store.clone() }))
+        .add_service(GroupServiceServer::new(GroupApi { students, group_store }))
+        .serve_with_shutdown(address, shutdown)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use proto::student_service_client::StudentServiceClient;
+    use tonic::transport::Channel;
+
+    use std::io::Cursor;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::memory::{InMemoryGroups, InMemoryStudents};
+
+    /// Serves both services from in-memory stores with group 1, and connects a client to them.
+    async fn client() -> StudentServiceClient<Channel> {
+        let group = Group {
+            id: 1,
+            name: "ІП-12".to_string(),
+            leader_id: None,
+            faculty: None,
+            specialty_code: None,
+            admission_year: None,
+            study_form: None,
+            max_capacity: None,
+        };
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let students = Arc::new(InMemoryStudents::default());
+        actix_web::rt::spawn(serve(port, students, Arc::new(InMemoryGroups::new([group])), futures::future::pending()));
+        for _ in 0..100 {
+            match StudentServiceClient::connect(format!("http://127.0.0.1:{port}")).await {
+                Ok(client) => return client,
+                Err(_) => actix_web::rt::time::sleep(Duration::from_millis(20)).await,
+            }
+        }
+        panic!("the gRPC server did not start listening on port {port}");
+    }
+
+    fn upload(student_id: i32, content_type: &str, data: &[u8]) -> impl Stream<Item = proto::PhotoChunk> + Send + 'static {
+        let header = proto::PhotoHeader { student_id, content_type: content_type.to_string() };
+        let chunks: Vec<_> = std::iter::once(Part::Header(header))
+            .chain(data.chunks(PHOTO_CHUNK_SIZE).map(|data| Part::Data(data.to_vec())))
+            .map(|part| proto::PhotoChunk { part: Some(part) })
+            .collect();
+        futures::stream::iter(chunks)
+    }
+
+    #[actix_web::test]
+    async fn student_photo_round_trip() {
+        let mut client = client().await;
+        let input = proto::StudentInput {
+            name: "Олена".to_string(),
+            surname: "Шевченко".to_string(),
+            group_id: Some(1),
+            ..Default::default()
+        };
+        let student = client.create_student(input).await.expect("the student is created").into_inner();
+        assert_eq!((student.name_latin.as_deref(), student.group_id), (Some("Olena"), Some(1)));
+
+        let mut png = Vec::new();
+        image::DynamicImage::new_rgb8(300, 300)
+            .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+            .expect("a PNG can be encoded");
+        client.upload_photo(upload(student.id, "image/png", &png)).await.expect("the photo is stored");
+
+        let mut chunks = client.download_photo(proto::StudentId { id: student.id }).await.unwrap().into_inner();
+        let mut downloaded = Vec::new();
+        while let Some(chunk) = chunks.message().await.unwrap() {
+            match chunk.part {
+                Some(Part::Header(header)) => assert_eq!(header.content_type, "image/png"),
+                Some(Part::Data(data)) => downloaded.extend_from_slice(&data),
+                None => panic!("empty photo message"),
+            }
+        }
+        assert_eq!(downloaded, png);
+    }
+
+    #[actix_web::test]
+    async fn oversized_photos_are_refused() {
+        let mut client = client().await;
+        let input = proto::StudentInput {
+            name: "Петро".to_string(),
+            surname: "Іваненко".to_string(),
+            group_id: Some(1),
+            ..Default::default()
+        };
+        let student = client.create_student(input).await.expect("the student is created").into_inner();
+
+        let status = client.upload_photo(upload(student.id, "image/png", &vec![0; MAX_PHOTO_SIZE + 1]))
+            .await
+            .expect_err("the photo is too large");
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted, "{status}");
+        assert!(client.download_photo(proto::StudentId { id: student.id }).await.is_err());
+    }
+}
@@ -0,0 +1,452 @@
+use actix_multipart::Multipart;
+use actix_web::{web, HttpResponse, Responder};
+use calamine::{Reader, Xlsx};
+use chrono::{Days, NaiveDate};
+use futures::{StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+use std::collections::{HashMap, HashSet};
+use std::io::Cursor;
+
+use crate::events::{self, Event, EventKind};
+use crate::groups::GroupStore;
+use crate::openapi::ServerError;
+use crate::profile::StudentProfile;
+use crate::translit::transliterate;
+use crate::routes::{database_error, GroupFilter};
+use crate::storage::{Group, STUDENT_TABLE_NAME};
+use crate::webhooks;
+
+/// Every XLSX workbook is a ZIP archive, so it is recognised by the local file header signature.
+const ZIP_SIGNATURE: &[u8] = b"PK\x03\x04";
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct ImportOptions {
+    /// Validate the file without importing anything.
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+struct RowError {
+    row: usize,
+    message: String,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ImportReport {
+    dry_run: bool,
+    total_rows: usize,
+    valid_rows: usize,
+    imported: usize,
+    errors: Vec<RowError>,
+}
+
+/// Multipart body of an import request, used to document the part read by [`read_upload`].
+#[derive(ToSchema)]
+#[allow(dead_code)]
+struct ImportUpload {
+    /// CSV or XLSX file with a header row naming the columns.
+    #[schema(content_media_type = "application/octet-stream")]
+    file: Vec<u8>,
+}
+
+struct ImportRow {
+    name: String,
+    surname: String,
+    group_id: i32,
+    profile: StudentProfile,
+}
+
+struct ColumnIndices {
+    name: usize,
+    surname: usize,
+    group: usize,
+    patronymic: Option<usize>,
+    email: Option<usize>,
+    phone: Option<usize>,
+    birth_date: Option<usize>,
+    record_book_number: Option<usize>,
+}
+
+impl ColumnIndices {
+    fn from_header(header: &[String]) -> Result<Self, String> {
+        let find = |names: &[&str]| {
+            header.iter().position(|column| {
+                let column = column.trim().to_lowercase();
+                names.contains(&column.as_str())
+            })
+        };
+        Ok(ColumnIndices {
+            name: find(&["name"]).ok_or("Missing column: name")?,
+            surname: find(&["surname"]).ok_or("Missing column: surname")?,
+            group: find(&["group", "group_id", "group_name"]).ok_or("Missing column: group")?,
+            patronymic: find(&["patronymic"]),
+            email: find(&["email"]),
+            phone: find(&["phone"]),
+            birth_date: find(&["birth_date"]),
+            record_book_number: find(&["record_book_number", "record_book"]),
+        })
+    }
+}
+
+async fn read_upload(mut payload: Multipart) -> Result<Vec<u8>, HttpResponse> {
+    let mut file_data = None;
+
+    while let Some(mut field) = payload.try_next().await.map_err(|e| {
+        HttpResponse::BadRequest().body(format!("Failed to read multipart payload: {e}"))
+    })? {
+        let field_name = field.content_disposition()
+            .get_name()
+            .unwrap_or("")
+            .to_string();
+
+        if field_name != "file" {
+            return Err(HttpResponse::BadRequest()
+                .body(format!("Unrecognized key: {field_name}")));
+        }
+
+        let mut data = Vec::new();
+        while let Some(chunk) = field.next().await {
+            match chunk {
+                Ok(chunk) => data.extend_from_slice(&chunk),
+                Err(e) => return Err(HttpResponse::BadRequest()
+                    .body(format!("Failed to read file data: {e}")))
+            }
+        }
+        file_data = Some(data);
+    }
+
+    match file_data {
+        Some(data) if !data.is_empty() => Ok(data),
+        _ => Err(HttpResponse::BadRequest().body("Empty or missing file")),
+    }
+}
+
+fn parse_csv(data: &[u8]) -> Result<Vec<Vec<String>>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(data);
+
+    reader.records()
+        .map(|record| {
+            record
+                .map(|record| record.iter().map(str::to_string).collect())
+                .map_err(|e| format!("Invalid CSV: {e}"))
+        })
+        .collect()
+}
+
+fn parse_xlsx(data: Vec<u8>) -> Result<Vec<Vec<String>>, String> {
+    let mut workbook: Xlsx<_> = Xlsx::new(Cursor::new(data))
+        .map_err(|e| format!("Invalid XLSX: {e}"))?;
+    let range = workbook.worksheet_range_at(0)
+        .ok_or("XLSX workbook has no sheets")?
+        .map_err(|e| format!("Invalid XLSX sheet: {e}"))?;
+
+    Ok(range.rows()
+        .map(|row| row.iter().map(|cell| cell.to_string().trim().to_string()).collect())
+        .collect())
+}
+
+/// Accepts ISO dates as well as the day serials XLSX uses for date cells.
+fn parse_birth_date(value: &str) -> Result<Option<NaiveDate>, String> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(Some(date));
+    }
+    let excel_epoch = NaiveDate::from_ymd_opt(1899, 12, 30).expect("valid date");
+    value.parse::<f64>()
+        .ok()
+        .filter(|serial| serial.is_finite() && *serial >= 1.0 && *serial < 1e6)
+        .and_then(|serial| excel_epoch.checked_add_days(Days::new(serial as u64)))
+        .map(Some)
+        .ok_or(format!("Invalid date of birth: {value}"))
+}
+
+/// Resolves the `group` column, which holds either a numeric id or a group name like "ІП-11".
+fn resolve_group(
+    value: &str,
+    by_id: &HashMap<i32, &Group>,
+    by_name: &HashMap<String, Vec<i32>>
+) -> Result<i32, String> {
+    if let Ok(group_id) = value.parse::<i32>() {
+        return match by_id.contains_key(&group_id) {
+            true => Ok(group_id),
+            false => Err(format!("Group with id {group_id} does not exist")),
+        };
+    }
+
+    match by_name.get(&value.to_lowercase()).map(Vec::as_slice) {
+        Some([group_id]) => Ok(*group_id),
+        Some(_) => Err(format!("Group name {value} is ambiguous, use the group id instead")),
+        None => Err(format!("Group {value} does not exist")),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/students/import",
+    tag = "students",
+    params(ImportOptions),
+    request_body(content = inline(ImportUpload), content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "Every row was valid and, unless this is a dry run, imported", body = ImportReport),
+        (status = 400, description = "The file could not be read, or some rows are invalid and nothing was imported", body = ImportReport),
+        (status = 500, response = ServerError)
+    )
+)]
+pub(crate) async fn import_students(
+    options: web::Query<ImportOptions>,
+    payload: Multipart,
+    pool: web::Data<PgPool>,
+    group_store: web::Data<GroupStore>
+) -> impl Responder {
+    log::debug!("Processing student import request, dry run: {}", options.dry_run);
+    let data = match read_upload(payload).await {
+        Ok(data) => data,
+        Err(response) => {
+            log::error!("Failed to read import file");
+            return response;
+        }
+    };
+
+    let parsed = match data.starts_with(ZIP_SIGNATURE) {
+        true => parse_xlsx(data),
+        false => parse_csv(&data),
+    };
+    let mut rows = match parsed {
+        Ok(rows) => rows.into_iter(),
+        Err(e) => {
+            log::warn!("Failed to parse import file: {}", e);
+            return HttpResponse::BadRequest().body(e);
+        }
+    };
+
+    let columns = match rows.next().map(|header| ColumnIndices::from_header(&header)) {
+        Some(Ok(columns)) => columns,
+        Some(Err(e)) => return HttpResponse::BadRequest().body(e),
+        None => return HttpResponse::BadRequest().body("Import file is empty"),
+    };
+    let rows: Vec<Vec<String>> = rows.collect();
+
+    let groups = match group_store.find_all(&GroupFilter::default(), None).await {
+        Ok(groups) => groups,
+        Err(e) => {
+            log::error!("Failed to fetch groups: {}", e);
+            return HttpResponse::InternalServerError().body(e.to_string());
+        }
+    };
+    let by_id: HashMap<i32, &Group> = groups.iter().map(|group| (group.id, group)).collect();
+    let mut by_name: HashMap<String, Vec<i32>> = HashMap::new();
+    for group in &groups {
+        by_name.entry(group.name.trim().to_lowercase()).or_default().push(group.id);
+    }
+
+    let query = format!(
+        "SELECT group_id, COUNT(*) FROM {} WHERE group_id IS NOT NULL GROUP BY group_id",
+        *STUDENT_TABLE_NAME
+    );
+    let mut group_sizes: HashMap<i32, i64> = match sqlx::query_as::<_, (i32, i64)>(&query)
+        .fetch_all(pool.get_ref())
+        .await
+    {
+        Ok(counts) => counts.into_iter().collect(),
+        Err(e) => {
+            log::error!("Error counting students per group: {}", e);
+            return HttpResponse::InternalServerError().body(e.to_string());
+        }
+    };
+
+    // Numbers already in use are looked up first, so that a row they reject never
+    // takes up a place in its group.
+    let numbers: Vec<&str> = match columns.record_book_number {
+        Some(index) => rows
+            .iter()
+            .filter_map(|record| record.get(index))
+            .map(|cell| cell.trim())
+            .filter(|cell| !cell.is_empty())
+            .collect(),
+        None => Vec::new(),
+    };
+    let query = format!(
+        "SELECT record_book_number FROM {} WHERE record_book_number = ANY($1)",
+        *STUDENT_TABLE_NAME
+    );
+    let taken: HashSet<String> = match sqlx::query_scalar::<_, String>(&query)
+        .bind(&numbers)
+        .fetch_all(pool.get_ref())
+        .await
+    {
+        Ok(taken) => taken.into_iter().collect(),
+        Err(e) => {
+            log::error!("Error checking record book numbers: {}", e);
+            return HttpResponse::InternalServerError().body(e.to_string());
+        }
+    };
+
+    let mut valid_rows = Vec::new();
+    let mut errors = Vec::new();
+    let mut record_books = HashSet::new();
+    let mut total_rows = 0;
+
+    // Spreadsheet row numbers: the header is row 1.
+    for (row_number, record) in (2..).zip(&rows) {
+        if record.iter().all(|cell| cell.is_empty()) {
+            continue;
+        }
+        total_rows += 1;
+
+        let cell = |index: usize| record.get(index).map(String::as_str).unwrap_or("");
+        let optional_cell = |index: Option<usize>| index.map(cell).unwrap_or("");
+        let (name, surname, group) = (cell(columns.name), cell(columns.surname), cell(columns.group));
+        let details = parse_birth_date(optional_cell(columns.birth_date)).and_then(|birth_date| {
+            StudentProfile {
+                patronymic: Some(optional_cell(columns.patronymic).to_string()),
+                email: Some(optional_cell(columns.email).to_string()),
+                phone: Some(optional_cell(columns.phone).to_string()),
+                birth_date,
+                record_book_number: Some(optional_cell(columns.record_book_number).to_string()),
+            }.normalize()
+        }).and_then(|profile| match &profile.record_book_number {
+            Some(number) if taken.contains(number) => Err(format!("Record book number {number} is already in use")),
+            Some(number) if !record_books.insert(number.clone()) => {
+                Err(format!("Record book number {number} appears more than once"))
+            },
+            _ => Ok(profile),
+        });
+
+        let result = if name.is_empty() {
+            Err("Name must not be empty".to_string())
+        } else if surname.is_empty() {
+            Err("Surname must not be empty".to_string())
+        } else if let Err(e) = &details {
+            Err(e.clone())
+        } else {
+            resolve_group(group, &by_id, &by_name).and_then(|group_id| {
+                let size = group_sizes.entry(group_id).or_insert(0);
+                match by_id[&group_id].max_capacity {
+                    Some(capacity) if *size >= i64::from(capacity) => {
+                        Err(format!("Group {} is at full capacity ({} students)", by_id[&group_id].name, capacity))
+                    },
+                    _ => {
+                        *size += 1;
+                        Ok(group_id)
+                    }
+                }
+            })
+        };
+
+        match (result, details) {
+            (Ok(group_id), Ok(profile)) => valid_rows.push(ImportRow {
+                name: name.to_string(),
+                surname: surname.to_string(),
+                group_id,
+                profile,
+            }),
+            (Err(message), _) | (_, Err(message)) => errors.push(RowError { row: row_number, message }),
+        }
+    }
+
+
+    let mut report = ImportReport {
+        dry_run: options.dry_run,
+        total_rows,
+        valid_rows: valid_rows.len(),
+        imported: 0,
+        errors,
+    };
+
+    if options.dry_run {
+        log::info!("Import dry run: {} of {} rows valid", report.valid_rows, report.total_rows);
+        return HttpResponse::Ok().json(report);
+    }
+    if !report.errors.is_empty() {
+        log::warn!("Rejecting import with {} invalid rows", report.errors.len());
+        return HttpResponse::BadRequest().json(report);
+    }
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            log::error!("Failed to start transaction: {}", e);
+            return HttpResponse::InternalServerError()
+                .body(format!("Failed to start transaction: {}", e));
+        }
+    };
+
+    let names: Vec<&str> = valid_rows.iter().map(|row| row.name.as_str()).collect();
+    let surnames: Vec<&str> = valid_rows.iter().map(|row| row.surname.as_str()).collect();
+    let names_latin: Vec<String> = names.iter().map(|name| transliterate(name)).collect();
+    let surnames_latin: Vec<String> = surnames.iter().map(|surname| transliterate(surname)).collect();
+    let group_ids: Vec<i32> = valid_rows.iter().map(|row| row.group_id).collect();
+    let patronymics: Vec<Option<&str>> = valid_rows.iter().map(|row| row.profile.patronymic.as_deref()).collect();
+    let emails: Vec<Option<&str>> = valid_rows.iter().map(|row| row.profile.email.as_deref()).collect();
+    let phones: Vec<Option<&str>> = valid_rows.iter().map(|row| row.profile.phone.as_deref()).collect();
+    let birth_dates: Vec<Option<NaiveDate>> = valid_rows.iter().map(|row| row.profile.birth_date).collect();
+    let record_book_numbers: Vec<Option<&str>> = valid_rows
+        .iter()
+        .map(|row| row.profile.record_book_number.as_deref())
+        .collect();
+    let insert_result = sqlx::query_as::<_, (i32, i32)>(&format!(
+        "INSERT INTO {} (name, surname, group_id, patronymic, email, phone, birth_date, record_book_number,
+                         name_latin, surname_latin)
+         SELECT * FROM UNNEST($1::VARCHAR[], $2::VARCHAR[], $3::INT[], $4::VARCHAR[], $5::VARCHAR[],
+                              $6::VARCHAR[], $7::DATE[], $8::VARCHAR[], $9::VARCHAR[], $10::VARCHAR[])
+         RETURNING id, group_id",
+        *STUDENT_TABLE_NAME
+    ))
+        .bind(&names)
+        .bind(&surnames)
+        .bind(&group_ids)
+        .bind(&patronymics)
+        .bind(&emails)
+        .bind(&phones)
+        .bind(&birth_dates)
+        .bind(&record_book_numbers)
+        .bind(&names_latin)
+        .bind(&surnames_latin)
+        .fetch_all(&mut *tx)
+        .await;
+
+    match insert_result {
+        Ok(created) => {
+            let created_events: Vec<Event> = created
+                .iter()
+                .map(|&(id, group_id)| Event::student(EventKind::StudentCreated, id, [Some(group_id)]))
+                .collect();
+            if let Err(e) = webhooks::enqueue(&mut *tx, &created_events).await {
+                if let Err(rollback_err) = tx.rollback().await {
+                    log::error!("Failed to rollback transaction: {}", rollback_err);
+                }
+                return database_error("Failed to queue webhooks", e);
+            }
+            if let Err(e) = tx.commit().await {
+                log::error!("Failed to commit transaction: {}", e);
+                return HttpResponse::InternalServerError()
+                    .body(format!("Failed to commit transaction: {}", e));
+            }
+            report.imported = created.len();
+            log::info!("Successfully imported {} students", report.imported);
+            created_events.into_iter().for_each(events::publish);
+            HttpResponse::Ok().json(report)
+        },
+        Err(e) => {
+            if let Err(rollback_err) = tx.rollback().await {
+                log::error!("Failed to rollback transaction: {}", rollback_err);
+            }
+            database_error("Failed to import students", e)
+        }
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(import_students))]
+pub(crate) struct ApiDoc;
@@ -0,0 +1,278 @@
+use actix_web::{web, HttpResponse, Responder};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use utoipa::{OpenApi, ToSchema};
+
+use crate::events::{self, Event, EventKind};
+use crate::groups::GroupStore;
+use crate::openapi::{BadRequest, NotFound, ServerError};
+use crate::profile::StudentStatus;
+use crate::routes::database_error;
+use crate::saga::{self, Saga};
+use crate::storage::{group_occupancy, STUDENT_TABLE_NAME};
+use crate::webhooks;
+
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct StatusChange {
+    status: StudentStatus,
+    reason: String,
+    effective_date: NaiveDate,
+    /// Group to return to on reinstatement; by default the student goes back to their old group.
+    #[serde(default)]
+    group_id: Option<i32>,
+}
+
+#[derive(Serialize, sqlx::FromRow, ToSchema)]
+struct StatusHistoryEntry {
+    id: i32,
+    student_id: i32,
+    from_status: String,
+    to_status: String,
+    reason: String,
+    effective_date: NaiveDate,
+    /// Group the student belonged to before the transition.
+    group_id: Option<i32>,
+    recorded_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct StudentStatusView {
+    student_id: i32,
+    status: String,
+    group_id: Option<i32>,
+    history: Vec<StatusHistoryEntry>,
+}
+
+async fn fetch_history(pool: &PgPool, student_id: i32) -> Result<Vec<StatusHistoryEntry>, sqlx::Error> {
+    sqlx::query_as::<_, StatusHistoryEntry>(
+        "SELECT id, student_id, from_status, to_status, reason, effective_date, group_id, recorded_at
+         FROM student_status_history WHERE student_id = $1 ORDER BY effective_date, id"
+    )
+        .bind(student_id)
+        .fetch_all(pool)
+        .await
+}
+
+#[utoipa::path(
+    get,
+    path = "/students/{id}/status",
+    tag = "students",
+    params(("id" = i32, Path, description = "Student id")),
+    responses(
+        (status = 200, description = "Current status of the student and every past change", body = StudentStatusView),
+        (status = 404, response = NotFound),
+        (status = 500, response = ServerError)
+    )
+)]
+pub(crate) async fn get_student_status(id: web::Path<i32>, pool: web::Data<PgPool>) -> impl Responder {
+    log::debug!("Fetching status history of student id: {}", id);
+    let query = format!("SELECT status, group_id FROM {} WHERE id = $1", *STUDENT_TABLE_NAME);
+    let (status, group_id) = match sqlx::query_as::<_, (String, Option<i32>)>(&query)
+        .bind(id.as_ref())
+        .fetch_optional(pool.get_ref())
+        .await
+    {
+        Ok(Some(student)) => student,
+        Ok(None) => {
+            log::debug!("Student not found with id: {}", id);
+            return HttpResponse::NotFound().finish();
+        },
+        Err(e) => return database_error("Failed to fetch student", e),
+    };
+
+    match fetch_history(pool.get_ref(), *id).await {
+        Ok(history) => HttpResponse::Ok().json(StudentStatusView { student_id: *id, status, group_id, history }),
+        Err(e) => database_error("Failed to fetch status history", e),
+    }
+}
+
+/// Moves a student along the enrollment lifecycle. Graduated and expelled
+/// students leave their group; reinstated students may join a different one.
+#[utoipa::path(
+    post,
+    path = "/students/{id}/status",
+    tag = "students",
+    params(("id" = i32, Path, description = "Student id")),
+    request_body = StatusChange,
+    responses(
+        (status = 200, description = "The recorded status change", body = StatusHistoryEntry),
+        (status = 400, response = BadRequest),
+        (status = 404, response = NotFound),
+        (status = 409, description = "The student cannot move from the current status to the requested one", body = String),
+        (status = 500, response = ServerError)
+    )
+)]
+pub(crate) async fn change_student_status(
+    id: web::Path<i32>,
+    change: web::Json<StatusChange>,
+    pool: web::Data<PgPool>,
+    group_store: web::Data<GroupStore>
+) -> impl Responder {
+    log::debug!("Changing status of student id: {} to {}", id, change.status.as_str());
+    let reason = change.reason.trim();
+    if reason.is_empty() {
+        return HttpResponse::BadRequest().body("A reason for the status change is required");
+    }
+    if change.group_id.is_some() && change.status != StudentStatus::Active {
+        return HttpResponse::BadRequest().body("A group can only be chosen when reinstating a student");
+    }
+
+    let target_group = match change.group_id {
+        Some(group_id) => match group_store.find(group_id).await {
+            Ok(Some(group)) => Some(group),
+            Ok(None) => return HttpResponse::BadRequest().body(format!("Group {group_id} does not exist")),
+            Err(e) => {
+                log::error!("Failed to fetch group: {}", e);
+                return HttpResponse::InternalServerError().body(e.to_string());
+            }
+        },
+        None => None,
+    };
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            log::error!("Failed to start transaction: {}", e);
+            return HttpResponse::InternalServerError()
+                .body(format!("Failed to start transaction: {}", e));
+        }
+    };
+
+    let query = format!("SELECT status, group_id FROM {} WHERE id = $1 FOR UPDATE", *STUDENT_TABLE_NAME);
+    let (current, previous_group) = match sqlx::query_as::<_, (String, Option<i32>)>(&query)
+        .bind(id.as_ref())
+        .fetch_optional(&mut *tx)
+        .await
+    {
+        Ok(Some(student)) => student,
+        Ok(None) => {
+            log::debug!("Student not found with id: {}", id);
+            return HttpResponse::NotFound().finish();
+        },
+        Err(e) => return database_error("Failed to fetch student", e),
+    };
+    let current: StudentStatus = match current.parse() {
+        Ok(status) => status,
+        Err(e) => {
+            log::error!("Student {} has an invalid status: {}", id, e);
+            return HttpResponse::InternalServerError().body(e);
+        }
+    };
+
+    if !current.can_become(change.status) {
+        log::warn!("Rejected status change of student {} from {} to {}", id, current.as_str(), change.status.as_str());
+        return HttpResponse::Conflict().body(format!(
+            "Cannot change status from {} to {}", current.as_str(), change.status.as_str()
+        ));
+    }
+
+    match sqlx::query_scalar::<_, Option<NaiveDate>>(
+        "SELECT MAX(effective_date) FROM student_status_history WHERE student_id = $1"
+    )
+        .bind(id.as_ref())
+        .fetch_one(&mut *tx)
+        .await
+    {
+        Ok(Some(last)) if change.effective_date < last => {
+            return HttpResponse::BadRequest()
+                .body(format!("Effective date must not be earlier than the previous status change on {last}"));
+        },
+        Ok(_) => {},
+        Err(e) => return database_error("Failed to fetch status history", e),
+    }
+
+    let new_group = match (&target_group, change.status.keeps_group()) {
+        (Some(group), _) => Some(group.id),
+        (None, true) => previous_group,
+        (None, false) => None,
+    };
+    if let Some(group) = target_group.as_ref().filter(|group| Some(group.id) != previous_group) {
+        if let Some(max_capacity) = group.max_capacity {
+            match group_occupancy(&mut *tx, group.id, Some(*id)).await {
+                Ok((count, _)) if count >= i64::from(max_capacity) => {
+                    return HttpResponse::BadRequest()
+                        .body(format!("Group {} is at full capacity ({} students)", group.name, max_capacity));
+                },
+                Ok(_) => {},
+                Err(e) => return database_error("Failed to count students in group", e),
+            }
+        }
+    }
+
+    let query = format!("UPDATE {} SET status = $1, group_id = $2 WHERE id = $3", *STUDENT_TABLE_NAME);
+    if let Err(e) = sqlx::query(&query)
+        .bind(change.status.as_str())
+        .bind(new_group)
+        .bind(id.as_ref())
+        .execute(&mut *tx)
+        .await
+    {
+        if let Err(rollback_err) = tx.rollback().await {
+            log::error!("Failed to rollback transaction: {}", rollback_err);
+        }
+        return database_error("Failed to update student status", e);
+    }
+
+    let entry = match sqlx::query_as::<_, StatusHistoryEntry>(
+        "INSERT INTO student_status_history (student_id, from_status, to_status, reason, effective_date, group_id)
+         VALUES ($1, $2, $3, $4, $5, $6)
+         RETURNING id, student_id, from_status, to_status, reason, effective_date, group_id, recorded_at"
+    )
+        .bind(id.as_ref())
+        .bind(current.as_str())
+        .bind(change.status.as_str())
+        .bind(reason)
+        .bind(change.effective_date)
+        .bind(previous_group)
+        .fetch_one(&mut *tx)
+        .await
+    {
+        Ok(entry) => entry,
+        Err(e) => {
+            if let Err(rollback_err) = tx.rollback().await {
+                log::error!("Failed to rollback transaction: {}", rollback_err);
+            }
+            return database_error("Failed to record status change", e);
+        }
+    };
+
+    let event = Event::student(EventKind::StudentUpdated, *id, [previous_group, new_group]);
+    if let Err(e) = webhooks::enqueue(&mut *tx, std::slice::from_ref(&event)).await {
+        if let Err(rollback_err) = tx.rollback().await {
+            log::error!("Failed to rollback transaction: {}", rollback_err);
+        }
+        return database_error("Failed to queue webhooks", e);
+    }
+
+    let mut sagas = Vec::new();
+    if previous_group.is_some() && previous_group != new_group {
+        sagas.push(Saga::ReleaseLeader { student_id: *id });
+    }
+    let sagas = match saga::begin_all(&mut tx, sagas).await {
+        Ok(sagas) => sagas,
+        Err(e) => {
+            if let Err(rollback_err) = tx.rollback().await {
+                log::error!("Failed to rollback transaction: {}", rollback_err);
+            }
+            return database_error("Failed to record status change", e);
+        }
+    };
+
+    if let Err(e) = tx.commit().await {
+        log::error!("Failed to commit transaction: {}", e);
+        return HttpResponse::InternalServerError()
+            .body(format!("Failed to commit transaction: {}", e));
+    }
+    saga::finish_all(sagas, pool.get_ref(), group_store.get_ref()).await;
+
+    log::info!("Changed status of student id: {} from {} to {}", id, entry.from_status, entry.to_status);
+    events::publish(event);
+    HttpResponse::Ok().json(entry)
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    get_student_status, change_student_status,
+))]
+pub(crate) struct ApiDoc;
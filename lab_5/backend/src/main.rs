@@ -1,34 +1,53 @@
 use actix_web::{web, App, HttpServer};
 use actix_cors::Cors;
-use mongodb::Client as MongoClient;
+use mongodb::{Client as MongoClient, Collection};
 use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
 use actix_web::middleware::Logger;
 use env_logger::Env;
+use sqlx::PgPool;
 use sqlx::postgres::PgPoolOptions;
-use crate::routes::Group;
+use crate::groups::{GroupStore, MongoGroups, PostgresGroups};
+use crate::storage::Group;
+use crate::students::{PostgresStudents, StudentStore};
 
+mod attendance;
+mod audit;
+mod backup;
+mod batch;
+mod calendar;
+mod duplicates;
+mod events;
+mod export;
+mod grades;
+mod groups;
+mod graphql;
+mod grpc;
+mod import;
+mod lifecycle;
+#[cfg(test)]
+mod memory;
+mod openapi;
+mod profile;
 mod routes;
+mod saga;
+mod schedule;
+mod storage;
+mod students;
+mod translit;
+mod webhooks;
 
-#[actix_web::main]
-async fn main() -> std::io::Result<()> {
-    env_logger::init_from_env(Env::default().default_filter_or("info"));
+const USAGE: &str =
+    "Usage: server [backup <archive.zip> | restore <archive.zip> | migrate-groups <mongo|postgres>]";
 
+async fn connect_postgres() -> PgPool {
     let pg_host = env::var("POSTGRES_HOST").expect("POSTGRES_HOST must be set");
     let pg_port = env::var("POSTGRES_PORT").expect("POSTGRES_PORT must be set");
     let pg_user = env::var("POSTGRES_USER").expect("POSTGRES_USER must be set");
     let pg_password = env::var("POSTGRES_PASSWORD").expect("POSTGRES_PASSWORD must be set");
     let pg_db = env::var("POSTGRES_DB").expect("POSTGRES_DB must be set");
 
-    let mongo_host = env::var("MONGO_HOST").expect("MONGO_HOST must be set");
-    let mongo_port = env::var("MONGO_PORT").expect("MONGO_PORT must be set");
-    let mongo_db = env::var("MONGO_INITDB_DATABASE").expect("MONGO_INITDB_DATABASE must be set");
-    let mongo_collection = env::var("MONGO_COLLECTION").expect("MONGO_COLLECTION must be set");
-
-    let backend_port = env::var("BACKEND_PORT")
-        .expect("BACKEND_PORT must be set")
-        .parse::<u16>()
-        .expect("BACKEND_PORT must be a valid port number");
-
     log::info!("Connecting to PostgreSQL database...");
     let database_url = format!("postgresql://{pg_user}:{pg_password}@{pg_host}:{pg_port}/{pg_db}");
 
@@ -39,6 +58,14 @@ async fn main() -> std::io::Result<()> {
         .await
         .expect("Failed to create pool");
     log::info!("Successfully connected to PostgreSQL");
+    pool
+}
+
+async fn connect_mongo() -> Collection<Group> {
+    let mongo_host = env::var("MONGO_HOST").expect("MONGO_HOST must be set");
+    let mongo_port = env::var("MONGO_PORT").expect("MONGO_PORT must be set");
+    let mongo_db = env::var("MONGO_INITDB_DATABASE").expect("MONGO_INITDB_DATABASE must be set");
+    let mongo_collection = env::var("MONGO_COLLECTION").expect("MONGO_COLLECTION must be set");
 
     log::info!("Connecting to MongoDB...");
     let mongo_uri = format!("mongodb://{}:{}", mongo_host, mongo_port);
@@ -48,9 +75,52 @@ async fn main() -> std::io::Result<()> {
     let mongo_db = mongo_client.database(&mongo_db);
     let mongo_collection = mongo_db.collection::<Group>(&mongo_collection);
     log::info!("MongoDB collection configured: {}", mongo_collection.name());
+    mongo_collection
+}
+
+/// Opens the group store named by `GROUP_STORE`, Mongo by default.
+async fn connect_group_store(pool: &PgPool) -> Arc<GroupStore> {
+    match env::var("GROUP_STORE").as_deref().unwrap_or("mongo") {
+        "mongo" => Arc::new(MongoGroups(connect_mongo().await)),
+        "postgres" => {
+            groups::add_foreign_keys(pool)
+                .await
+                .expect("Failed to reference groups in Postgres; run `server migrate-groups postgres` first");
+            log::info!("Keeping groups in PostgreSQL");
+            Arc::new(PostgresGroups(pool.clone()))
+        },
+        _ => panic!("GROUP_STORE must be mongo or postgres"),
+    }
+}
+
+async fn run_server() -> std::io::Result<()> {
+    let backend_port = env::var("BACKEND_PORT")
+        .expect("BACKEND_PORT must be set")
+        .parse::<u16>()
+        .expect("BACKEND_PORT must be a valid port number");
+    let grpc_port = env::var("GRPC_PORT")
+        .expect("GRPC_PORT must be set")
+        .parse::<u16>()
+        .expect("GRPC_PORT must be a valid port number");
+
+    let pool = connect_postgres().await;
+    match translit::backfill_latin_names(&pool).await {
+        Ok(0) => {},
+        Ok(count) => log::info!("Transliterated the names of {} students", count),
+        Err(e) => log::error!("Failed to transliterate student names: {}", e),
+    }
+    let group_store = connect_group_store(&pool).await;
+    let student_store: Arc<StudentStore> = Arc::new(PostgresStudents(pool.clone()));
+    actix_web::rt::spawn(webhooks::dispatch_deliveries(pool.clone()));
+    actix_web::rt::spawn(saga::reconcile(pool.clone(), group_store.clone()));
     log::info!("Starting server on port {}", backend_port);
 
-    HttpServer::new(move || {
+    // The HTTP server stops on SIGINT and SIGTERM; the gRPC server follows it.
+    let (stop_grpc, grpc_stopped) = futures::channel::oneshot::channel::<()>();
+    let grpc_server = grpc::serve(grpc_port, student_store.clone(), group_store.clone(), async {
+        grpc_stopped.await.ok();
+    });
+    let http_server = HttpServer::new(move || {
         let cors = Cors::default()
             .allow_any_origin()
             .allowed_methods(vec!["GET", "POST", "PUT", "DELETE"])
@@ -59,6 +129,7 @@ async fn main() -> std::io::Result<()> {
                 "Accept",
                 "Content-Type",
             ])
+            .expose_headers(vec!["Deprecation", "Link"])
             .supports_credentials()
             .max_age(3600);
 
@@ -67,10 +138,53 @@ async fn main() -> std::io::Result<()> {
             .wrap(Logger::new("%a %r %s %b %{Referer}i %{User-Agent}i %T")) // Or custom format
             .wrap(cors)
             .app_data(web::Data::new(pool.clone()))
-            .app_data(web::Data::new(mongo_collection.clone()))
+            .app_data(web::Data::from(student_store.clone()))
+            .app_data(web::Data::from(group_store.clone()))
             .configure(routes::configure_routes)
     })
         .bind(("0.0.0.0", backend_port))?
-        .run()
-        .await
-}
\ No newline at end of file
+        .run();
+
+    let http_server = async {
+        let result = http_server.await;
+        stop_grpc.send(()).ok();
+        result
+    };
+    futures::try_join!(http_server, async { grpc_server.await.map_err(std::io::Error::other) })?;
+    Ok(())
+}
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    env_logger::init_from_env(Env::default().default_filter_or("info"));
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        [] => run_server().await,
+        ["backup", archive_path] => {
+            let pool = connect_postgres().await;
+            let group_store = connect_group_store(&pool).await;
+            backup::create_backup(&pool, group_store.as_ref(), &PathBuf::from(archive_path))
+                .await
+                .map_err(std::io::Error::other)
+        },
+        ["restore", archive_path] => {
+            let pool = connect_postgres().await;
+            let group_store = connect_group_store(&pool).await;
+            backup::restore_backup(&pool, group_store.as_ref(), &PathBuf::from(archive_path))
+                .await
+                .map_err(std::io::Error::other)
+        },
+        ["migrate-groups", target @ ("mongo" | "postgres")] => {
+            let pool = connect_postgres().await;
+            let mongo = MongoGroups(connect_mongo().await);
+            groups::migrate(&pool, &mongo, target)
+                .await
+                .map_err(std::io::Error::other)
+        },
+        _ => {
+            eprintln!("{USAGE}");
+            std::process::exit(2);
+        }
+    }
+}
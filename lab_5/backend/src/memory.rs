@@ -0,0 +1,348 @@
+//! Student and group stores kept in memory, so that handlers can be exercised
+//! without Postgres or Mongo. They keep the rules the real stores enforce on
+//! their own: leaders are released when they leave a group, and groups with
+//! students cannot be deleted. Classes and assessments are not modelled.
+
+use async_trait::async_trait;
+
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use crate::events::{Event, EventKind};
+use crate::groups::{GroupRepository, GroupStore};
+use crate::profile::{StudentProfile, StudentStatus};
+use crate::routes::{GroupFilter, GroupInput, StudentForm};
+use crate::storage::{Group, StoreError, Student};
+use crate::students::StudentRepository;
+use crate::translit::transliterate;
+
+#[derive(Default)]
+pub(crate) struct InMemoryGroups(Mutex<BTreeMap<i32, Group>>);
+
+impl InMemoryGroups {
+    pub(crate) fn new(groups: impl IntoIterator<Item = Group>) -> Self {
+        InMemoryGroups(Mutex::new(groups.into_iter().map(|group| (group.id, group)).collect()))
+    }
+
+    pub(crate) fn get(&self, id: i32) -> Option<Group> {
+        self.0.lock().unwrap().get(&id).cloned()
+    }
+}
+
+fn apply_input(group: &mut Group, input: &GroupInput) {
+    group.name = input.name.clone();
+    if let Some(faculty) = &input.faculty {
+        group.faculty = faculty.clone();
+    }
+    if let Some(specialty_code) = &input.specialty_code {
+        group.specialty_code = specialty_code.clone();
+    }
+    if let Some(admission_year) = input.admission_year {
+        group.admission_year = admission_year;
+    }
+    if let Some(study_form) = input.study_form {
+        group.study_form = study_form;
+    }
+    if let Some(max_capacity) = input.max_capacity {
+        group.max_capacity = max_capacity;
+    }
+}
+
+fn matches_filter(group: &Group, filter: &GroupFilter) -> bool {
+    (filter.faculty.is_none() || group.faculty == filter.faculty)
+        && (filter.specialty_code.is_none() || group.specialty_code == filter.specialty_code)
+        && (filter.admission_year.is_none() || group.admission_year == filter.admission_year)
+        && (filter.study_form.is_none() || group.study_form == filter.study_form)
+}
+
+#[async_trait]
+impl GroupRepository for InMemoryGroups {
+    async fn find_all(&self, filter: &GroupFilter, page: Option<(u64, i64)>) -> Result<Vec<Group>, StoreError> {
+        let (offset, limit) = page.map_or((0, usize::MAX), |(offset, limit)| (offset as usize, limit as usize));
+        let groups = self.0.lock().unwrap();
+        Ok(groups.values().filter(|group| matches_filter(group, filter)).skip(offset).take(limit).cloned().collect())
+    }
+
+    async fn find_many(&self, ids: &[i32]) -> Result<Vec<Group>, StoreError> {
+        let groups = self.0.lock().unwrap();
+        Ok(ids.iter().filter_map(|id| groups.get(id)).cloned().collect())
+    }
+
+    async fn find(&self, id: i32) -> Result<Option<Group>, StoreError> {
+        Ok(self.get(id))
+    }
+
+    async fn insert(&self, input: &GroupInput) -> Result<Group, StoreError> {
+        let mut groups = self.0.lock().unwrap();
+        let id = groups.keys().next_back().map_or(0, |id| id + 1);
+        let mut group = Group {
+            id,
+            name: String::new(),
+            leader_id: None,
+            faculty: None,
+            specialty_code: None,
+            admission_year: None,
+            study_form: None,
+            max_capacity: None,
+        };
+        apply_input(&mut group, input);
+        groups.insert(id, group.clone());
+        Ok(group)
+    }
+
+    async fn update(&self, id: i32, input: &GroupInput) -> Result<bool, StoreError> {
+        let mut groups = self.0.lock().unwrap();
+        Ok(groups.get_mut(&id).map(|group| apply_input(group, input)).is_some())
+    }
+
+    async fn delete(&self, id: i32) -> Result<(), StoreError> {
+        self.0.lock().unwrap().remove(&id);
+        Ok(())
+    }
+
+    async fn set_leader(&self, id: i32, leader_id: Option<i32>) -> Result<bool, StoreError> {
+        let mut groups = self.0.lock().unwrap();
+        Ok(groups.get_mut(&id).map(|group| group.leader_id = leader_id).is_some())
+    }
+
+    async fn clear_leader_for_student(&self, student_id: i32, keep_group: Option<i32>) -> Result<u64, StoreError> {
+        let mut cleared = 0;
+        for group in self.0.lock().unwrap().values_mut() {
+            if group.leader_id == Some(student_id) && Some(group.id) != keep_group {
+                group.leader_id = None;
+                cleared += 1;
+            }
+        }
+        Ok(cleared)
+    }
+
+    async fn hand_over_leadership(&self, from: i32, to: i32) -> Result<(), StoreError> {
+        for group in self.0.lock().unwrap().values_mut() {
+            if group.leader_id == Some(from) {
+                group.leader_id = Some(to);
+            }
+        }
+        Ok(())
+    }
+
+    async fn insert_missing(&self, new_groups: &[Group]) -> Result<u64, StoreError> {
+        let mut groups = self.0.lock().unwrap();
+        let mut added = 0;
+        for group in new_groups {
+            if let Entry::Vacant(entry) = groups.entry(group.id) {
+                entry.insert(group.clone());
+                added += 1;
+            }
+        }
+        Ok(added)
+    }
+
+    async fn replace_all(&self, new_groups: &[Group]) -> Result<(), StoreError> {
+        *self.0.lock().unwrap() = new_groups.iter().map(|group| (group.id, group.clone())).collect();
+        Ok(())
+    }
+}
+
+/// Photo bytes and their MIME type.
+type Photo = (Vec<u8>, String);
+
+#[derive(Default)]
+struct StudentState {
+    students: BTreeMap<i32, (Student, Option<Photo>)>,
+    next_id: i32,
+    events: Vec<Event>,
+}
+
+impl StudentState {
+    /// Queues the webhooks of an event, by keeping it.
+    fn record(&mut self, event: Event) -> Event {
+        self.events.push(event.clone());
+        event
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct InMemoryStudents(Mutex<StudentState>);
+
+impl InMemoryStudents {
+    pub(crate) fn get(&self, id: i32) -> Option<Student> {
+        self.0.lock().unwrap().students.get(&id).map(|(student, _)| student.clone())
+    }
+
+    /// Stores a student the way [`StudentRepository::insert`] does, but without an
+    /// event, so that tests can start from existing students.
+    pub(crate) fn seed(&self, form: &StudentForm) -> i32 {
+        let mut state = self.0.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        let mut student = Student {
+            id,
+            name: String::new(),
+            surname: String::new(),
+            name_latin: None,
+            surname_latin: None,
+            group_id: None,
+            profile: Default::default(),
+            status: StudentStatus::Active.as_str().to_string(),
+        };
+        set_form(&mut student, form);
+        state.students.insert(id, (student, form.image_data.clone()));
+        id
+    }
+
+    /// Events whose webhooks were queued, oldest first.
+    pub(crate) fn events(&self) -> Vec<Event> {
+        self.0.lock().unwrap().events.clone()
+    }
+
+    fn members(&self, group_id: i32) -> Vec<Student> {
+        let state = self.0.lock().unwrap();
+        state.students.values()
+            .map(|(student, _)| student)
+            .filter(|student| student.group_id == Some(group_id))
+            .cloned()
+            .collect()
+    }
+}
+
+fn set_form(student: &mut Student, form: &StudentForm) {
+    student.name = form.name.clone();
+    student.surname = form.surname.clone();
+    student.name_latin = Some(transliterate(&form.name));
+    student.surname_latin = Some(transliterate(&form.surname));
+    let keep = |column| form.kept_fields.contains(&column);
+    if !keep("group_id") {
+        student.group_id = form.group_id;
+    }
+    let (old, new) = (std::mem::take(&mut student.profile), form.profile.clone());
+    student.profile = StudentProfile {
+        patronymic: if keep("patronymic") { old.patronymic } else { new.patronymic },
+        email: if keep("email") { old.email } else { new.email },
+        phone: if keep("phone") { old.phone } else { new.phone },
+        birth_date: if keep("birth_date") { old.birth_date } else { new.birth_date },
+        record_book_number: if keep("record_book_number") { old.record_book_number } else { new.record_book_number },
+    };
+}
+
+fn matches_query(student: &Student, query: Option<&str>) -> bool {
+    let fields = [Some(&student.name), Some(&student.surname), student.name_latin.as_ref(), student.surname_latin.as_ref()];
+    query.unwrap_or_default().split_whitespace().all(|term| {
+        let (original, latin) = (term.to_lowercase(), transliterate(term).to_lowercase());
+        fields.iter().flatten().any(|field| {
+            let field = field.to_lowercase();
+            field.contains(&original) || field.contains(&latin)
+        })
+    })
+}
+
+#[async_trait]
+impl StudentRepository for InMemoryStudents {
+    async fn find_all(&self, status: Option<StudentStatus>, query: Option<&str>) -> Result<Vec<Student>, StoreError> {
+        let state = self.0.lock().unwrap();
+        Ok(state.students.values()
+            .map(|(student, _)| student)
+            .filter(|student| status.is_none_or(|status| student.status == status.as_str()))
+            .filter(|student| matches_query(student, query))
+            .cloned()
+            .collect())
+    }
+
+    async fn find(&self, id: i32) -> Result<Option<Student>, StoreError> {
+        Ok(self.get(id))
+    }
+
+    async fn find_many(&self, ids: &[i32]) -> Result<Vec<Student>, StoreError> {
+        Ok(ids.iter().filter_map(|&id| self.get(id)).collect())
+    }
+
+    async fn find_group_members(&self, group_ids: &[i32]) -> Result<Vec<Student>, StoreError> {
+        let state = self.0.lock().unwrap();
+        Ok(state.students.values()
+            .map(|(student, _)| student)
+            .filter(|student| student.group_id.is_some_and(|group_id| group_ids.contains(&group_id)))
+            .cloned()
+            .collect())
+    }
+
+    async fn find_group_members_page(
+        &self,
+        group_id: i32,
+        limit: Option<i64>,
+        offset: i64
+    ) -> Result<Vec<Student>, StoreError> {
+        let limit = limit.map_or(usize::MAX, |limit| limit as usize);
+        Ok(self.members(group_id).into_iter().skip(offset as usize).take(limit).collect())
+    }
+
+    async fn count_group_members(&self, group_id: i32) -> Result<i64, StoreError> {
+        Ok(self.members(group_id).len() as i64)
+    }
+
+    async fn group_occupancy(&self, group_id: i32, student_id: Option<i32>) -> Result<(i64, bool), StoreError> {
+        let members = self.members(group_id);
+        let already_member = members.iter().any(|student| Some(student.id) == student_id);
+        Ok((members.len() as i64 - i64::from(already_member), already_member))
+    }
+
+    async fn student_group(&self, id: i32) -> Result<Option<Option<i32>>, StoreError> {
+        Ok(self.get(id).map(|student| student.group_id))
+    }
+
+    async fn load_photo(&self, id: i32) -> Result<Option<(Vec<u8>, String)>, StoreError> {
+        Ok(self.0.lock().unwrap().students.get(&id).and_then(|(_, photo)| photo.clone()))
+    }
+
+    async fn find_photo_types(&self, ids: &[i32]) -> Result<Vec<(i32, String)>, StoreError> {
+        let state = self.0.lock().unwrap();
+        Ok(ids.iter()
+            .filter_map(|id| state.students.get(id))
+            .filter_map(|(student, photo)| Some((student.id, photo.as_ref()?.1.clone())))
+            .collect())
+    }
+
+    async fn insert(&self, form: &StudentForm, _groups: &GroupStore) -> Result<Event, StoreError> {
+        let id = self.seed(form);
+        Ok(self.0.lock().unwrap().record(Event::student(EventKind::StudentCreated, id, [form.group_id])))
+    }
+
+    async fn update(&self, id: i32, form: &StudentForm, groups: &GroupStore) -> Result<Event, StoreError> {
+        let (previous_group, group_id) = {
+            let mut state = self.0.lock().unwrap();
+            match state.students.get_mut(&id) {
+                Some((student, photo)) => {
+                    let previous_group = student.group_id;
+                    set_form(student, form);
+                    if form.image_data.is_some() {
+                        *photo = form.image_data.clone();
+                    }
+                    (previous_group, student.group_id)
+                },
+                None => return Err(StoreError::NotFound),
+            }
+        };
+        groups.clear_leader_for_student(id, group_id).await?;
+        let event = Event::student(EventKind::StudentUpdated, id, [previous_group, form.group_id]);
+        Ok(self.0.lock().unwrap().record(event))
+    }
+
+    async fn delete(&self, id: i32, groups: &GroupStore) -> Result<Event, StoreError> {
+        let (student, _) = self.0.lock().unwrap().students.remove(&id).ok_or(StoreError::NotFound)?;
+        let previous_group = student.group_id;
+        groups.clear_leader_for_student(id, None).await?;
+        Ok(self.0.lock().unwrap().record(Event::student(EventKind::StudentDeleted, id, [previous_group])))
+    }
+
+    async fn delete_group(&self, group_id: i32, groups: &GroupStore) -> Result<Event, StoreError> {
+        if !self.members(group_id).is_empty() {
+            return Err(StoreError::Rejected("Cannot delete group with existing students".to_string()));
+        }
+        groups.delete(group_id).await?;
+        Ok(self.0.lock().unwrap().record(Event::group(EventKind::GroupDeleted, group_id)))
+    }
+
+    async fn queue_webhooks(&self, event: &Event) -> Result<(), StoreError> {
+        self.0.lock().unwrap().record(event.clone());
+        Ok(())
+    }
+}
@@ -0,0 +1,218 @@
+use utoipa::openapi::schema::{KnownFormat, ObjectBuilder, Schema, SchemaFormat, Type};
+use utoipa::openapi::{OpenApi as OpenApiDocument, RefOr};
+use utoipa::{OpenApi, PartialSchema, ToResponse, ToSchema};
+
+use crate::{attendance, audit, batch, calendar, duplicates, events, export, grades, import, lifecycle, routes, schedule, webhooks};
+
+/// The request was rejected; the body says why.
+#[derive(ToResponse)]
+#[response(content_type = "text/plain")]
+#[allow(dead_code)]
+pub(crate) struct BadRequest(String);
+
+/// The addressed resource does not exist.
+#[derive(ToResponse)]
+#[allow(dead_code)]
+pub(crate) struct NotFound;
+
+/// The request contradicts the current state of the resource; the body says why.
+#[derive(ToResponse)]
+#[response(content_type = "text/plain")]
+#[allow(dead_code)]
+pub(crate) struct Conflict(String);
+
+/// A database or another unexpected failure; the body carries the error message.
+#[derive(ToResponse)]
+#[response(content_type = "text/plain")]
+#[allow(dead_code)]
+pub(crate) struct ServerError(String);
+
+/// Raw file contents such as a photo, a workbook or an archive.
+pub(crate) struct Binary;
+
+impl PartialSchema for Binary {
+    fn schema() -> RefOr<Schema> {
+        ObjectBuilder::new()
+            .schema_type(Type::String)
+            .format(Some(SchemaFormat::KnownFormat(KnownFormat::Binary)))
+            .into()
+    }
+}
+
+impl ToSchema for Binary {}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Student Management API", description = "REST API of the student management system.", version = "1"),
+    servers((url = "/api/v1")),
+    components(responses(BadRequest, NotFound, Conflict, ServerError)),
+    tags(
+        (name = "students", description = "Students, their photos, lifecycle and bulk operations"),
+        (name = "groups", description = "Study groups and their leaders"),
+        (name = "schedule", description = "Subjects, teachers, rooms and the weekly timetable"),
+        (name = "attendance", description = "Attendance of class sessions"),
+        (name = "grades", description = "Assessments, scores, gradebooks and transcripts"),
+        (name = "export", description = "CSV, JSON and XLSX exports"),
+        (name = "audit", description = "Trail of administrative changes"),
+        (name = "events", description = "Live notifications of changes to students and groups"),
+        (name = "webhooks", description = "Signed HTTP callbacks on changes to students and groups"),
+    )
+)]
+struct ApiDoc;
+
+/// The OpenAPI document of every route registered by [`routes::configure_routes`].
+pub(crate) fn openapi() -> OpenApiDocument {
+    let mut document = ApiDoc::openapi();
+    for module in [
+        routes::ApiDoc::openapi(),
+        schedule::ApiDoc::openapi(),
+        calendar::ApiDoc::openapi(),
+        attendance::ApiDoc::openapi(),
+        grades::ApiDoc::openapi(),
+        lifecycle::ApiDoc::openapi(),
+        duplicates::ApiDoc::openapi(),
+        import::ApiDoc::openapi(),
+        batch::ApiDoc::openapi(),
+        export::ApiDoc::openapi(),
+        audit::ApiDoc::openapi(),
+        events::ApiDoc::openapi(),
+        webhooks::ApiDoc::openapi(),
+    ] {
+        document.merge(module);
+    }
+    document
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::{Method, StatusCode};
+    use actix_web::{test, App};
+    use utoipa::openapi::path::HttpMethod;
+
+    use super::*;
+
+    fn method(method: &HttpMethod) -> Method {
+        match method {
+            HttpMethod::Get => Method::GET,
+            HttpMethod::Post => Method::POST,
+            HttpMethod::Put => Method::PUT,
+            HttpMethod::Delete => Method::DELETE,
+            HttpMethod::Options => Method::OPTIONS,
+            HttpMethod::Head => Method::HEAD,
+            HttpMethod::Patch => Method::PATCH,
+            HttpMethod::Trace => Method::TRACE,
+        }
+    }
+
+    /// Every `(method, path)` pair documented in the spec, with path parameters filled in.
+    fn documented_operations() -> Vec<(Method, String)> {
+        let document = openapi();
+        let mut operations = Vec::new();
+        for (path, item) in &document.paths.paths {
+            let concrete: String = path
+                .split('/')
+                .map(|segment| match segment.find('{') {
+                    Some(start) => {
+                        let end = segment.find('}').expect("unterminated path parameter");
+                        format!("{}1{}", &segment[..start], &segment[end + 1..])
+                    },
+                    None => segment.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join("/");
+            let methods = [
+                (HttpMethod::Get, &item.get),
+                (HttpMethod::Post, &item.post),
+                (HttpMethod::Put, &item.put),
+                (HttpMethod::Delete, &item.delete),
+                (HttpMethod::Patch, &item.patch),
+            ];
+            for (http_method, operation) in methods {
+                if operation.is_some() {
+                    operations.push((method(&http_method), concrete.clone()));
+                }
+            }
+        }
+        operations
+    }
+
+    /// Counts `.route(` registrations with a path in the sources, the only way REST
+    /// routes are added. The GraphQL endpoint is a resource and is not counted.
+    fn registered_route_count() -> usize {
+        let source_dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+        std::fs::read_dir(source_dir)
+            .expect("source directory is readable")
+            .map(|entry| entry.expect("source entry is readable").path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "rs"))
+            .map(|path| {
+                let source = std::fs::read_to_string(&path).expect("source file is readable");
+                source.matches(".route(\"").count()
+            })
+            .sum()
+    }
+
+    /// Fails when a documented operation is not routed, or when a route is added
+    /// or removed without updating the spec. Every operation must also be reachable
+    /// through the deprecated unversioned alias, and only there be marked deprecated.
+    #[actix_web::test]
+    async fn spec_matches_routes() {
+        let app = test::init_service(App::new().configure(routes::configure_routes)).await;
+        let operations = documented_operations();
+
+        for (method, path) in &operations {
+            for (prefix, deprecated) in [("/api/v1", false), ("/api", true)] {
+                let uri = format!("{prefix}{path}");
+                let request = test::TestRequest::default().method(method.clone()).uri(&uri).to_request();
+                let response = test::call_service(&app, request).await;
+                assert!(
+                    !matches!(response.status(), StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED),
+                    "{method} {uri} is documented but not routed"
+                );
+                assert_eq!(
+                    response.headers().contains_key("deprecation"),
+                    deprecated,
+                    "{method} {uri} has the wrong deprecation status"
+                );
+            }
+        }
+
+        assert_eq!(
+            operations.len(),
+            registered_route_count(),
+            "the number of documented operations differs from the number of registered routes"
+        );
+    }
+
+    /// Fails when an operation that creates or changes something does not document its body.
+    #[actix_web::test]
+    async fn writes_document_their_request_body() {
+        let document = openapi();
+        let mut undocumented = Vec::new();
+        for (path, item) in &document.paths.paths {
+            for (method, operation) in [("POST", &item.post), ("PUT", &item.put)] {
+                if operation.as_ref().is_some_and(|operation| operation.request_body.is_none()) {
+                    undocumented.push(format!("{method} {path}"));
+                }
+            }
+        }
+        assert!(undocumented.is_empty(), "no request body is documented for {undocumented:?}");
+    }
+
+    #[actix_web::test]
+    async fn spec_is_served() {
+        let app = test::init_service(App::new().configure(routes::configure_routes)).await;
+        let request = test::TestRequest::get().uri("/api/v1/openapi.json").to_request();
+        let document: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(document["openapi"], "3.1.0");
+        assert_eq!(document["servers"][0]["url"], "/api/v1");
+        assert!(document["paths"]["/students/{id}"]["put"]["requestBody"]["content"]
+            .get("multipart/form-data")
+            .is_some());
+
+        let response = test::call_service(&app, test::TestRequest::get().uri("/api/openapi.json").to_request()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().contains_key("deprecation"));
+        let alias: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(alias, document);
+    }
+}
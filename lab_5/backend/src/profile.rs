@@ -0,0 +1,157 @@
+use chrono::{NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use std::str::FromStr;
+
+const MAX_EMAIL_LENGTH: usize = 254;
+const MAX_RECORD_BOOK_LENGTH: usize = 32;
+const MAX_NAME_LENGTH: usize = 255;
+/// E.164 allows at most 15 digits; 10 is the shortest Ukrainian number without the country code.
+const PHONE_DIGITS: std::ops::RangeInclusive<usize> = 10..=15;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum StudentStatus {
+    Active,
+    AcademicLeave,
+    Expelled,
+    Graduated,
+}
+
+impl StudentStatus {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            StudentStatus::Active => "active",
+            StudentStatus::AcademicLeave => "academic_leave",
+            StudentStatus::Expelled => "expelled",
+            StudentStatus::Graduated => "graduated",
+        }
+    }
+
+    /// Enrolled students may go on academic leave, graduate or be expelled; students
+    /// on leave are either reinstated or expelled. Graduation and expulsion are final.
+    pub(crate) fn can_become(self, next: StudentStatus) -> bool {
+        matches!(
+            (self, next),
+            (StudentStatus::Active, StudentStatus::AcademicLeave)
+                | (StudentStatus::Active, StudentStatus::Graduated)
+                | (StudentStatus::Active, StudentStatus::Expelled)
+                | (StudentStatus::AcademicLeave, StudentStatus::Active)
+                | (StudentStatus::AcademicLeave, StudentStatus::Expelled)
+        )
+    }
+
+    /// Whether a student with this status still belongs to a group.
+    pub(crate) fn keeps_group(self) -> bool {
+        matches!(self, StudentStatus::Active | StudentStatus::AcademicLeave)
+    }
+}
+
+impl FromStr for StudentStatus {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim() {
+            "active" => Ok(StudentStatus::Active),
+            "academic_leave" => Ok(StudentStatus::AcademicLeave),
+            "expelled" => Ok(StudentStatus::Expelled),
+            "graduated" => Ok(StudentStatus::Graduated),
+            other => Err(format!("Unknown student status: {other}")),
+        }
+    }
+}
+
+/// Columns of the fields of [`StudentProfile`].
+pub(crate) const PROFILE_COLUMNS: [&str; 5] = ["patronymic", "email", "phone", "birth_date", "record_book_number"];
+
+/// The profile columns whose field was not sent, given whether each of them
+/// was, in the order of [`PROFILE_COLUMNS`].
+pub(crate) fn unsent_columns(sent: [bool; 5]) -> Vec<&'static str> {
+    PROFILE_COLUMNS.into_iter().zip(sent).filter_map(|(column, sent)| (!sent).then_some(column)).collect()
+}
+
+/// Optional personal details of a student, shared by the form, import and batch endpoints.
+#[derive(Serialize, Deserialize, sqlx::FromRow, Default, Clone, Debug, ToSchema)]
+pub(crate) struct StudentProfile {
+    #[serde(default)]
+    pub(crate) patronymic: Option<String>,
+    #[serde(default)]
+    pub(crate) email: Option<String>,
+    #[serde(default)]
+    pub(crate) phone: Option<String>,
+    #[serde(default)]
+    pub(crate) birth_date: Option<NaiveDate>,
+    #[serde(default)]
+    pub(crate) record_book_number: Option<String>,
+}
+
+/// Trims a value and treats blank input as missing.
+pub(crate) fn non_blank(value: Option<String>) -> Option<String> {
+    value
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+fn is_valid_email(email: &str) -> bool {
+    if email.len() > MAX_EMAIL_LENGTH || email.chars().any(char::is_whitespace) {
+        return false;
+    }
+    match email.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && !domain.contains('@')
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+        },
+        None => false,
+    }
+}
+
+fn is_valid_phone(phone: &str) -> bool {
+    let digits = phone.chars().filter(char::is_ascii_digit).count();
+    let body = phone.strip_prefix('+').unwrap_or(phone);
+    PHONE_DIGITS.contains(&digits)
+        && body.chars().all(|c| c.is_ascii_digit() || matches!(c, ' ' | '-' | '(' | ')'))
+}
+
+fn is_valid_record_book_number(number: &str) -> bool {
+    number.chars().count() <= MAX_RECORD_BOOK_LENGTH
+        && number.chars().all(|c| c.is_alphanumeric() || matches!(c, '-' | '/'))
+}
+
+impl StudentProfile {
+    /// Trims every field, drops blank ones and checks the formats of what remains.
+    pub(crate) fn normalize(self) -> Result<Self, String> {
+        let profile = StudentProfile {
+            patronymic: non_blank(self.patronymic),
+            email: non_blank(self.email).map(|email| email.to_lowercase()),
+            phone: non_blank(self.phone),
+            birth_date: self.birth_date,
+            record_book_number: non_blank(self.record_book_number),
+        };
+
+        if matches!(&profile.patronymic, Some(patronymic) if patronymic.chars().count() > MAX_NAME_LENGTH) {
+            return Err(format!("Patronymic must not be longer than {MAX_NAME_LENGTH} characters"));
+        }
+        if matches!(&profile.email, Some(email) if !is_valid_email(email)) {
+            return Err("Email address is not valid".to_string());
+        }
+        if matches!(&profile.phone, Some(phone) if !is_valid_phone(phone)) {
+            return Err("Phone number must contain 10 to 15 digits and may start with +".to_string());
+        }
+        if let Some(birth_date) = profile.birth_date {
+            let earliest = NaiveDate::from_ymd_opt(1900, 1, 1).expect("valid date");
+            if birth_date < earliest || birth_date > Utc::now().date_naive() {
+                return Err("Date of birth must be between 1900-01-01 and today".to_string());
+            }
+        }
+        if matches!(&profile.record_book_number, Some(number) if !is_valid_record_book_number(number)) {
+            return Err(format!(
+                "Record book number must be at most {MAX_RECORD_BOOK_LENGTH} letters, digits, '-' or '/'"
+            ));
+        }
+        Ok(profile)
+    }
+}
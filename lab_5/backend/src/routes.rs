@@ -1,67 +1,261 @@
 use actix_web::{web, HttpResponse, Responder};
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderName, HeaderValue};
+use actix_web::http::StatusCode;
 use actix_multipart::Multipart;
-use futures::{StreamExt, TryStreamExt};
+use futures::StreamExt;
 use serde::{Deserialize, Serialize};
-use sqlx::{PgPool, Row};
-use mongodb::Collection;
-use mongodb::bson::doc;
+use chrono::NaiveDate;
+use utoipa::{IntoParams, OpenApi, ToSchema};
+use utoipa_swagger_ui::SwaggerUi;
 
-use std::path::PathBuf;
-use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::future::Future;
 
-enum StorageType {
-    Blob,
-    Filesystem
+use crate::{attendance, audit, batch, calendar, duplicates, events, export, grades, graphql, import, lifecycle, openapi, schedule, webhooks};
+use crate::events::{Event, EventKind};
+use crate::groups::GroupStore;
+use crate::openapi::{BadRequest, Binary, NotFound, ServerError};
+use crate::profile::{StudentProfile, StudentStatus, PROFILE_COLUMNS};
+use crate::storage::{Group, StoreError, Student, StudyForm};
+use crate::students::StudentStore;
+
+/// An optional field of a request body: `None` when it was left out, `Some(None)`
+/// when it was sent as `null`.
+pub(crate) type Field<T> = Option<Option<T>>;
+
+pub(crate) fn present<'de, D: serde::Deserializer<'de>, T: Deserialize<'de>>(deserializer: D) -> Result<Field<T>, D::Error> {
+    Option::<T>::deserialize(deserializer).map(Some)
 }
 
-lazy_static! {
-    static ref STORAGE_TYPE_STR: String = std::env::var("STORAGE_TYPE").expect("STORAGE_TYPE must be set");
-    static ref STORAGE_TYPE: StorageType = match STORAGE_TYPE_STR.as_str() {
-        "blob" => StorageType::Blob,
-        "filesystem" => StorageType::Filesystem,
-        _ => panic!("Invalid storage type")
-    };
-    static ref STUDENT_TABLE_NAME: &'static str = {
-        match *STORAGE_TYPE {
-            StorageType::Blob => "students_blob",
-            StorageType::Filesystem => "students_fs",
+/// A new group, or the changes to one. An update leaves the fields it does not
+/// send as they are, and clears those it sends as `null`.
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct GroupInput {
+    pub(crate) name: String,
+    #[serde(default, deserialize_with = "present")]
+    #[schema(value_type = Option<String>)]
+    pub(crate) faculty: Field<String>,
+    #[serde(default, deserialize_with = "present")]
+    #[schema(value_type = Option<String>)]
+    pub(crate) specialty_code: Field<String>,
+    #[serde(default, deserialize_with = "present")]
+    #[schema(value_type = Option<i32>)]
+    pub(crate) admission_year: Field<i32>,
+    #[serde(default, deserialize_with = "present")]
+    #[schema(value_type = Option<StudyForm>)]
+    pub(crate) study_form: Field<StudyForm>,
+    #[serde(default, deserialize_with = "present")]
+    #[schema(value_type = Option<i32>)]
+    pub(crate) max_capacity: Field<i32>,
+}
+
+impl GroupInput {
+    fn validate(&self) -> Result<(), HttpResponse> {
+        if self.name.trim().is_empty() {
+            return Err(HttpResponse::BadRequest().body("Group name must not be empty"));
         }
-    };
-    static ref IMAGES_PATH: PathBuf = std::env::var("IMAGES_PATH").expect("IMAGES_PATH must be set").into();
+        if matches!(self.admission_year, Some(Some(year)) if !(1900..=2100).contains(&year)) {
+            return Err(HttpResponse::BadRequest().body("Admission year must be between 1900 and 2100"));
+        }
+        if matches!(self.max_capacity, Some(Some(capacity)) if capacity <= 0) {
+            return Err(HttpResponse::BadRequest().body("Max capacity must be positive"));
+        }
+        Ok(())
+    }
+
+    /// Names of the optional fields that were sent, the name aside.
+    pub(crate) fn sent_fields(&self) -> Vec<&'static str> {
+        [
+            ("faculty", self.faculty.is_some()),
+            ("specialty_code", self.specialty_code.is_some()),
+            ("admission_year", self.admission_year.is_some()),
+            ("study_form", self.study_form.is_some()),
+            ("max_capacity", self.max_capacity.is_some()),
+        ].into_iter().filter_map(|(field, sent)| sent.then_some(field)).collect()
+    }
 }
 
-#[derive(Serialize, Deserialize, sqlx::FromRow)]
-struct Student {
-    id: i32,
-    name: String,
-    surname: String,
-    group_id: i32,
+#[derive(Deserialize, IntoParams, Default)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct GroupFilter {
+    pub(crate) faculty: Option<String>,
+    pub(crate) specialty_code: Option<String>,
+    pub(crate) admission_year: Option<i32>,
+    pub(crate) study_form: Option<StudyForm>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Group {
-    id: i32,
-    name: String,
+#[derive(Deserialize, ToSchema)]
+struct LeaderInput {
+    student_id: i32,
+}
+
+const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 500;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct Pagination {
+    /// 1-based page number; pagination is off unless `page` or `per_page` is given.
+    page: Option<u32>,
+    /// Page size, 50 by default and at most 500.
+    per_page: Option<u32>,
 }
 
-#[derive(Deserialize)]
-struct GroupInput {
-    name: String,
+impl Pagination {
+    /// Returns `(page, per_page)` if the client asked for pagination at all.
+    fn resolve(&self) -> Option<(u32, u32)> {
+        if self.page.is_none() && self.per_page.is_none() {
+            return None;
+        }
+        let page = self.page.unwrap_or(1).max(1);
+        let per_page = self.per_page.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+        Some((page, per_page))
+    }
 }
 
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct GroupListOptions {
+    /// `students` embeds the members of every group.
+    expand: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct GroupWithStudents {
+    #[serde(flatten)]
+    group: Group,
+    student_count: usize,
+    students: Vec<Student>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct GroupRoster {
+    group: Group,
+    student_count: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    page: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    per_page: Option<u32>,
+    students: Vec<Student>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct StudentFilter {
+    status: Option<StudentStatus>,
+    /// Words that must each occur in the name or surname, in Cyrillic or Latin.
+    q: Option<String>,
+}
+
+/// Multipart body of `POST /api/v1/students` and `PUT /api/v1/students/{id}`; only
+/// used to document the fields parsed by [`process_multipart_fields`].
+#[derive(ToSchema)]
+#[schema(as = StudentForm)]
+#[allow(non_snake_case, dead_code)]
+struct StudentMultipart {
+    /// Id of the student; ignored on create, the path takes precedence on update.
+    studentId: Option<i32>,
+    studentName: String,
+    studentSurname: String,
+    /// Required for a new student. Like the profile fields, an update keeps the
+    /// group when this is left out and removes the student from it when sent blank.
+    studentGroup: Option<i32>,
+    /// This and the other profile fields keep their value on update when left
+    /// out, and are cleared when sent blank.
+    studentPatronymic: Option<String>,
+    studentEmail: Option<String>,
+    studentPhone: Option<String>,
+    studentBirthDate: Option<NaiveDate>,
+    studentRecordBook: Option<String>,
+    /// JPEG or PNG photo.
+    #[schema(content_media_type = "application/octet-stream")]
+    studentPhoto: Option<Vec<u8>>,
+}
+
+/// A student as submitted by a client, before validation.
 #[derive(Default)]
-struct StudentForm {
-    id: i32,
-    name: String,
-    surname: String,
-    group_id: i32,
-    image_data: Option<(Vec<u8>, String)>,
+pub(crate) struct StudentForm {
+    pub(crate) id: i32,
+    pub(crate) name: String,
+    pub(crate) surname: String,
+    pub(crate) group_id: Option<i32>,
+    pub(crate) profile: StudentProfile,
+    /// Columns of the group and profile fields the client left out, which an
+    /// update keeps as they are. Fields sent blank are cleared.
+    pub(crate) kept_fields: Vec<&'static str>,
+    /// Photo bytes and their MIME type, already checked to be a valid JPEG or PNG.
+    pub(crate) image_data: Option<(Vec<u8>, String)>,
+}
+
+impl StudentForm {
+    fn normalize(&mut self) -> Result<(), HttpResponse> {
+        self.profile = std::mem::take(&mut self.profile)
+            .normalize()
+            .map_err(|e| HttpResponse::BadRequest().body(e))?;
+        Ok(())
+    }
+}
+
+/// Rejects photos that are neither JPEG nor PNG before their contents are read.
+pub(crate) fn check_photo_type(image_type: &str) -> Result<(), HttpResponse> {
+    match image_type {
+        "image/jpeg" | "image/jpg" | "image/png" => Ok(()),
+        _ => Err(HttpResponse::BadRequest().body("Invalid image format. Only JPEG and PNG are supported.")),
+    }
 }
 
-// const MAX_IMAGE_SIZE: usize = 5 * 1024 * 1024; // 5MB
+/// Checks that a photo is a complete image in the format its MIME type claims.
+pub(crate) fn validate_photo(image_data: &[u8], image_type: &str) -> Result<(), HttpResponse> {
+    check_photo_type(image_type)?;
+    if image_data.is_empty() {
+        return Err(HttpResponse::BadRequest()
+            .body("Empty image data received"));
+    }
+
+    let format = match image::guess_format(image_data) {
+        Ok(format) => format,
+        Err(e) => {
+            return Err(HttpResponse::BadRequest()
+                .body(format!("Invalid image data: {}", e)));
+        }
+    };
+
+    let valid_format = match image_type {
+        "image/png" => format == image::ImageFormat::Png,
+        "image/jpeg" | "image/jpg" => format == image::ImageFormat::Jpeg,
+        _ => false
+    };
+
+    if !valid_format {
+        return Err(HttpResponse::BadRequest()
+            .body("Image format doesn't match the specified content type"));
+    }
+
+    if let Err(e) = image::load_from_memory(image_data) {
+        return Err(HttpResponse::BadRequest()
+            .body(format!("Corrupted image data: {}", e)));
+    }
+    Ok(())
+}
+
+/// Column of the group or profile field a form field carries.
+fn kept_column(field_name: &str) -> Option<&'static str> {
+    match field_name {
+        "studentGroup" => Some("group_id"),
+        "studentPatronymic" => Some("patronymic"),
+        "studentEmail" => Some("email"),
+        "studentPhone" => Some("phone"),
+        "studentBirthDate" => Some("birth_date"),
+        "studentRecordBook" => Some("record_book_number"),
+        _ => None,
+    }
+}
 
 async fn process_multipart_fields(mut payload: Multipart) -> Result<StudentForm, HttpResponse> {
-    let mut student: StudentForm = Default::default();
+    let kept_fields = PROFILE_COLUMNS.into_iter().chain(["group_id"]).collect();
+    let mut student = StudentForm { kept_fields, ..Default::default() };
 
     while let Some(Ok(mut field)) = payload.next().await {
         let field_name = field.content_disposition()
@@ -71,60 +265,28 @@ async fn process_multipart_fields(mut payload: Multipart) -> Result<StudentForm,
 
         match field_name.as_str() {
             "studentPhoto" => {
-                if let Some(content_type) = field.content_type() {
-                    let image_type = content_type.to_string();
-
-                    if !matches!(image_type.as_str(), "image/jpeg" | "image/jpg" | "image/png") {
-                        return Err(HttpResponse::BadRequest()
-                            .body("Invalid image format. Only JPEG and PNG are supported."));
-                    }
-
-                    let mut image_data = Vec::new();
-
-                    while let Some(chunk) = field.next().await {
-                        match chunk {
-                            Ok(data) => image_data.extend_from_slice(&data),
-                            Err(e) => return Err(HttpResponse::BadRequest()
-                                .body(format!("Failed to read image data: {e}")))
-                        }
-                    }
-
-                    if image_data.is_empty() {
-                        return Err(HttpResponse::BadRequest()
-                            .body("Empty image data received"));
-                    }
-
-                    let format = match image::guess_format(&image_data) {
-                        Ok(format) => format,
-                        Err(e) => {
-                            return Err(HttpResponse::BadRequest()
-                                .body(format!("Invalid image data: {}", e)));
-                        }
-                    };
+                let Some(content_type) = field.content_type() else {
+                    return Err(HttpResponse::BadRequest()
+                        .body("Invalid image format. Content-Type is missing."));
+                };
+                let image_type = content_type.to_string();
+                check_photo_type(&image_type)?;
 
-                    let valid_format = match image_type.as_str() {
-                        "image/png" => format == image::ImageFormat::Png,
-                        "image/jpeg" | "image/jpg" => format == image::ImageFormat::Jpeg,
-                        _ => false
-                    };
+                let mut image_data = Vec::new();
 
-                    if !valid_format {
-                        return Err(HttpResponse::BadRequest()
-                            .body("Image format doesn't match the specified content type"));
-                    }
-
-                    if let Err(e) = image::load_from_memory(&image_data) {
-                        return Err(HttpResponse::BadRequest()
-                            .body(format!("Corrupted image data: {}", e)));
+                while let Some(chunk) = field.next().await {
+                    match chunk {
+                        Ok(data) => image_data.extend_from_slice(&data),
+                        Err(e) => return Err(HttpResponse::BadRequest()
+                            .body(format!("Failed to read image data: {e}")))
                     }
-
-                    student.image_data = Some((image_data, image_type));
-                } else {
-                    return Err(HttpResponse::BadRequest()
-                        .body("Invalid image format. Content-Type is missing."));
                 }
+
+                validate_photo(&image_data, &image_type)?;
+                student.image_data = Some((image_data, image_type));
             },
-            "studentId" | "studentName" | "studentSurname" | "studentGroup" => {
+            "studentId" | "studentName" | "studentSurname" | "studentGroup" | "studentPatronymic" | "studentEmail"
+            | "studentPhone" | "studentBirthDate" | "studentRecordBook" => {
                 let mut field_value = Vec::new();
                 while let Some(chunk) = field.next().await {
                     match chunk {
@@ -135,11 +297,37 @@ async fn process_multipart_fields(mut payload: Multipart) -> Result<StudentForm,
                 }
 
                 let value = String::from_utf8_lossy(&field_value).to_string();
+                if let Some(column) = kept_column(&field_name) {
+                    student.kept_fields.retain(|kept| *kept != column);
+                }
                 match field_name.as_str() {
                     "studentId" => student.id = value.parse().unwrap_or(0),
                     "studentName" => student.name = value,
                     "studentSurname" => student.surname = value,
-                    "studentGroup" => student.group_id = value.parse().unwrap_or(0),
+                    "studentGroup" => {
+                        student.group_id = match value.trim() {
+                            "" => None,
+                            group => match group.parse() {
+                                Ok(group_id) => Some(group_id),
+                                Err(_) => return Err(HttpResponse::BadRequest()
+                                    .body(format!("Invalid group id: {group}"))),
+                            },
+                        }
+                    },
+                    "studentPatronymic" => student.profile.patronymic = Some(value),
+                    "studentEmail" => student.profile.email = Some(value),
+                    "studentPhone" => student.profile.phone = Some(value),
+                    "studentBirthDate" => {
+                        student.profile.birth_date = match value.trim() {
+                            "" => None,
+                            date => match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+                                Ok(date) => Some(date),
+                                Err(e) => return Err(HttpResponse::BadRequest()
+                                    .body(format!("Invalid date of birth: {e}"))),
+                            },
+                        }
+                    },
+                    "studentRecordBook" => student.profile.record_book_number = Some(value),
                     _ => unreachable!()
                 }
             },
@@ -147,33 +335,137 @@ async fn process_multipart_fields(mut payload: Multipart) -> Result<StudentForm,
                 .body(format!("Unrecognized key: {unrecognized_key}")))
         }
     }
+
     Ok(student)
 }
 
-async fn get_students(pool: web::Data<PgPool>) -> impl Responder {
+/// Status and message of an error response, for the APIs that report errors
+/// in a format of their own.
+pub(crate) fn error_parts(response: HttpResponse) -> (StatusCode, String) {
+    let status = response.status();
+    let body = response.into_body().try_into_bytes().unwrap_or_default();
+    let message = match String::from_utf8_lossy(&body).trim() {
+        "" => status.canonical_reason().unwrap_or("Request failed").to_string(),
+        message => message.to_string(),
+    };
+    (status, message)
+}
+
+/// Maps constraint violations to client errors and everything else to a 500.
+pub(crate) fn database_error(context: &str, e: sqlx::Error) -> HttpResponse {
+    if let sqlx::Error::Database(db_error) = &e {
+        // Raised when a student, class or assessment is placed in a group that is being deleted.
+        if db_error.code().as_deref() == Some("55006") {
+            log::warn!("{}: {}", context, e);
+            return HttpResponse::Conflict().body(format!("{context}: {}", db_error.message()));
+        }
+        let message = match db_error.code().as_deref() {
+            Some("23503") => Some("references a record that does not exist or is still referenced"),
+            Some("23505") => Some("conflicts with an existing record"),
+            Some("23514") => Some("violates a check constraint"),
+            _ => None,
+        };
+        if let Some(message) = message {
+            log::warn!("{}: {}", context, e);
+            return HttpResponse::BadRequest().body(format!("{context}: {message}"));
+        }
+    }
+    log::error!("{}: {}", context, e);
+    HttpResponse::InternalServerError().body(e.to_string())
+}
+
+/// Maps a failure of a store to a response like [`database_error`] does; the
+/// reasons a store refuses a change become client errors.
+pub(crate) fn store_error(context: &str, e: StoreError) -> HttpResponse {
+    match e {
+        StoreError::Postgres(e) => database_error(context, e),
+        StoreError::Rejected(message) => {
+            log::warn!("{}: {}", context, message);
+            HttpResponse::BadRequest().body(message)
+        },
+        StoreError::NotFound => {
+            log::warn!("{}: not found", context);
+            HttpResponse::NotFound().finish()
+        },
+        e => {
+            log::error!("{}: {}", context, e);
+            HttpResponse::InternalServerError().body(e.to_string())
+        }
+    }
+}
+
+/// Rejects placing a student into a group that has already reached its
+/// `max_capacity`. Students that are already members of the group are let through.
+async fn check_group_capacity(
+    group_store: &GroupStore,
+    student_store: &StudentStore,
+    group_id: i32,
+    student_id: Option<i32>
+) -> Result<(), HttpResponse> {
+    let max_capacity = match group_store.find(group_id).await {
+        Ok(Some(group)) => group.max_capacity,
+        Ok(None) => None,
+        Err(e) => {
+            log::error!("Failed to fetch group: {}", e);
+            return Err(HttpResponse::InternalServerError().body(e.to_string()));
+        }
+    };
+    let Some(max_capacity) = max_capacity else {
+        return Ok(());
+    };
+
+    match student_store.group_occupancy(group_id, student_id).await {
+        Ok((count, already_member)) => {
+            if !already_member && count >= i64::from(max_capacity) {
+                log::warn!("Group {} is at full capacity ({} students)", group_id, count);
+                return Err(HttpResponse::BadRequest()
+                    .body(format!("Group is at full capacity ({} students)", max_capacity)));
+            }
+            Ok(())
+        },
+        Err(e) => {
+            log::error!("Error counting students in group: {}", e);
+            Err(HttpResponse::InternalServerError().body(e.to_string()))
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/students",
+    tag = "students",
+    params(StudentFilter),
+    responses(
+        (status = 200, description = "Students matching the filters", body = Vec<Student>),
+        (status = 400, response = BadRequest),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn get_students(filter: web::Query<StudentFilter>, student_store: web::Data<StudentStore>) -> impl Responder {
     log::debug!("Fetching all students");
-    let query = format!("SELECT id, name, surname, group_id FROM {}", *STUDENT_TABLE_NAME);
-    match sqlx::query_as::<_, Student>(&query)
-        .fetch_all(pool.get_ref())
-        .await {
+    match student_store.find_all(filter.status, filter.q.as_deref()).await {
         Ok(students) => {
             log::info!("Successfully retrieved {} students", students.len());
             HttpResponse::Ok().json(students)
         },
-        Err(e) => {
-            log::error!("Failed to fetch students: {}", e);
-            HttpResponse::InternalServerError().body(e.to_string())
-        }
+        Err(e) => store_error("Failed to fetch students", e),
     }
 }
 
-async fn get_student(id: web::Path<i32>, pool: web::Data<PgPool>) -> impl Responder {
+#[utoipa::path(
+    get,
+    path = "/students/{id}",
+    tag = "students",
+    params(("id" = i32, Path, description = "Student id")),
+    responses(
+        (status = 200, description = "The student", body = Student),
+        (status = 404, response = NotFound),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn get_student(id: web::Path<i32>, student_store: web::Data<StudentStore>) -> impl Responder {
     log::debug!("Fetching student with id: {}", id);
-    let query = format!("SELECT id, name, surname, group_id FROM {} WHERE id = $1", *STUDENT_TABLE_NAME);
-    match sqlx::query_as::<_, Student>(&query)
-        .bind(id.as_ref())
-        .fetch_optional(pool.get_ref())
-        .await {
+    match student_store.find(*id).await {
         Ok(Some(student)) => {
             log::debug!("Successfully retrieved student: {} {}", student.name, student.surname);
             HttpResponse::Ok().json(student)
@@ -182,110 +474,58 @@ async fn get_student(id: web::Path<i32>, pool: web::Data<PgPool>) -> impl Respon
             log::debug!("Student not found with id: {}", id);
             HttpResponse::NotFound().finish()
         },
-        Err(e) => {
-            log::error!("Failed to fetch student: {}", e);
-            HttpResponse::InternalServerError().body(e.to_string())
-        }
+        Err(e) => store_error("Failed to fetch student", e),
     }
 }
 
-#[derive(Serialize, Deserialize, sqlx::FromRow)]
-struct ImageBlobData {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    image_data: Option<Vec<u8>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    image_type: Option<String>,
-}
-
-#[derive(Serialize, Deserialize, sqlx::FromRow)]
-struct ImageFilesystemData {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    image_path: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    image_type: Option<String>,
-}
-
-async fn get_student_image(id: web::Path<i32>, pool: web::Data<PgPool>) -> impl Responder {
+#[utoipa::path(
+    get,
+    path = "/students/image/{id}",
+    tag = "students",
+    params(("id" = i32, Path, description = "Student id")),
+    responses(
+        (status = 200, description = "Photo of the student", content(
+            (Binary = "image/jpeg"),
+            (Binary = "image/png")
+        )),
+        (status = 404, description = "The student does not exist or has no photo"),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn get_student_image(id: web::Path<i32>, student_store: web::Data<StudentStore>) -> impl Responder {
     log::debug!("Fetching image for student with id: {}", id);
 
-    match *STORAGE_TYPE {
-        StorageType::Blob => {
-            let query = format!(
-                "SELECT image_data, image_type FROM {} WHERE id = $1",
-                *STUDENT_TABLE_NAME
-            );
-            match sqlx::query_as::<_, ImageBlobData>(&query)
-                .bind(id.as_ref())
-                .fetch_optional(pool.get_ref())
-                .await
-            {
-                Ok(Some(row)) => {
-                    if let (Some(image_data), Some(image_type)) = (row.image_data, row.image_type) {
-                        log::debug!("Successfully retrieved blob image of type: {} for student id: {}", image_type, id);
-                        HttpResponse::Ok()
-                            .content_type(image_type)
-                            .body(image_data)
-                    } else {
-                        log::debug!("No image found for student id: {}", id);
-                        HttpResponse::NotFound().finish()
-                    }
-                },
-                Ok(None) => {
-                    log::debug!("Student not found with id: {}", id);
-                    HttpResponse::NotFound().finish()
-                },
-                Err(e) => {
-                    log::error!("Query error in get_student_image: {}", e);
-                    HttpResponse::InternalServerError()
-                        .body(format!("Query error in get_student_image: {}", e))
-                }
-            }
+    match student_store.load_photo(*id).await {
+        Ok(Some((image_data, image_type))) => {
+            log::debug!("Successfully retrieved image of type: {} for student id: {}", image_type, id);
+            HttpResponse::Ok()
+                .content_type(image_type)
+                .body(image_data)
         },
-        StorageType::Filesystem => {
-            let query = format!(
-                "SELECT image_path, image_type FROM {} WHERE id = $1",
-                *STUDENT_TABLE_NAME
-            );
-            match sqlx::query_as::<_, ImageFilesystemData>(&query)
-                .bind(id.as_ref())
-                .fetch_optional(pool.get_ref())
-                .await
-            {
-                Ok(Some(row)) => {
-                    if let (Some(image_path), Some(image_type)) = (row.image_path, row.image_type) {
-                        match tokio::fs::read(image_path).await {
-                            Ok(image_data) => {
-                                log::debug!("Successfully retrieved file image of type: {} for student id: {}", image_type, id);
-                                HttpResponse::Ok()
-                                    .content_type(image_type)
-                                    .body(image_data)
-                            },
-                            Err(e) => {
-                                log::error!("Failed to read image file: {}", e);
-                                HttpResponse::InternalServerError()
-                                    .body(format!("Failed to read image file: {}", e))
-                            }
-                        }
-                    } else {
-                        log::debug!("No image found for student id: {}", id);
-                        HttpResponse::NotFound().finish()
-                    }
-                },
-                Ok(None) => {
-                    log::debug!("Student not found with id: {}", id);
-                    HttpResponse::NotFound().finish()
-                },
-                Err(e) => {
-                    log::error!("Query error in get_student_image: {}", e);
-                    HttpResponse::InternalServerError()
-                        .body(format!("Query error in get_student_image: {}", e))
-                }
-            }
-        }
+        Ok(None) => {
+            log::debug!("No image found for student id: {}", id);
+            HttpResponse::NotFound().finish()
+        },
+        Err(e) => store_error("Failed to load student photo", e),
     }
 }
 
-async fn create_student(payload: Multipart, pool: web::Data<PgPool>) -> impl Responder {
+#[utoipa::path(
+    post,
+    path = "/students",
+    tag = "students",
+    request_body(content = inline(StudentMultipart), content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "The student was created"),
+        (status = 400, response = BadRequest),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn create_student(
+    payload: Multipart,
+    student_store: web::Data<StudentStore>,
+    group_store: web::Data<GroupStore>
+) -> impl Responder {
     log::debug!("Processing new student creation request");
     let student_form = match process_multipart_fields(payload).await {
         Ok(fields) => {
@@ -298,150 +538,53 @@ async fn create_student(payload: Multipart, pool: web::Data<PgPool>) -> impl Res
         },
     };
 
-    match *STORAGE_TYPE {
-        StorageType::Blob => {
-            let res = match &student_form.image_data {
-                Some((image_data, image_type)) => {
-                    log::debug!("Creating student with blob image, type: {}", image_type);
-                    sqlx::query(&format!(
-                        "INSERT INTO {} (name, surname, group_id, image_data, image_type)
-                         VALUES ($1, $2, $3, $4, $5)",
-                        *STUDENT_TABLE_NAME
-                    ))
-                        .bind(&student_form.name)
-                        .bind(&student_form.surname)
-                        .bind(student_form.group_id)
-                        .bind(image_data)
-                        .bind(image_type)
-                        .execute(pool.get_ref()).await
-                },
-                None => {
-                    log::debug!("Creating student without image");
-                    sqlx::query(&format!(
-                        "INSERT INTO {} (name, surname, group_id)
-                         VALUES ($1, $2, $3)",
-                        *STUDENT_TABLE_NAME
-                    ))
-                        .bind(&student_form.name)
-                        .bind(&student_form.surname)
-                        .bind(student_form.group_id)
-                        .execute(pool.get_ref()).await
-                }
-            };
+    match add_student(student_store.get_ref(), group_store.get_ref(), student_form).await {
+        Ok(_) => HttpResponse::Ok().finish(),
+        Err(response) => response,
+    }
+}
 
-            match res {
-                Ok(_) => {
-                    log::info!("Successfully created new student: {}", student_form.name);
-                    HttpResponse::Ok().finish()
-                },
-                Err(e) => {
-                    log::error!("Failed to create student: {}", e);
-                    HttpResponse::InternalServerError().body(e.to_string())
-                }
-            }
-        },
-        StorageType::Filesystem => {
-            let result = match &student_form.image_data {
-                Some((image_data, image_type)) => {
-                    let mut tx = match pool.begin().await {
-                        Ok(tx) => tx,
-                        Err(e) => {
-                            log::error!("Failed to start transaction: {}", e);
-                            return HttpResponse::InternalServerError()
-                                .body(format!("Failed to start transaction: {}", e));
-                        }
-                    };
-
-                    let file_path = IMAGES_PATH
-                        .join(uuid::Uuid::new_v4().to_string());
-                    let file_path = file_path.to_str();
-                    if file_path.is_none() {
-                        return HttpResponse::InternalServerError()
-                            .body(format!("Can not convert to string: {:?}", file_path));
-                    }
-                    let file_path = file_path.unwrap();
-
-                    let db_result = sqlx::query(&format!(
-                        "INSERT INTO {} (name, surname, group_id, image_path, image_type)
-                         VALUES ($1, $2, $3, $4, $5) RETURNING id",
-                        *STUDENT_TABLE_NAME
-                    ))
-                        .bind(&student_form.name)
-                        .bind(&student_form.surname)
-                        .bind(student_form.group_id)
-                        .bind(file_path)
-                        .bind(image_type)
-                        .execute(&mut *tx)
-                        .await;
-
-                    match db_result {
-                        Ok(_) => {
-                            match tokio::fs::write(file_path, image_data).await {
-                                Ok(_) => {
-                                    if let Err(e) = tx.commit().await {
-                                        if let Err(cleanup_err) = tokio::fs::remove_file(&file_path).await {
-                                            log::error!("Failed to clean up file after commit failure: {}", cleanup_err);
-                                        }
-                                        log::error!("Failed to commit transaction: {}", e);
-                                        return HttpResponse::InternalServerError()
-                                            .body(format!("Failed to commit transaction: {}", e));
-                                    }
-                                    log::info!("Successfully created new student: {} with image", student_form.name);
-                                    HttpResponse::Ok().finish()
-                                },
-                                Err(e) => {
-                                    if let Err(rollback_err) = tx.rollback().await {
-                                        log::error!("Failed to rollback transaction: {}", rollback_err);
-                                    }
-                                    log::error!("Failed to write image file: {}", e);
-                                    HttpResponse::InternalServerError()
-                                        .body(format!("Failed to write image file: {}", e))
-                                }
-                            }
-                        },
-                        Err(e) => {
-                            if let Err(rollback_err) = tx.rollback().await {
-                                log::error!("Failed to rollback transaction: {}", rollback_err);
-                            }
-                            log::error!("Failed to create student record: {}", e);
-                            HttpResponse::InternalServerError().body(e.to_string())
-                        }
-                    }
-                },
-                None => {
-                    match sqlx::query(&format!(
-                        "INSERT INTO {} (name, surname, group_id)
-                         VALUES ($1, $2, $3)",
-                        *STUDENT_TABLE_NAME
-                    ))
-                        .bind(&student_form.name)
-                        .bind(&student_form.surname)
-                        .bind(student_form.group_id)
-                        .execute(pool.get_ref())
-                        .await
-                    {
-                        Ok(_) => {
-                            log::info!("Successfully created new student: {}", student_form.name);
-                            HttpResponse::Ok().finish()
-                        },
-                        Err(e) => {
-                            log::error!("Failed to create student: {}", e);
-                            HttpResponse::InternalServerError().body(e.to_string())
-                        }
-                    }
-                }
-            };
+/// Validates and stores a new student together with their photo. Returns the
+/// id of the student.
+pub(crate) async fn add_student(
+    student_store: &StudentStore,
+    group_store: &GroupStore,
+    mut student_form: StudentForm
+) -> Result<i32, HttpResponse> {
+    student_form.normalize()?;
+    let Some(group_id) = student_form.group_id else {
+        return Err(HttpResponse::BadRequest().body("A new student must be assigned to a group"));
+    };
+    check_group_capacity(group_store, student_store, group_id, None).await?;
 
-            result
-        }
-    }
+    let event = student_store.insert(&student_form, group_store).await
+        .map_err(|e| store_error("Failed to create student", e))?;
+    log::info!("Successfully created new student: {}", student_form.name);
+
+    let id = event.id;
+    events::publish(event);
+    Ok(id)
 }
 
+#[utoipa::path(
+    put,
+    path = "/students/{id}",
+    tag = "students",
+    params(("id" = i32, Path, description = "Student id")),
+    request_body(content = inline(StudentMultipart), content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "The student was updated; the photo is kept unless a new one is sent"),
+        (status = 400, response = BadRequest),
+        (status = 404, response = NotFound),
+        (status = 500, response = ServerError)
+    )
+)]
 async fn update_student(
     id: web::Path<i32>,
     payload: Multipart,
-    pool: web::Data<PgPool>
-) -> impl Responder {
+    student_store: web::Data<StudentStore>,
+    group_store: web::Data<GroupStore>
+) -> HttpResponse {
     log::debug!("Processing update request for student id: {}", id.as_ref());
     let student_form = match process_multipart_fields(payload).await {
         Ok(fields) => {
@@ -454,290 +597,132 @@ async fn update_student(
         },
     };
 
-    match *STORAGE_TYPE {
-        StorageType::Blob => {
-            let res = match &student_form.image_data {
-                Some((image_data, image_type)) => {
-                    log::debug!("Updating student with new blob image, type: {}", image_type);
-                    sqlx::query(&format!(
-                        "UPDATE {}
-                         SET name = $1, surname = $2, group_id = $3, image_data = $4, image_type = $5
-                         WHERE id = $6",
-                        *STUDENT_TABLE_NAME
-                    ))
-                        .bind(&student_form.name)
-                        .bind(&student_form.surname)
-                        .bind(student_form.group_id)
-                        .bind(image_data)
-                        .bind(image_type)
-                        .bind(id.as_ref())
-                        .execute(pool.get_ref()).await
-                },
-                None => {
-                    log::debug!("Updating student without changing image");
-                    let query = format!(
-                        "UPDATE {} \
-                        SET name = $1, surname = $2, group_id = $3 \
-                        WHERE id = $4",
-                        *STUDENT_TABLE_NAME
-                    );
-                    sqlx::query(&query)
-                        .bind(&student_form.name)
-                        .bind(&student_form.surname)
-                        .bind(student_form.group_id)
-                        .bind(id.as_ref())
-                        .execute(pool.get_ref()).await
-                }
-            };
+    match edit_student(student_store.get_ref(), group_store.get_ref(), *id, student_form).await {
+        Ok(()) => HttpResponse::Ok().finish(),
+        Err(response) => response,
+    }
+}
 
-            match res {
-                Ok(_) => {
-                    log::info!("Successfully updated student id: {}", id);
-                    HttpResponse::Ok().finish()
-                },
-                Err(e) => {
-                    log::error!("Error updating student: {}", e);
-                    HttpResponse::InternalServerError()
-                        .body(format!("Error update_student: {}", e))
-                }
-            }
-        },
-        StorageType::Filesystem => {
-            let result = match &student_form.image_data {
-                Some((image_data, image_type)) => {
-                    let mut tx = match pool.begin().await {
-                        Ok(tx) => tx,
-                        Err(e) => {
-                            log::error!("Failed to start transaction: {}", e);
-                            return HttpResponse::InternalServerError()
-                                .body(format!("Failed to start transaction: {}", e));
-                        }
-                    };
-
-                    // First get the current image path within transaction
-                    let query = format!("SELECT image_path FROM {} WHERE id = $1", *STUDENT_TABLE_NAME);
-                    let current_path: Option<String> = match sqlx::query(&query)
-                        .bind(id.as_ref())
-                        .fetch_optional(&mut *tx)
-                        .await
-                    {
-                        Ok(row) => row.and_then(|r| r.get("image_path")),
-                        Err(e) => {
-                            if let Err(rollback_err) = tx.rollback().await {
-                                log::error!("Failed to rollback transaction: {}", rollback_err);
-                            }
-                            log::error!("Failed to fetch current image path: {}", e);
-                            return HttpResponse::InternalServerError()
-                                .body(format!("Failed to fetch current image path: {}", e));
-                        }
-                    };
-                    let file_path = IMAGES_PATH.join(uuid::Uuid::new_v4().to_string());
-                    let file_path = file_path.to_str();
+/// Validates and stores the changes to a student. The photo is only replaced
+/// when the form carries a new one.
+pub(crate) async fn edit_student(
+    student_store: &StudentStore,
+    group_store: &GroupStore,
+    id: i32,
+    mut student_form: StudentForm
+) -> Result<(), HttpResponse> {
+    student_form.normalize()?;
+    if let Some(group_id) = student_form.group_id {
+        check_group_capacity(group_store, student_store, group_id, Some(id)).await?;
+    }
+    let event = student_store.update(id, &student_form, group_store).await
+        .map_err(|e| store_error("Failed to update student", e))?;
+    log::info!("Successfully updated student id: {}", id);
 
-                    if file_path.is_none() {
-                        if let Err(rollback_err) = tx.rollback().await {
-                            log::error!("Failed to rollback transaction: {}", rollback_err);
-                        }
-                        return HttpResponse::InternalServerError()
-                                .body("Failed to generate valid file path");
-                    }
-                    let file_path = file_path.unwrap();
-
-                    let update_result = sqlx::query(&format!(
-                        "UPDATE {} \
-                        SET name = $1, surname = $2, group_id = $3, image_path = $4, image_type = $5 \
-                        WHERE id = $6",
-                        *STUDENT_TABLE_NAME
-                    ))
-                        .bind(&student_form.name)
-                        .bind(&student_form.surname)
-                        .bind(student_form.group_id)
-                        .bind(file_path)
-                        .bind(image_type)
-                        .bind(id.as_ref())
-                        .execute(&mut *tx)
-                        .await;
-
-                    match update_result {
-                        Ok(_) => {
-                            // Database update successful, now save the new file
-                            match tokio::fs::write(file_path, image_data).await {
-                                Ok(_) => {
-                                    // New file saved, commit transaction
-                                    if let Err(e) = tx.commit().await {
-                                        // Commit failed, clean up the new file
-                                        if let Err(cleanup_err) = tokio::fs::remove_file(file_path).await {
-                                            log::error!("Failed to clean up file after commit failure: {}", cleanup_err);
-                                        }
-                                        log::error!("Failed to commit transaction: {}", e);
-                                        return HttpResponse::InternalServerError()
-                                            .body(format!("Failed to commit transaction: {}", e));
-                                    }
-
-                                    // Everything succeeded, now safe to delete old file if it exists
-                                    if let Some(old_path) = current_path {
-                                        if let Err(e) = tokio::fs::remove_file(old_path).await {
-                                            log::warn!("Failed to delete old image file: {}", e);
-                                            // Non-critical error, operation still successful
-                                        }
-                                    }
-                                    log::info!("Successfully updated student id: {} with new image", id);
-                                    HttpResponse::Ok().finish()
-                                },
-                                Err(e) => {
-                                    // Failed to save new file, rollback transaction
-                                    if let Err(rollback_err) = tx.rollback().await {
-                                        log::error!("Failed to rollback transaction: {}", rollback_err);
-                                    }
-                                    log::error!("Failed to save new image file: {}", e);
-                                    HttpResponse::InternalServerError()
-                                        .body(format!("Failed to save new image file: {}", e))
-                                }
-                            }
-                        },
-                        Err(e) => {
-                            // Database update failed, rollback transaction
-                            if let Err(rollback_err) = tx.rollback().await {
-                                log::error!("Failed to rollback transaction: {}", rollback_err);
-                            }
-                            log::error!("Failed to update student record: {}", e);
-                            HttpResponse::InternalServerError().body(e.to_string())
-                        }
-                    }
-                },
-                None => {
-                    // Simple update without image change
-                    match sqlx::query(&format!(
-                        "UPDATE {} \
-                        SET name = $1, surname = $2, group_id = $3 \
-                        WHERE id = $4",
-                        *STUDENT_TABLE_NAME
-                    ))
-                        .bind(&student_form.name)
-                        .bind(&student_form.surname)
-                        .bind(student_form.group_id)
-                        .bind(id.as_ref())
-                        .execute(pool.get_ref())
-                        .await
-                    {
-                        Ok(_) => {
-                            log::info!("Successfully updated student id: {}", id);
-                            HttpResponse::Ok().finish()
-                        },
-                        Err(e) => {
-                            log::error!("Failed to update student: {}", e);
-                            HttpResponse::InternalServerError().body(e.to_string())
-                        }
-                    }
-                }
-            };
+    events::publish(event);
+    Ok(())
+}
 
-            result
-        }
+
+#[utoipa::path(
+    delete,
+    path = "/students/{id}",
+    tag = "students",
+    params(("id" = i32, Path, description = "Student id")),
+    responses(
+        (status = 200, description = "The student and their records were deleted"),
+        (status = 404, response = NotFound),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn delete_student(
+    id: web::Path<i32>,
+    student_store: web::Data<StudentStore>,
+    group_store: web::Data<GroupStore>
+) -> HttpResponse {
+    match remove_student(student_store.get_ref(), group_store.get_ref(), *id).await {
+        Ok(()) => HttpResponse::Ok().finish(),
+        Err(response) => response,
     }
 }
 
-async fn delete_student(id: web::Path<i32>, pool: web::Data<PgPool>) -> impl Responder {
-    log::debug!("Attempting to delete student with id: {}", id.as_ref());
-
-    match *STORAGE_TYPE {
-        StorageType::Blob => {
-            let query = format!("DELETE FROM {} WHERE id = $1", *STUDENT_TABLE_NAME);
-            match sqlx::query(&query)
-                .bind(id.as_ref())
-                .execute(pool.get_ref())
-                .await 
-            {
-                Ok(_) => {
-                    log::info!("Successfully deleted student with id: {}", id);
-                    HttpResponse::Ok().finish()
-                },
-                Err(e) => {
-                    log::error!("Failed to delete student: {}", e);
-                    HttpResponse::InternalServerError().body(e.to_string())
-                }
-            }
-        },
-        StorageType::Filesystem => {
-            let mut tx = match pool.begin().await {
-                Ok(tx) => tx,
-                Err(e) => {
-                    log::error!("Failed to start transaction: {}", e);
-                    return HttpResponse::InternalServerError()
-                        .body(format!("Failed to start transaction: {}", e));
-                }
-            };
+/// Deletes a student, their photo and, through the database trigger, their records.
+pub(crate) async fn remove_student(
+    student_store: &StudentStore,
+    group_store: &GroupStore,
+    id: i32
+) -> Result<(), HttpResponse> {
+    log::debug!("Attempting to delete student with id: {}", id);
+    student_store.delete(id, group_store).await
+        .map(events::publish)
+        .map_err(|e| store_error("Failed to delete student", e))?;
+    log::info!("Successfully deleted student with id: {}", id);
+    Ok(())
+}
 
-            // First get the image path within transaction
-            let query = format!("SELECT image_path FROM {} WHERE id = $1", *STUDENT_TABLE_NAME);
-            let image_path: Option<String> = match sqlx::query(&query)
-                .bind(id.as_ref())
-                .fetch_optional(&mut *tx)
-                .await 
-            {
-                Ok(row) => row.and_then(|r| r.get("image_path")),
-                Err(e) => {
-                    if let Err(rollback_err) = tx.rollback().await {
-                        log::error!("Failed to rollback transaction: {}", rollback_err);
-                    }
-                    log::error!("Failed to fetch image path: {}", e);
-                    return HttpResponse::InternalServerError()
-                        .body(format!("Failed to fetch image path: {}", e));
-                }
-            };
 
-            // Delete from database first
-            let query = format!("DELETE FROM {} WHERE id = $1", *STUDENT_TABLE_NAME);
-            match sqlx::query(&query)
-                .bind(id.as_ref())
-                .execute(&mut *tx)
-                .await 
-            {
-                Ok(_) => {
-                    // Database deletion successful, commit transaction
-                    if let Err(e) = tx.commit().await {
-                        log::error!("Failed to commit transaction: {}", e);
-                        return HttpResponse::InternalServerError()
-                            .body(format!("Failed to commit transaction: {}", e));
-                    }
+#[utoipa::path(
+    get,
+    path = "/groups",
+    tag = "groups",
+    params(GroupFilter, GroupListOptions, Pagination),
+    responses(
+        (status = 200, description = "Groups matching the filters; with `expand=students` every group \
+            also carries `student_count` and `students` like `GroupWithStudents`", body = Vec<Group>),
+        (status = 400, response = BadRequest),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn get_groups(
+    filter: web::Query<GroupFilter>,
+    options: web::Query<GroupListOptions>,
+    pagination: web::Query<Pagination>,
+    group_store: web::Data<GroupStore>,
+    student_store: web::Data<StudentStore>
+) -> impl Responder {
+    log::debug!("Fetching all groups");
+    let expand_students = match options.expand.as_deref() {
+        None => false,
+        Some("students") => true,
+        Some(other) => {
+            return HttpResponse::BadRequest().body(format!("Unsupported expand value: {other}"));
+        }
+    };
 
-                    // If there was an image, try to delete it
-                    if let Some(path) = image_path {
-                        if let Err(e) = tokio::fs::remove_file(path).await {
-                            log::warn!("Failed to delete image file: {}", e);
-                            // Non-critical error, student was deleted successfully
-                        }
-                    }
+    let page = pagination.resolve().map(|(page, per_page)| {
+        (u64::from(page - 1) * u64::from(per_page), i64::from(per_page))
+    });
 
-                    log::info!("Successfully deleted student with id: {}", id);
-                    HttpResponse::Ok().finish()
-                },
+    match group_store.find_all(&filter, page).await {
+        Ok(groups) if expand_students => {
+            let group_ids: Vec<i32> = groups.iter().map(|group| group.id).collect();
+            let students = match student_store.find_group_members(&group_ids).await {
+                Ok(students) => students,
                 Err(e) => {
-                    if let Err(rollback_err) = tx.rollback().await {
-                        log::error!("Failed to rollback transaction: {}", rollback_err);
-                    }
-                    log::error!("Failed to delete student: {}", e);
-                    HttpResponse::InternalServerError().body(e.to_string())
+                    log::error!("Failed to fetch students for groups: {}", e);
+                    return HttpResponse::InternalServerError().body(e.to_string());
                 }
-            }
-        }
-    }
-}
+            };
 
-async fn get_groups(mongo_client: web::Data<Collection<Group>>) -> impl Responder {
-    log::debug!("Fetching all groups");
-    match mongo_client.find(None, None).await {
-        Ok(cursor_group) => {
-            match cursor_group.try_collect::<Vec<Group>>().await {
-                Ok(groups) => {
-                    log::info!("Successfully retrieved {} groups", groups.len());
-                    HttpResponse::Ok().json(groups)
-                },
-                Err(e) => {
-                    log::error!("Failed to collect groups: {}", e);
-                    HttpResponse::InternalServerError().body(e.to_string())
+            let mut members: HashMap<i32, Vec<Student>> = HashMap::new();
+            for student in students {
+                if let Some(group_id) = student.group_id {
+                    members.entry(group_id).or_default().push(student);
                 }
             }
+            let expanded: Vec<GroupWithStudents> = groups
+                .into_iter()
+                .map(|group| {
+                    let students = members.remove(&group.id).unwrap_or_default();
+                    GroupWithStudents { student_count: students.len(), students, group }
+                })
+                .collect();
+            log::info!("Successfully retrieved {} groups with students", expanded.len());
+            HttpResponse::Ok().json(expanded)
+        },
+        Ok(groups) => {
+            log::info!("Successfully retrieved {} groups", groups.len());
+            HttpResponse::Ok().json(groups)
         },
         Err(e) => {
             log::error!("Failed to fetch groups: {}", e);
@@ -746,9 +731,20 @@ async fn get_groups(mongo_client: web::Data<Collection<Group>>) -> impl Responde
     }
 }
 
-async fn get_group(id: web::Path<i32>, mongo_client: web::Data<Collection<Group>>) -> impl Responder {
+#[utoipa::path(
+    get,
+    path = "/groups/{id}",
+    tag = "groups",
+    params(("id" = i32, Path, description = "Group id")),
+    responses(
+        (status = 200, description = "The group", body = Group),
+        (status = 404, response = NotFound),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn get_group(id: web::Path<i32>, group_store: web::Data<GroupStore>) -> impl Responder {
     log::debug!("Fetching group with id: {}", id.as_ref());
-    match mongo_client.find_one(doc! { "id": id.as_ref() }, None).await {
+    match group_store.find(*id).await {
         Ok(Some(group)) => {
             log::debug!("Successfully retrieved group: {}", group.name);
             HttpResponse::Ok().json(group)
@@ -764,140 +760,417 @@ async fn get_group(id: web::Path<i32>, mongo_client: web::Data<Collection<Group>
     }
 }
 
-async fn create_group(
-    group: web::Json<GroupInput>,
-    mongo_client: web::Data<Collection<Group>>
+#[utoipa::path(
+    get,
+    path = "/groups/{id}/students",
+    tag = "groups",
+    params(("id" = i32, Path, description = "Group id"), Pagination),
+    responses(
+        (status = 200, description = "The group with its members", body = GroupRoster),
+        (status = 404, response = NotFound),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn get_group_students(
+    id: web::Path<i32>,
+    pagination: web::Query<Pagination>,
+    group_store: web::Data<GroupStore>,
+    student_store: web::Data<StudentStore>
 ) -> impl Responder {
-    log::debug!("Creating new group with name: {}", group.name);
-    match mongo_client.find(None, None).await {
-        Ok(cursor_group) => {
-            match cursor_group.try_collect::<Vec<Group>>().await {
-                Ok(collected_groups) => {
-                    let max_id = collected_groups
-                        .iter()
-                        .map(|doc| doc.id)
-                        .max()
-                        .unwrap_or(-1);
-
-                    let new_group = Group {
-                        id: max_id + 1,
-                        name: group.name.clone(),
-                    };
-
-                    log::debug!("Inserting new group with id: {}", new_group.id);
-                    match mongo_client.insert_one(new_group, None).await {
-                        Ok(_) => {
-                            log::info!("Successfully created new group: {}", group.name);
-                            HttpResponse::Ok().finish()
-                        },
-                        Err(e) => {
-                            log::error!("Failed to insert group: {}", e);
-                            HttpResponse::InternalServerError().body(e.to_string())
-                        }
-                    }
-                },
-                Err(e) => {
-                    log::error!("Failed to collect groups: {}", e);
-                    HttpResponse::InternalServerError().body(e.to_string())
-                }
-            }
+    log::debug!("Fetching roster of group id: {}", id);
+    let group = match group_store.find(*id).await {
+        Ok(Some(group)) => group,
+        Ok(None) => {
+            log::debug!("Group not found with id: {}", id);
+            return HttpResponse::NotFound().finish();
+        },
+        Err(e) => {
+            log::error!("Failed to fetch group: {}", e);
+            return HttpResponse::InternalServerError().body(e.to_string());
+        }
+    };
+
+    let student_count = match student_store.count_group_members(*id).await {
+        Ok(count) => count,
+        Err(e) => {
+            log::error!("Error counting students in group: {}", e);
+            return HttpResponse::InternalServerError().body(e.to_string());
+        }
+    };
+
+    let page = pagination.resolve();
+    let (limit, offset) = match page {
+        Some((page, per_page)) => (Some(i64::from(per_page)), i64::from(page - 1) * i64::from(per_page)),
+        None => (None, 0),
+    };
+    match student_store.find_group_members_page(*id, limit, offset).await {
+        Ok(students) => {
+            log::info!("Successfully retrieved {} students of group id: {}", students.len(), id);
+            HttpResponse::Ok().json(GroupRoster {
+                group,
+                student_count,
+                page: page.map(|(page, _)| page),
+                per_page: page.map(|(_, per_page)| per_page),
+                students,
+            })
         },
         Err(e) => {
-            log::error!("Failed to fetch groups for id generation: {}", e);
+            log::error!("Failed to fetch students of group: {}", e);
             HttpResponse::InternalServerError().body(e.to_string())
         }
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/groups",
+    tag = "groups",
+    request_body = GroupInput,
+    responses(
+        (status = 200, description = "The group was created"),
+        (status = 400, response = BadRequest),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn create_group(
+    group: web::Json<GroupInput>,
+    group_store: web::Data<GroupStore>,
+    student_store: web::Data<StudentStore>
+) -> impl Responder {
+    match add_group(group_store.get_ref(), student_store.get_ref(), &group).await {
+        Ok(_) => HttpResponse::Ok().finish(),
+        Err(response) => response,
+    }
+}
+
+/// Validates and stores a new group under the next free id.
+pub(crate) async fn add_group(
+    group_store: &GroupStore,
+    student_store: &StudentStore,
+    group: &GroupInput
+) -> Result<Group, HttpResponse> {
+    log::debug!("Creating new group with name: {}", group.name);
+    group.validate()?;
+    let new_group = group_store.insert(group).await
+        .map_err(|e| store_error("Failed to create group", e))?;
+    log::info!("Successfully created new group {} with id: {}", group.name, new_group.id);
+    events::emit(student_store, Event::group(EventKind::GroupCreated, new_group.id)).await;
+    Ok(new_group)
+}
+
+#[utoipa::path(
+    put,
+    path = "/groups/{id}",
+    tag = "groups",
+    params(("id" = i32, Path, description = "Group id")),
+    request_body = GroupInput,
+    responses(
+        (status = 200, description = "The group was updated"),
+        (status = 400, response = BadRequest),
+        (status = 404, response = NotFound),
+        (status = 500, response = ServerError)
+    )
+)]
 async fn update_group(
     id: web::Path<i32>,
     group: web::Json<GroupInput>,
-    mongo_client: web::Data<Collection<Group>>
+    group_store: web::Data<GroupStore>,
+    student_store: web::Data<StudentStore>
 ) -> impl Responder {
+    match edit_group(group_store.get_ref(), student_store.get_ref(), *id, &group).await {
+        Ok(()) => HttpResponse::Ok().finish(),
+        Err(response) => response,
+    }
+}
+
+/// Validates and stores the changes to a group. The capacity cannot drop below
+/// the number of its current members.
+pub(crate) async fn edit_group(
+    group_store: &GroupStore,
+    student_store: &StudentStore,
+    id: i32,
+    group: &GroupInput
+) -> Result<(), HttpResponse> {
     log::debug!("Updating group id: {} with new name: {}", id, group.name);
-    match mongo_client.update_one(
-        doc! { "id": id.as_ref() },
-        doc! { "$set": { "name": &group.name } },
-        None
-    ).await {
-        Ok(result) if result.modified_count > 0 => {
-            log::info!("Successfully updated group id: {}", id);
-            HttpResponse::Ok().finish()
-        },
-        Ok(_) => {
-            log::debug!("Group not found with id: {}", id);
-            HttpResponse::NotFound().finish()
-        },
-        Err(e) => {
-            log::error!("Failed to update group: {}", e);
-            HttpResponse::InternalServerError().body(e.to_string())
+    group.validate()?;
+
+    if let Some(Some(max_capacity)) = group.max_capacity {
+        let count = student_store.count_group_members(id).await
+            .map_err(|e| store_error("Failed to count students in group", e))?;
+        if count > i64::from(max_capacity) {
+            log::warn!("Cannot shrink group {} below its {} students", id, count);
+            return Err(HttpResponse::BadRequest()
+                .body(format!("Group already has {} students", count)));
         }
     }
+
+    let updated = group_store.update(id, group).await
+        .map_err(|e| store_error("Failed to update group", e))?;
+    if !updated {
+        log::debug!("Group not found with id: {}", id);
+        return Err(HttpResponse::NotFound().finish());
+    }
+    log::info!("Successfully updated group id: {}", id);
+    events::emit(student_store, Event::group(EventKind::GroupUpdated, id)).await;
+    Ok(())
 }
 
+#[utoipa::path(
+    delete,
+    path = "/groups/{id}",
+    tag = "groups",
+    params(("id" = i32, Path, description = "Group id")),
+    responses(
+        (status = 200, description = "The group was deleted"),
+        (status = 400, description = "The group still has students, classes or assessments", body = String),
+        (status = 404, response = NotFound),
+        (status = 500, response = ServerError)
+    )
+)]
 async fn delete_group(
     id: web::Path<i32>,
-    mongo_client: web::Data<Collection<Group>>,
-    pool: web::Data<PgPool>
+    group_store: web::Data<GroupStore>,
+    student_store: web::Data<StudentStore>
 ) -> impl Responder {
+    match remove_group(group_store.get_ref(), student_store.get_ref(), *id).await {
+        Ok(()) => HttpResponse::Ok().finish(),
+        Err(response) => response,
+    }
+}
+
+/// Deletes a group that no students, classes or assessments refer to.
+pub(crate) async fn remove_group(
+    group_store: &GroupStore,
+    student_store: &StudentStore,
+    id: i32
+) -> Result<(), HttpResponse> {
     log::debug!("Attempting to delete group with id: {}", id);
-    let query = format!("SELECT COUNT(*) FROM {} WHERE group_id = $1", *STUDENT_TABLE_NAME);
-    let count: i64 = match sqlx::query(&query)
-        .bind(id.as_ref())
-        .fetch_one(pool.get_ref())
-        .await
-    {
-        Ok(row) => {
-            let count: i64 = row.get(0);
-            log::debug!("Found {} students in group {}", count, id);
-            count
+    match group_store.find(id).await {
+        Ok(Some(_)) => {},
+        Ok(None) => {
+            log::debug!("Group not found with id: {}", id);
+            return Err(HttpResponse::NotFound().finish());
         },
         Err(e) => {
-            log::error!("Error checking for students in group: {}", e);
-            return HttpResponse::InternalServerError().body(e.to_string())
+            log::error!("Failed to fetch group: {}", e);
+            return Err(HttpResponse::InternalServerError().body(e.to_string()));
+        }
+    }
+
+    let event = student_store.delete_group(id, group_store).await
+        .map_err(|e| store_error("Failed to delete group", e))?;
+    log::info!("Successfully deleted group with id: {}", id);
+    events::publish(event);
+    Ok(())
+}
+
+#[utoipa::path(
+    put,
+    path = "/groups/{id}/leader",
+    tag = "groups",
+    params(("id" = i32, Path, description = "Group id")),
+    request_body = LeaderInput,
+    responses(
+        (status = 200, description = "The student now leads the group"),
+        (status = 400, response = BadRequest),
+        (status = 404, response = NotFound),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn set_group_leader(
+    id: web::Path<i32>,
+    leader: web::Json<LeaderInput>,
+    group_store: web::Data<GroupStore>,
+    student_store: web::Data<StudentStore>
+) -> impl Responder {
+    match assign_group_leader(group_store.get_ref(), student_store.get_ref(), *id, leader.student_id).await {
+        Ok(()) => HttpResponse::Ok().finish(),
+        Err(response) => response,
+    }
+}
+
+/// Makes a member of the group its leader.
+pub(crate) async fn assign_group_leader(
+    group_store: &GroupStore,
+    student_store: &StudentStore,
+    id: i32,
+    student_id: i32
+) -> Result<(), HttpResponse> {
+    log::debug!("Assigning student {} as leader of group {}", student_id, id);
+    match group_store.find(id).await {
+        Ok(Some(_)) => {},
+        Ok(None) => {
+            log::debug!("Group not found with id: {}", id);
+            return Err(HttpResponse::NotFound().finish());
         },
-    };
+        Err(e) => return Err(store_error("Failed to fetch group", e)),
+    }
 
-    if count > 0 {
-        log::warn!("Cannot delete group {} as it contains {} students", id, count);
-        return HttpResponse::BadRequest().body("Cannot delete group with existing students");
+    match student_store.student_group(student_id).await {
+        Ok(Some(group_id)) => {
+            if group_id != Some(id) {
+                log::warn!("Student {} does not belong to group {}", student_id, id);
+                return Err(HttpResponse::BadRequest().body("Leader must be a member of the group"));
+            }
+        },
+        Ok(None) => {
+            log::debug!("Student not found with id: {}", student_id);
+            return Err(HttpResponse::BadRequest().body("Student does not exist"));
+        },
+        Err(e) => return Err(store_error("Failed to fetch student", e)),
     }
 
-    match mongo_client.delete_one(doc! { "id": id.as_ref() }, None).await {
-        Ok(result) if result.deleted_count > 0 => {
-            log::info!("Successfully deleted group with id: {}", id);
-            HttpResponse::Ok().finish()
+    match group_store.set_leader(id, Some(student_id)).await {
+        Ok(true) => {
+            log::info!("Successfully assigned leader {} to group id: {}", student_id, id);
+            events::emit(student_store, Event::group(EventKind::GroupUpdated, id)).await;
+            Ok(())
         },
-        Ok(_) => {
+        Ok(false) => {
             log::debug!("Group not found with id: {}", id);
-            HttpResponse::NotFound().finish()
+            Err(HttpResponse::NotFound().finish())
         },
-        Err(e) => {
-            log::error!("Failed to delete group: {}", e);
-            HttpResponse::InternalServerError().body(e.to_string())
+        Err(e) => Err(store_error("Failed to assign group leader", e)),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/groups/{id}/leader",
+    tag = "groups",
+    params(("id" = i32, Path, description = "Group id")),
+    responses(
+        (status = 200, description = "The group no longer has a leader"),
+        (status = 404, response = NotFound),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn delete_group_leader(
+    id: web::Path<i32>,
+    group_store: web::Data<GroupStore>,
+    student_store: web::Data<StudentStore>
+) -> impl Responder {
+    match clear_group_leader(group_store.get_ref(), student_store.get_ref(), *id).await {
+        Ok(()) => HttpResponse::Ok().finish(),
+        Err(response) => response,
+    }
+}
+
+pub(crate) async fn clear_group_leader(
+    group_store: &GroupStore,
+    student_store: &StudentStore,
+    id: i32
+) -> Result<(), HttpResponse> {
+    log::debug!("Clearing leader of group id: {}", id);
+    match group_store.set_leader(id, None).await {
+        Ok(true) => {
+            log::info!("Successfully cleared leader of group id: {}", id);
+            events::emit(student_store, Event::group(EventKind::GroupUpdated, id)).await;
+            Ok(())
+        },
+        Ok(false) => {
+            log::debug!("Group not found with id: {}", id);
+            Err(HttpResponse::NotFound().finish())
+        },
+        Err(e) => Err(store_error("Failed to clear group leader", e)),
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    get_students, create_student, get_student, update_student, delete_student, get_student_image,
+    get_groups, create_group, get_group, update_group, delete_group, get_group_students,
+    set_group_leader, delete_group_leader,
+))]
+pub(crate) struct ApiDoc;
+
+/// Date from which the unversioned `/api` paths are deprecated, as an RFC 9745 `Deprecation` value.
+const UNVERSIONED_DEPRECATED_SINCE: &str = "@1792368000";
+
+/// Marks every response of the unversioned `/api` alias as deprecated and links
+/// to the same resource under `/api/v1`.
+fn deprecated_alias<S, B>(
+    request: ServiceRequest,
+    service: &S
+) -> impl Future<Output = Result<ServiceResponse<B>, actix_web::Error>>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+{
+    let successor = request.path().replacen("/api", "/api/v1", 1);
+    log::debug!("Deprecated unversioned path requested: {}", request.path());
+    let response = service.call(request);
+    async move {
+        let mut response = response.await?;
+        let headers = response.headers_mut();
+        headers.insert(HeaderName::from_static("deprecation"), HeaderValue::from_static(UNVERSIONED_DEPRECATED_SINCE));
+        if let Ok(link) = HeaderValue::from_str(&format!("<{successor}>; rel=\"successor-version\"")) {
+            headers.insert(header::LINK, link);
         }
+        Ok(response)
     }
 }
 
-pub fn configure_routes(cfg: &mut web::ServiceConfig) {
+/// Routes of version 1 of the API, relative to its mount point. A later version
+/// gets its own table and handlers that share the queries in [`storage`].
+fn configure_v1_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(
-        web::scope("/api")
-            .service(
-                web::scope("/students")
-                    .route("", web::get().to(get_students))
-                    .route("", web::post().to(create_student))
-                    .route("/{id}", web::get().to(get_student))
-                    .route("/{id}", web::put().to(update_student))
-                    .route("/{id}", web::delete().to(delete_student))
-                    .route("/image/{id}", web::get().to(get_student_image))
-            )
-            .service(
-                web::scope("/groups")
-                    .route("", web::get().to(get_groups))
-                    .route("", web::post().to(create_group))
-                    .route("/{id}", web::get().to(get_group))
-                    .route("/{id}", web::put().to(update_group))
-                    .route("/{id}", web::delete().to(delete_group))
-            )
-    );
-}
\ No newline at end of file
+        web::scope("/students")
+            .route("", web::get().to(get_students))
+            .route("", web::post().to(create_student))
+            .route("/import", web::post().to(import::import_students))
+            .route("/batch", web::post().to(batch::execute_batch))
+            .route("/duplicates", web::get().to(duplicates::get_duplicates))
+            .route("/{id}", web::get().to(get_student))
+            .route("/{id}", web::put().to(update_student))
+            .route("/{id}", web::delete().to(delete_student))
+            .route("/image/{id}", web::get().to(get_student_image))
+            .route("/{id}/schedule.ics", web::get().to(calendar::get_student_calendar))
+            .route("/{id}/transcript", web::get().to(grades::get_student_transcript))
+            .route("/{id}/status", web::get().to(lifecycle::get_student_status))
+            .route("/{id}/status", web::post().to(lifecycle::change_student_status))
+            .route("/{id}/merge", web::post().to(duplicates::merge_students))
+    )
+    .service(
+        web::scope("/groups")
+            .route("", web::get().to(get_groups))
+            .route("", web::post().to(create_group))
+            .route("/{id}", web::get().to(get_group))
+            .route("/{id}", web::put().to(update_group))
+            .route("/{id}", web::delete().to(delete_group))
+            .route("/{id}/students", web::get().to(get_group_students))
+            .route("/{id}/schedule", web::get().to(schedule::get_group_schedule))
+            .route("/{id}/schedule.ics", web::get().to(calendar::get_group_calendar))
+            .route("/{id}/gradebook", web::get().to(grades::get_group_gradebook))
+            .route("/{id}/leader", web::put().to(set_group_leader))
+            .route("/{id}/leader", web::delete().to(delete_group_leader))
+    )
+    .service(
+        web::scope("/export")
+            .route("", web::get().to(export::export_students))
+            .route("/archive", web::get().to(export::export_archive))
+    )
+    .configure(schedule::configure_routes)
+    .configure(attendance::configure_routes)
+    .configure(grades::configure_routes)
+    .configure(audit::configure_routes)
+    .configure(events::configure_routes)
+    .configure(webhooks::configure_routes);
+}
+
+/// The spec under the unversioned alias, for clients that have not moved to `/api/v1` yet.
+async fn get_unversioned_openapi() -> HttpResponse {
+    HttpResponse::Ok().json(openapi::openapi())
+}
+
+pub fn configure_routes(cfg: &mut web::ServiceConfig) {
+    // Registered ahead of the `/api` scopes, which would otherwise swallow the spec URLs.
+    cfg.service(SwaggerUi::new("/swagger-ui/{_:.*}").url("/api/v1/openapi.json", openapi::openapi()));
+    cfg.service(web::resource("/api/openapi.json").wrap_fn(deprecated_alias).get(get_unversioned_openapi));
+    cfg.configure(graphql::configure_routes);
+    cfg.service(web::scope("/api/v1").configure(configure_v1_routes));
+    // The unversioned paths predate `/api/v1` and keep serving version 1 until clients have moved on.
+    cfg.service(web::scope("/api").wrap_fn(deprecated_alias).configure(configure_v1_routes));
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,531 @@
+//! Every student and group route, served from the in-memory stores.
+
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::{test, App};
+use serde_json::{json, Value};
+
+use std::io::Cursor;
+use std::sync::Arc;
+
+use super::*;
+use crate::groups::GroupRepository;
+use crate::memory::{InMemoryGroups, InMemoryStudents};
+use crate::students::StudentRepository;
+
+const BOUNDARY: &str = "test-boundary";
+
+/// MIME type and bytes of an uploaded photo.
+type Photo<'a> = Option<(&'a str, &'a [u8])>;
+
+fn group(id: i32, name: &str, faculty: &str, max_capacity: Option<i32>) -> Group {
+    Group {
+        id,
+        name: name.to_string(),
+        leader_id: None,
+        faculty: Some(faculty.to_string()),
+        specialty_code: Some("121".to_string()),
+        admission_year: Some(2021 + id),
+        study_form: Some(StudyForm::FullTime),
+        max_capacity,
+    }
+}
+
+/// Groups 0 and 1 of ФІОТ, the first with room for two students, and group 2 of ФПМ.
+fn stores() -> (Arc<InMemoryStudents>, Arc<InMemoryGroups>) {
+    let groups = InMemoryGroups::new([
+        group(0, "ІП-11", "ФІОТ", Some(2)),
+        group(1, "ІП-12", "ФІОТ", None),
+        group(2, "КМ-11", "ФПМ", None),
+    ]);
+    (Arc::new(InMemoryStudents::default()), Arc::new(groups))
+}
+
+/// Serves every route from the given in-memory stores.
+macro_rules! app {
+    ($students:expr, $groups:expr) => {{
+        let student_store: Arc<StudentStore> = $students.clone();
+        let group_store: Arc<GroupStore> = $groups.clone();
+        test::init_service(
+            App::new()
+                .app_data(web::Data::from(student_store))
+                .app_data(web::Data::from(group_store))
+                .configure(configure_routes)
+        ).await
+    }};
+}
+
+fn png() -> Vec<u8> {
+    let mut bytes = Vec::new();
+    image::DynamicImage::new_rgb8(1, 1)
+        .write_to(&mut Cursor::new(&mut bytes), image::ImageFormat::Png)
+        .expect("a PNG can be encoded");
+    bytes
+}
+
+/// A multipart body with the given text fields and an optional `studentPhoto`.
+fn multipart(fields: &[(&str, &str)], photo: Photo) -> Vec<u8> {
+    let mut body = Vec::new();
+    for (name, value) in fields {
+        body.extend_from_slice(format!(
+            "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n{value}\r\n"
+        ).as_bytes());
+    }
+    if let Some((content_type, data)) = photo {
+        body.extend_from_slice(format!(
+            "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"studentPhoto\"; filename=\"photo\"\r\n\
+             Content-Type: {content_type}\r\n\r\n"
+        ).as_bytes());
+        body.extend_from_slice(data);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{BOUNDARY}--\r\n").as_bytes());
+    body
+}
+
+fn student_request(request: test::TestRequest, fields: &[(&str, &str)], photo: Photo) -> test::TestRequest {
+    request
+        .insert_header(("Content-Type", format!("multipart/form-data; boundary={BOUNDARY}")))
+        .set_payload(multipart(fields, photo))
+}
+
+async fn status_and_body<S, R>(app: &S, request: R) -> (StatusCode, String)
+where
+    S: Service<R, Response = ServiceResponse, Error = actix_web::Error>,
+{
+    let response = test::call_service(app, request).await;
+    let status = response.status();
+    let body = test::read_body(response).await;
+    (status, String::from_utf8_lossy(&body).into_owned())
+}
+
+fn add(students: &InMemoryStudents, name: &str, surname: &str, group_id: i32) -> i32 {
+    let form = StudentForm {
+        name: name.to_string(),
+        surname: surname.to_string(),
+        group_id: Some(group_id),
+        ..Default::default()
+    };
+    students.seed(&form)
+}
+
+fn event_kinds(students: &InMemoryStudents) -> Vec<&'static str> {
+    students.events().iter().map(|event| event.kind.as_str()).collect()
+}
+
+#[actix_web::test]
+async fn students_are_listed_searched_and_fetched() {
+    let (students, groups) = stores();
+    let id = add(&students, "Олена", "Шевченко", 0);
+    add(&students, "Петро", "Іваненко", 1);
+    let app = app!(students, groups);
+
+    let request = test::TestRequest::get().uri("/api/v1/students").to_request();
+    let all: Vec<Value> = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(all.len(), 2);
+
+    let request = test::TestRequest::get().uri("/api/v1/students?q=shevchenko").to_request();
+    let found: Vec<Value> = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0]["surname"], "Шевченко");
+
+    let request = test::TestRequest::get().uri("/api/v1/students?status=expelled").to_request();
+    let expelled: Vec<Value> = test::call_and_read_body_json(&app, request).await;
+    assert!(expelled.is_empty());
+
+    let request = test::TestRequest::get().uri(&format!("/api/v1/students/{id}")).to_request();
+    let student: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(student["name_latin"], "Olena");
+    assert_eq!(student["group_id"], 0);
+
+    let request = test::TestRequest::get().uri("/api/v1/students/99").to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn student_is_created_with_a_photo() {
+    let (students, groups) = stores();
+    let app = app!(students, groups);
+    let photo = png();
+
+    let fields = [
+        ("studentName", "Олена"),
+        ("studentSurname", "Шевченко"),
+        ("studentGroup", "1"),
+        ("studentEmail", " Olena@Example.com "),
+    ];
+    let request = student_request(test::TestRequest::post().uri("/api/v1/students"), &fields, Some(("image/png", &photo)));
+    let request = request.to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+
+    let student = students.get(0).expect("the student was stored");
+    assert_eq!(student.group_id, Some(1));
+    assert_eq!(student.profile.email.as_deref(), Some("olena@example.com"));
+    assert_eq!(event_kinds(&students), ["student.created"]);
+
+    let response = test::call_service(&app, test::TestRequest::get().uri("/api/v1/students/image/0").to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers().get("content-type").unwrap(), "image/png");
+    assert_eq!(test::read_body(response).await, photo);
+}
+
+#[actix_web::test]
+async fn invalid_students_are_rejected() {
+    let (students, groups) = stores();
+    add(&students, "Олена", "Шевченко", 0);
+    add(&students, "Петро", "Іваненко", 0);
+    let app = app!(students, groups);
+
+    let cases: [(&[(&str, &str)], &str); 4] = [
+        (&[("studentName", "Іван"), ("studentSurname", "Коваль")], "A new student must be assigned to a group"),
+        (&[("studentName", "Іван"), ("studentGroup", "0")], "Group is at full capacity (2 students)"),
+        (&[("studentName", "Іван"), ("studentGroup", "1"), ("studentEmail", "nope")], "Email address is not valid"),
+        (&[("studentName", "Іван"), ("studentGroup", "1"), ("studentAge", "19")], "Unrecognized key: studentAge"),
+    ];
+    for (fields, message) in cases {
+        let request = student_request(test::TestRequest::post().uri("/api/v1/students"), fields, None).to_request();
+        let (status, body) = status_and_body(&app, request).await;
+        assert_eq!((status, body.as_str()), (StatusCode::BAD_REQUEST, message), "{fields:?}");
+    }
+
+    let photos: [(&str, &[u8], &str); 2] = [
+        ("image/png", b"not a png", "Invalid image data"),
+        ("image/gif", b"GIF89a", "Invalid image format. Only JPEG and PNG are supported."),
+    ];
+    for (content_type, data, message) in photos {
+        let fields = [("studentName", "Іван"), ("studentGroup", "1")];
+        let request = student_request(test::TestRequest::post().uri("/api/v1/students"), &fields, Some((content_type, data)));
+        let (status, body) = status_and_body(&app, request.to_request()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{content_type}");
+        assert!(body.starts_with(message), "unexpected error for {content_type}: {body}");
+    }
+    assert_eq!(students.find_all(None, None).await.unwrap().len(), 2);
+    assert!(students.events().is_empty());
+}
+
+#[actix_web::test]
+async fn student_update_keeps_the_photo_and_releases_leadership() {
+    let (students, groups) = stores();
+    let app = app!(students, groups);
+    let photo = png();
+    let request = student_request(
+        test::TestRequest::post().uri("/api/v1/students"),
+        &[("studentName", "Олена"), ("studentSurname", "Шевченко"), ("studentGroup", "0")],
+        Some(("image/png", &photo)),
+    ).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    groups.set_leader(0, Some(0)).await.unwrap();
+
+    let request = student_request(
+        test::TestRequest::put().uri("/api/v1/students/0"),
+        &[("studentName", "Олена"), ("studentSurname", "Коваль"), ("studentGroup", "2")],
+        None,
+    ).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+
+    let student = students.get(0).unwrap();
+    assert_eq!((student.surname.as_str(), student.group_id), ("Коваль", Some(2)));
+    assert_eq!(groups.get(0).unwrap().leader_id, None);
+    assert_eq!(students.load_photo(0).await.unwrap().map(|(data, _)| data), Some(photo));
+    assert_eq!(students.events().last().unwrap().groups, [0, 2]);
+}
+
+#[actix_web::test]
+async fn student_update_keeps_the_fields_it_leaves_out() {
+    let (students, groups) = stores();
+    let app = app!(students, groups);
+
+    let fields = [
+        ("studentName", "Олена"),
+        ("studentSurname", "Шевченко"),
+        ("studentGroup", "1"),
+        ("studentEmail", "olena@example.com"),
+        ("studentPhone", "+380441234567"),
+    ];
+    let request = student_request(test::TestRequest::post().uri("/api/v1/students"), &fields, None).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+
+    let fields = [("studentName", "Олена"), ("studentSurname", "Коваль"), ("studentGroup", "1")];
+    let request = student_request(test::TestRequest::put().uri("/api/v1/students/0"), &fields, None).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    let profile = students.get(0).unwrap().profile;
+    assert_eq!(profile.email.as_deref(), Some("olena@example.com"));
+    assert_eq!(profile.phone.as_deref(), Some("+380441234567"));
+
+    let fields = [("studentName", "Олена"), ("studentSurname", "Коваль"), ("studentGroup", "1"), ("studentEmail", "")];
+    let request = student_request(test::TestRequest::put().uri("/api/v1/students/0"), &fields, None).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    let profile = students.get(0).unwrap().profile;
+    assert_eq!((profile.email, profile.phone.as_deref()), (None, Some("+380441234567")));
+
+    let fields = [("studentName", "Олена"), ("studentSurname", "Шевченко")];
+    let request = student_request(test::TestRequest::put().uri("/api/v1/students/0"), &fields, None).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    assert_eq!(students.get(0).unwrap().group_id, Some(1));
+    assert_eq!(students.events().last().unwrap().groups, [1]);
+}
+
+#[actix_web::test]
+async fn student_is_deleted() {
+    let (students, groups) = stores();
+    let id = add(&students, "Олена", "Шевченко", 1);
+    groups.set_leader(1, Some(id)).await.unwrap();
+    let app = app!(students, groups);
+
+    let request = test::TestRequest::delete().uri(&format!("/api/v1/students/{id}")).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    assert!(students.get(id).is_none());
+    assert_eq!(groups.get(1).unwrap().leader_id, None);
+    assert_eq!(event_kinds(&students), ["student.deleted"]);
+
+    let request = test::TestRequest::get().uri(&format!("/api/v1/students/image/{id}")).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn missing_students_are_not_updated_or_deleted() {
+    let (students, groups) = stores();
+    let app = app!(students, groups);
+
+    let fields = [("studentName", "Олена"), ("studentSurname", "Шевченко"), ("studentGroup", "1")];
+    let request = student_request(test::TestRequest::put().uri("/api/v1/students/99"), &fields, None).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::NOT_FOUND);
+
+    let request = test::TestRequest::delete().uri("/api/v1/students/99").to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::NOT_FOUND);
+    assert!(students.events().is_empty());
+}
+
+#[actix_web::test]
+async fn groups_are_filtered_paged_and_expanded() {
+    let (students, groups) = stores();
+    add(&students, "Олена", "Шевченко", 1);
+    let app = app!(students, groups);
+
+    let names = |groups: Vec<Value>| {
+        groups.iter().map(|group| group["name"].as_str().unwrap().to_string()).collect::<Vec<_>>()
+    };
+
+    let request = test::TestRequest::get().uri("/api/v1/groups?faculty=%D0%A4%D0%86%D0%9E%D0%A2").to_request();
+    assert_eq!(names(test::call_and_read_body_json(&app, request).await), ["ІП-11", "ІП-12"]);
+
+    let request = test::TestRequest::get().uri("/api/v1/groups?page=2&per_page=2").to_request();
+    assert_eq!(names(test::call_and_read_body_json(&app, request).await), ["КМ-11"]);
+
+    let request = test::TestRequest::get().uri("/api/v1/groups?expand=students").to_request();
+    let expanded: Vec<Value> = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(expanded[0]["student_count"], 0);
+    assert_eq!(expanded[1]["student_count"], 1);
+    assert_eq!(expanded[1]["students"][0]["surname"], "Шевченко");
+
+    let request = test::TestRequest::get().uri("/api/v1/groups?expand=teachers").to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn group_is_fetched_with_its_roster() {
+    let (students, groups) = stores();
+    add(&students, "Олена", "Шевченко", 1);
+    add(&students, "Петро", "Іваненко", 1);
+    let app = app!(students, groups);
+
+    let request = test::TestRequest::get().uri("/api/v1/groups/1").to_request();
+    let group: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(group["name"], "ІП-12");
+
+    let request = test::TestRequest::get().uri("/api/v1/groups/1/students?page=2&per_page=1").to_request();
+    let roster: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(roster["student_count"], 2);
+    assert_eq!(roster["page"], 2);
+    assert_eq!(roster["students"].as_array().unwrap().len(), 1);
+    assert_eq!(roster["students"][0]["name"], "Петро");
+
+    for uri in ["/api/v1/groups/9", "/api/v1/groups/9/students"] {
+        let request = test::TestRequest::get().uri(uri).to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), StatusCode::NOT_FOUND, "{uri}");
+    }
+}
+
+#[actix_web::test]
+async fn groups_are_created_and_updated() {
+    let (students, groups) = stores();
+    add(&students, "Олена", "Шевченко", 1);
+    add(&students, "Петро", "Іваненко", 1);
+    let app = app!(students, groups);
+
+    let request = test::TestRequest::post()
+        .uri("/api/v1/groups")
+        .set_json(json!({ "name": "ІП-13", "study_form": "part_time" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    assert_eq!(groups.get(3).unwrap().study_form, Some(StudyForm::PartTime));
+
+    let request = test::TestRequest::post().uri("/api/v1/groups").set_json(json!({ "name": " " })).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+
+    let request = test::TestRequest::put()
+        .uri("/api/v1/groups/1")
+        .set_json(json!({ "name": "ІП-12", "max_capacity": 1 }))
+        .to_request();
+    let (status, body) = status_and_body(&app, request).await;
+    assert_eq!((status, body.as_str()), (StatusCode::BAD_REQUEST, "Group already has 2 students"));
+
+    let request = test::TestRequest::put()
+        .uri("/api/v1/groups/1")
+        .set_json(json!({ "name": "ІП-12м", "max_capacity": 30 }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    assert_eq!(groups.get(1).unwrap().name, "ІП-12м");
+
+    let request = test::TestRequest::put().uri("/api/v1/groups/9").set_json(json!({ "name": "ІП-19" })).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(event_kinds(&students), ["group.created", "group.updated"]);
+}
+
+#[actix_web::test]
+async fn group_update_keeps_the_fields_it_leaves_out() {
+    let (students, groups) = stores();
+    let app = app!(students, groups);
+
+    let request = test::TestRequest::put().uri("/api/v1/groups/0").set_json(json!({ "name": "ІП-11м" })).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    let renamed = groups.get(0).unwrap();
+    assert_eq!(renamed.name, "ІП-11м");
+    assert_eq!((renamed.faculty.as_deref(), renamed.max_capacity), (Some("ФІОТ"), Some(2)));
+
+    let request = test::TestRequest::put()
+        .uri("/api/v1/groups/0")
+        .set_json(json!({ "name": "ІП-11м", "faculty": null, "admission_year": 2022 }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    let changed = groups.get(0).unwrap();
+    assert_eq!((changed.faculty, changed.admission_year), (None, Some(2022)));
+    assert_eq!((changed.specialty_code.as_deref(), changed.max_capacity), (Some("121"), Some(2)));
+}
+
+#[actix_web::test]
+async fn only_groups_without_students_are_deleted() {
+    let (students, groups) = stores();
+    add(&students, "Олена", "Шевченко", 1);
+    let app = app!(students, groups);
+
+    let (status, body) = status_and_body(&app, test::TestRequest::delete().uri("/api/v1/groups/1").to_request()).await;
+    assert_eq!((status, body.as_str()), (StatusCode::BAD_REQUEST, "Cannot delete group with existing students"));
+    assert!(groups.get(1).is_some());
+
+    let request = test::TestRequest::delete().uri("/api/v1/groups/2").to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    assert!(groups.get(2).is_none());
+
+    let request = test::TestRequest::delete().uri("/api/v1/groups/2").to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(event_kinds(&students), ["group.deleted"]);
+}
+
+#[actix_web::test]
+async fn leader_must_be_a_member() {
+    let (students, groups) = stores();
+    let member = add(&students, "Олена", "Шевченко", 1);
+    let outsider = add(&students, "Петро", "Іваненко", 2);
+    let app = app!(students, groups);
+
+    let cases = [
+        (1, outsider, StatusCode::BAD_REQUEST),
+        (1, 99, StatusCode::BAD_REQUEST),
+        (9, member, StatusCode::NOT_FOUND),
+        (1, member, StatusCode::OK),
+    ];
+    for (group_id, student_id, expected) in cases {
+        let request = test::TestRequest::put()
+            .uri(&format!("/api/v1/groups/{group_id}/leader"))
+            .set_json(json!({ "student_id": student_id }))
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), expected, "student {student_id} in group {group_id}");
+    }
+    assert_eq!(groups.get(1).unwrap().leader_id, Some(member));
+
+    let request = test::TestRequest::delete().uri("/api/v1/groups/1/leader").to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    assert_eq!(groups.get(1).unwrap().leader_id, None);
+
+    let request = test::TestRequest::delete().uri("/api/v1/groups/9/leader").to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn leader_from_another_group_is_refused() {
+    let (students, groups) = stores();
+    let leader = add(&students, "Олена", "Шевченко", 1);
+    let outsider = add(&students, "Петро", "Іваненко", 2);
+    groups.set_leader(1, Some(leader)).await.unwrap();
+    let app = app!(students, groups);
+
+    let request = test::TestRequest::put()
+        .uri("/api/v1/groups/1/leader")
+        .set_json(json!({ "student_id": outsider }))
+        .to_request();
+    let (status, body) = status_and_body(&app, request).await;
+    assert_eq!((status, body.as_str()), (StatusCode::BAD_REQUEST, "Leader must be a member of the group"));
+    assert_eq!(groups.get(1).unwrap().leader_id, Some(leader));
+    assert!(students.events().is_empty());
+}
+
+#[actix_web::test]
+async fn unversioned_alias_serves_the_same_data() {
+    let (students, groups) = stores();
+    add(&students, "Олена", "Шевченко", 1);
+    let app = app!(students, groups);
+
+    let response = test::call_service(&app, test::TestRequest::get().uri("/api/groups/1/students").to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers().get("link").unwrap(), "</api/v1/groups/1/students>; rel=\"successor-version\"");
+    let roster: Value = test::read_body_json(response).await;
+    assert_eq!(roster["students"][0]["surname"], "Шевченко");
+}
+
+#[actix_web::test]
+async fn graphql_reads_students_from_the_store() {
+    let (students, groups) = stores();
+    let app = app!(students, groups);
+    let fields = [("studentName", "Олена"), ("studentSurname", "Шевченко"), ("studentGroup", "1")];
+    let request = student_request(test::TestRequest::post().uri("/api/v1/students"), &fields, Some(("image/png", &png())));
+    assert_eq!(test::call_service(&app, request.to_request()).await.status(), StatusCode::OK);
+    add(&students, "Петро", "Іваненко", 1);
+
+    let query = r#"{
+        students(search: "shevchenko") { surname photoUrl group { name students { surname } } }
+        student(id: "1") { nameLatin photoUrl }
+    }"#;
+    let request = test::TestRequest::post().uri("/graphql").set_json(json!({ "query": query })).to_request();
+    let response: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(response["errors"], Value::Null, "{response}");
+    assert_eq!(response["data"], json!({
+        "students": [{
+            "surname": "Шевченко",
+            "photoUrl": "/api/v1/students/image/0",
+            "group": { "name": "ІП-12", "students": [{ "surname": "Шевченко" }, { "surname": "Іваненко" }] },
+        }],
+        "student": { "nameLatin": "Petro", "photoUrl": null },
+    }));
+}
+
+#[actix_web::test]
+async fn graphql_refuses_queries_nested_too_deep() {
+    let (students, groups) = stores();
+    add(&students, "Олена", "Шевченко", 1);
+    let app = app!(students, groups);
+
+    // Every level adds two to the depth of `{ groups { name } }`.
+    let query = |levels: usize| {
+        let nested = (0..levels).fold("name".to_string(), |inner, _| format!("students {{ group {{ {inner} }} }}"));
+        json!({ "query": format!("{{ groups {{ {nested} }} }}") })
+    };
+    let request = test::TestRequest::post().uri("/graphql").set_json(query(6)).to_request();
+    let response: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(response["errors"], Value::Null, "{response}");
+
+    let request = test::TestRequest::post().uri("/graphql").set_json(query(7)).to_request();
+    let response: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(response["errors"][0]["message"], "Query is nested too deep.", "{response}");
+    assert_eq!(response["data"], Value::Null);
+}
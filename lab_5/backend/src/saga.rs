@@ -0,0 +1,241 @@
+//! Operations that change Postgres together with the group store or image files.
+//!
+//! A saga is recorded in Postgres before, or in the same transaction as, the
+//! Postgres part of the operation. Once that part is over, the saga is resolved:
+//! what is left to do outside Postgres is done if the change was committed, and
+//! undone if it was not. The decision is taken from the data itself, so the
+//! reconciler can resolve a saga whose request died halfway the same way.
+
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use sqlx::{PgConnection, PgExecutor, PgPool};
+
+use std::io::ErrorKind;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::groups::GroupStore;
+use crate::storage::student_group;
+
+/// Pending sagas untouched for this long are considered abandoned by their
+/// request and resolved by the reconciler.
+const ABANDONED_AFTER: Duration = Duration::from_secs(300);
+const RECONCILE_INTERVAL: Duration = Duration::from_secs(60);
+const RECONCILE_BATCH_SIZE: i64 = 50;
+/// A group deletion the group store still refuses after this many attempts is
+/// given up, so that the group takes new members again.
+const DELETE_GROUP_ATTEMPTS: i32 = 5;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(crate) enum Saga {
+    /// Deleting a group from the group store. While the saga is pending, Postgres rejects
+    /// students, classes and assessments of the group.
+    DeleteGroup { group_id: i32 },
+    /// A photo written for a student and the one it replaces. Whichever of the
+    /// files no student refers to once the change is over is deleted.
+    ReplaceImage { new_path: Option<String>, old_path: Option<String> },
+    /// A student left their group or was deleted, so no other group may keep
+    /// them as its leader.
+    ReleaseLeader { student_id: i32 },
+    /// The groups led by a merged duplicate are handed over to the kept student.
+    HandOverLeadership { from: i32, to: i32 },
+}
+
+impl Saga {
+    fn kind(&self) -> &'static str {
+        match self {
+            Saga::DeleteGroup { .. } => "delete_group",
+            Saga::ReplaceImage { .. } => "replace_image",
+            Saga::ReleaseLeader { .. } => "release_leader",
+            Saga::HandOverLeadership { .. } => "hand_over_leadership",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Outcome {
+    /// The operation took effect everywhere.
+    Completed,
+    /// The operation was rolled back in Postgres, and undone everywhere else.
+    Compensated,
+}
+
+impl Outcome {
+    fn as_str(self) -> &'static str {
+        match self {
+            Outcome::Completed => "completed",
+            Outcome::Compensated => "compensated",
+        }
+    }
+}
+
+/// A recorded saga that is yet to be resolved.
+#[must_use = "a saga stays pending until it is finished"]
+pub(crate) struct PendingSaga {
+    id: i32,
+    saga: Saga,
+    /// Failed attempts to resolve the saga so far.
+    attempts: i32,
+}
+
+/// Records a saga. In a transaction, the saga only exists if the transaction commits.
+pub(crate) async fn begin<'e, E: PgExecutor<'e>>(executor: E, saga: Saga) -> Result<PendingSaga, sqlx::Error> {
+    let id = sqlx::query_scalar("INSERT INTO sagas (kind, payload) VALUES ($1, $2) RETURNING id")
+        .bind(saga.kind())
+        .bind(Json(&saga))
+        .fetch_one(executor)
+        .await?;
+    Ok(PendingSaga { id, saga, attempts: 0 })
+}
+
+/// Records several sagas on one connection, such as that of a transaction.
+pub(crate) async fn begin_all(conn: &mut PgConnection, sagas: Vec<Saga>) -> Result<Vec<PendingSaga>, sqlx::Error> {
+    let mut pending = Vec::with_capacity(sagas.len());
+    for saga in sagas {
+        pending.push(begin(&mut *conn, saga).await?);
+    }
+    Ok(pending)
+}
+
+/// Resolves sagas whose outcome the caller does not wait for. Failures are
+/// logged, and the reconciler retries them.
+pub(crate) async fn finish_all(sagas: Vec<PendingSaga>, pool: &PgPool, group_store: &GroupStore) {
+    for saga in sagas {
+        saga.finish(pool, group_store).await.ok();
+    }
+}
+
+impl PendingSaga {
+    /// Resolves the saga. A saga that fails to resolve stays pending and is
+    /// retried by the reconciler.
+    pub(crate) async fn finish(self, pool: &PgPool, group_store: &GroupStore) -> Result<(), String> {
+        let result = resolve(pool, group_store, &self.saga, self.attempts + 1).await;
+        let query = sqlx::query(
+            "UPDATE sagas
+             SET status = COALESCE($2, status), attempts = attempts + 1, last_error = $3, updated_at = NOW()
+             WHERE id = $1"
+        )
+            .bind(self.id)
+            .bind(result.as_ref().ok().map(|outcome| outcome.as_str()))
+            .bind(result.as_ref().err());
+        if let Err(e) = query.execute(pool).await {
+            log::error!("Failed to record the outcome of saga {}: {}", self.id, e);
+        }
+        match result {
+            Ok(outcome) => {
+                log::debug!("Saga {} ({}) {}", self.id, self.saga.kind(), outcome.as_str());
+                Ok(())
+            },
+            Err(e) => {
+                log::error!("Saga {} ({}) failed, it will be retried: {}", self.id, self.saga.kind(), e);
+                Err(e)
+            }
+        }
+    }
+}
+
+async fn image_in_use(pool: &PgPool, path: &str) -> Result<bool, String> {
+    // Only filesystem storage keeps photos in files.
+    sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM students_fs WHERE image_path = $1)")
+        .bind(path)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Deletes the file unless a student refers to it. Returns whether it is in use.
+async fn remove_unless_in_use(pool: &PgPool, path: &str) -> Result<bool, String> {
+    if image_in_use(pool, path).await? {
+        return Ok(true);
+    }
+    match tokio::fs::remove_file(path).await {
+        Ok(()) => log::info!("Deleted unused image file: {}", path),
+        Err(e) if e.kind() == ErrorKind::NotFound => {},
+        Err(e) => return Err(format!("Failed to delete image file {path}: {e}")),
+    }
+    Ok(false)
+}
+
+/// Resolves a saga on its `attempt`th try, counting from one.
+async fn resolve(pool: &PgPool, group_store: &GroupStore, saga: &Saga, attempt: i32) -> Result<Outcome, String> {
+    match saga {
+        // Postgres was checked for references before the saga was recorded,
+        // and none could be added since, so the deletion moves forward until
+        // the group store has refused it too often. Nothing was changed in
+        // Postgres, so giving up leaves nothing to undo.
+        Saga::DeleteGroup { group_id } => match group_store.delete(*group_id).await {
+            Ok(()) => Ok(Outcome::Completed),
+            Err(e) if attempt >= DELETE_GROUP_ATTEMPTS => {
+                log::error!("Gave up deleting group {} after {} attempts: {}", group_id, attempt, e);
+                Ok(Outcome::Compensated)
+            },
+            Err(e) => Err(e.to_string()),
+        },
+        Saga::ReplaceImage { new_path, old_path } => {
+            let new_in_use = match new_path {
+                Some(path) => Some(remove_unless_in_use(pool, path).await?),
+                None => None,
+            };
+            let old_in_use = match old_path {
+                Some(path) => remove_unless_in_use(pool, path).await?,
+                None => false,
+            };
+            match new_in_use.unwrap_or(!old_in_use) {
+                true => Ok(Outcome::Completed),
+                false => Ok(Outcome::Compensated),
+            }
+        },
+        Saga::ReleaseLeader { student_id } => {
+            let group_id = student_group(pool, *student_id).await.map_err(|e| e.to_string())?.flatten();
+            let cleared = group_store
+                .clear_leader_for_student(*student_id, group_id)
+                .await
+                .map_err(|e| e.to_string())?;
+            if cleared > 0 {
+                log::info!("Cleared leader of {} group(s) for student id: {}", cleared, student_id);
+            }
+            Ok(Outcome::Completed)
+        },
+        Saga::HandOverLeadership { from, to } => {
+            group_store.hand_over_leadership(*from, *to).await.map_err(|e| e.to_string())?;
+            Ok(Outcome::Completed)
+        },
+    }
+}
+
+async fn claim_abandoned_sagas(pool: &PgPool) -> Result<Vec<PendingSaga>, sqlx::Error> {
+    let claimed = sqlx::query_as::<_, (i32, Json<Saga>, i32)>(
+        "UPDATE sagas SET updated_at = NOW()
+         WHERE id IN (
+             SELECT id FROM sagas
+             WHERE status = 'pending' AND updated_at < NOW() - $1 * INTERVAL '1 second'
+             ORDER BY id
+             LIMIT $2
+             FOR UPDATE SKIP LOCKED
+         )
+         RETURNING id, payload, attempts"
+    )
+        .bind(ABANDONED_AFTER.as_secs_f64())
+        .bind(RECONCILE_BATCH_SIZE)
+        .fetch_all(pool)
+        .await?;
+    Ok(claimed.into_iter().map(|(id, Json(saga), attempts)| PendingSaga { id, saga, attempts }).collect())
+}
+
+/// Resolves sagas left pending by requests that died halfway, for as long as
+/// the server runs.
+pub(crate) async fn reconcile(pool: PgPool, group_store: Arc<GroupStore>) {
+    loop {
+        match claim_abandoned_sagas(&pool).await {
+            Ok(sagas) => {
+                for saga in &sagas {
+                    log::warn!("Resuming abandoned saga {} ({})", saga.id, saga.saga.kind());
+                }
+                finish_all(sagas, &pool, group_store.as_ref()).await;
+            },
+            Err(e) => log::error!("Failed to claim abandoned sagas: {}", e),
+        }
+        actix_web::rt::time::sleep(RECONCILE_INTERVAL).await;
+    }
+}
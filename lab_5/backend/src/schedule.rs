@@ -0,0 +1,935 @@
+use actix_web::{web, HttpResponse, Responder};
+use chrono::{NaiveDate, NaiveTime};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, PgPool};
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+use crate::calendar::SEMESTER;
+use crate::groups::GroupStore;
+use crate::openapi::{BadRequest, NotFound, ServerError};
+use crate::routes::database_error;
+
+#[derive(Serialize, sqlx::FromRow, ToSchema)]
+pub(crate) struct Subject {
+    pub(crate) id: i32,
+    pub(crate) name: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct SubjectInput {
+    name: String,
+}
+
+#[derive(Serialize, sqlx::FromRow, ToSchema)]
+struct Teacher {
+    id: i32,
+    name: String,
+    surname: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct TeacherInput {
+    name: String,
+    surname: String,
+}
+
+#[derive(Serialize, sqlx::FromRow, ToSchema)]
+struct Room {
+    id: i32,
+    name: String,
+    capacity: Option<i32>,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct RoomInput {
+    name: String,
+    capacity: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum WeekParity {
+    Odd,
+    Even,
+    Both,
+}
+
+impl WeekParity {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            WeekParity::Odd => "odd",
+            WeekParity::Even => "even",
+            WeekParity::Both => "both",
+        }
+    }
+
+    /// Parity of a 1-based week of the semester; the first week is odd.
+    pub(crate) fn of_week(week: u32) -> Self {
+        match week % 2 {
+            1 => WeekParity::Odd,
+            _ => WeekParity::Even,
+        }
+    }
+}
+
+#[derive(Serialize, sqlx::FromRow, ToSchema)]
+struct ScheduleSlot {
+    id: i32,
+    group_id: i32,
+    subject_id: i32,
+    teacher_id: i32,
+    room_id: i32,
+    weekday: i16,
+    start_time: NaiveTime,
+    end_time: NaiveTime,
+    week_parity: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct ScheduleSlotInput {
+    group_id: i32,
+    subject_id: i32,
+    teacher_id: i32,
+    room_id: i32,
+    /// ISO weekday, 1 is Monday.
+    weekday: i16,
+    start_time: NaiveTime,
+    end_time: NaiveTime,
+    week_parity: WeekParity,
+}
+
+impl ScheduleSlotInput {
+    fn validate(&self) -> Result<(), HttpResponse> {
+        if !(1..=7).contains(&self.weekday) {
+            return Err(HttpResponse::BadRequest().body("Weekday must be between 1 (Monday) and 7 (Sunday)"));
+        }
+        if self.start_time >= self.end_time {
+            return Err(HttpResponse::BadRequest().body("Start time must be before end time"));
+        }
+        Ok(())
+    }
+}
+
+/// A slot together with the names of everything it references, as shown in a timetable.
+#[derive(Serialize, sqlx::FromRow, ToSchema)]
+pub(crate) struct ScheduleEntry {
+    pub(crate) id: i32,
+    pub(crate) group_id: i32,
+    pub(crate) weekday: i16,
+    pub(crate) start_time: NaiveTime,
+    pub(crate) end_time: NaiveTime,
+    pub(crate) week_parity: String,
+    pub(crate) subject_id: i32,
+    pub(crate) subject_name: String,
+    pub(crate) teacher_id: i32,
+    pub(crate) teacher_name: String,
+    pub(crate) teacher_surname: String,
+    pub(crate) room_id: i32,
+    pub(crate) room_name: String,
+}
+
+#[derive(Serialize, sqlx::FromRow, ToSchema)]
+struct SlotConflict {
+    slot_id: i32,
+    same_group: bool,
+    same_teacher: bool,
+    same_room: bool,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SlotFilter {
+    group_id: Option<i32>,
+    teacher_id: Option<i32>,
+    room_id: Option<i32>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct WeekQuery {
+    /// Week of the semester, starting from 1.
+    week: Option<u32>,
+    /// Alternative to `week`: any date within the semester.
+    date: Option<NaiveDate>,
+}
+
+pub(crate) fn require_non_empty(value: &str, field: &str) -> Result<(), HttpResponse> {
+    match value.trim().is_empty() {
+        true => Err(HttpResponse::BadRequest().body(format!("{field} must not be empty"))),
+        false => Ok(()),
+    }
+}
+
+pub(crate) async fn group_exists(group_store: &GroupStore, group_id: i32) -> Result<bool, HttpResponse> {
+    match group_store.find(group_id).await {
+        Ok(group) => Ok(group.is_some()),
+        Err(e) => {
+            log::error!("Failed to fetch group: {}", e);
+            Err(HttpResponse::InternalServerError().body(e.to_string()))
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/subjects",
+    tag = "schedule",
+    responses(
+        (status = 200, description = "All subjects", body = Vec<Subject>),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn get_subjects(pool: web::Data<PgPool>) -> impl Responder {
+    log::debug!("Fetching all subjects");
+    match sqlx::query_as::<_, Subject>("SELECT id, name FROM subjects ORDER BY name")
+        .fetch_all(pool.get_ref())
+        .await
+    {
+        Ok(subjects) => {
+            log::info!("Successfully retrieved {} subjects", subjects.len());
+            HttpResponse::Ok().json(subjects)
+        },
+        Err(e) => database_error("Failed to fetch subjects", e),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/subjects",
+    tag = "schedule",
+    request_body = SubjectInput,
+    responses(
+        (status = 200, description = "The created subject", body = Subject),
+        (status = 400, response = BadRequest),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn create_subject(subject: web::Json<SubjectInput>, pool: web::Data<PgPool>) -> impl Responder {
+    log::debug!("Creating new subject with name: {}", subject.name);
+    if let Err(response) = require_non_empty(&subject.name, "Subject name") {
+        return response;
+    }
+    match sqlx::query_as::<_, Subject>("INSERT INTO subjects (name) VALUES ($1) RETURNING id, name")
+        .bind(subject.name.trim())
+        .fetch_one(pool.get_ref())
+        .await
+    {
+        Ok(subject) => {
+            log::info!("Successfully created new subject: {}", subject.name);
+            HttpResponse::Ok().json(subject)
+        },
+        Err(e) => database_error("Failed to create subject", e),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/subjects/{id}",
+    tag = "schedule",
+    params(("id" = i32, Path, description = "Subject id")),
+    request_body = SubjectInput,
+    responses(
+        (status = 200, description = "The subject was updated"),
+        (status = 400, response = BadRequest),
+        (status = 404, response = NotFound),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn update_subject(
+    id: web::Path<i32>,
+    subject: web::Json<SubjectInput>,
+    pool: web::Data<PgPool>
+) -> impl Responder {
+    log::debug!("Updating subject id: {} with new name: {}", id, subject.name);
+    if let Err(response) = require_non_empty(&subject.name, "Subject name") {
+        return response;
+    }
+    match sqlx::query("UPDATE subjects SET name = $1 WHERE id = $2")
+        .bind(subject.name.trim())
+        .bind(id.as_ref())
+        .execute(pool.get_ref())
+        .await
+    {
+        Ok(result) if result.rows_affected() > 0 => {
+            log::info!("Successfully updated subject id: {}", id);
+            HttpResponse::Ok().finish()
+        },
+        Ok(_) => {
+            log::debug!("Subject not found with id: {}", id);
+            HttpResponse::NotFound().finish()
+        },
+        Err(e) => database_error("Failed to update subject", e),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/subjects/{id}",
+    tag = "schedule",
+    params(("id" = i32, Path, description = "Subject id")),
+    responses(
+        (status = 200, description = "The subject was deleted"),
+        (status = 400, description = "The subject is still used by the timetable", body = String),
+        (status = 404, response = NotFound),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn delete_subject(id: web::Path<i32>, pool: web::Data<PgPool>) -> impl Responder {
+    log::debug!("Attempting to delete subject with id: {}", id);
+    match sqlx::query("DELETE FROM subjects WHERE id = $1")
+        .bind(id.as_ref())
+        .execute(pool.get_ref())
+        .await
+    {
+        Ok(result) if result.rows_affected() > 0 => {
+            log::info!("Successfully deleted subject with id: {}", id);
+            HttpResponse::Ok().finish()
+        },
+        Ok(_) => {
+            log::debug!("Subject not found with id: {}", id);
+            HttpResponse::NotFound().finish()
+        },
+        Err(e) => database_error("Failed to delete subject", e),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/teachers",
+    tag = "schedule",
+    responses(
+        (status = 200, description = "All teachers", body = Vec<Teacher>),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn get_teachers(pool: web::Data<PgPool>) -> impl Responder {
+    log::debug!("Fetching all teachers");
+    match sqlx::query_as::<_, Teacher>("SELECT id, name, surname FROM teachers ORDER BY surname, name")
+        .fetch_all(pool.get_ref())
+        .await
+    {
+        Ok(teachers) => {
+            log::info!("Successfully retrieved {} teachers", teachers.len());
+            HttpResponse::Ok().json(teachers)
+        },
+        Err(e) => database_error("Failed to fetch teachers", e),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/teachers",
+    tag = "schedule",
+    request_body = TeacherInput,
+    responses(
+        (status = 200, description = "The created teacher", body = Teacher),
+        (status = 400, response = BadRequest),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn create_teacher(teacher: web::Json<TeacherInput>, pool: web::Data<PgPool>) -> impl Responder {
+    log::debug!("Creating new teacher: {} {}", teacher.name, teacher.surname);
+    if let Err(response) = require_non_empty(&teacher.name, "Name")
+        .and_then(|_| require_non_empty(&teacher.surname, "Surname"))
+    {
+        return response;
+    }
+    match sqlx::query_as::<_, Teacher>(
+        "INSERT INTO teachers (name, surname) VALUES ($1, $2) RETURNING id, name, surname"
+    )
+        .bind(teacher.name.trim())
+        .bind(teacher.surname.trim())
+        .fetch_one(pool.get_ref())
+        .await
+    {
+        Ok(teacher) => {
+            log::info!("Successfully created new teacher: {} {}", teacher.name, teacher.surname);
+            HttpResponse::Ok().json(teacher)
+        },
+        Err(e) => database_error("Failed to create teacher", e),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/teachers/{id}",
+    tag = "schedule",
+    params(("id" = i32, Path, description = "Teacher id")),
+    request_body = TeacherInput,
+    responses(
+        (status = 200, description = "The teacher was updated"),
+        (status = 400, response = BadRequest),
+        (status = 404, response = NotFound),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn update_teacher(
+    id: web::Path<i32>,
+    teacher: web::Json<TeacherInput>,
+    pool: web::Data<PgPool>
+) -> impl Responder {
+    log::debug!("Updating teacher id: {}", id);
+    if let Err(response) = require_non_empty(&teacher.name, "Name")
+        .and_then(|_| require_non_empty(&teacher.surname, "Surname"))
+    {
+        return response;
+    }
+    match sqlx::query("UPDATE teachers SET name = $1, surname = $2 WHERE id = $3")
+        .bind(teacher.name.trim())
+        .bind(teacher.surname.trim())
+        .bind(id.as_ref())
+        .execute(pool.get_ref())
+        .await
+    {
+        Ok(result) if result.rows_affected() > 0 => {
+            log::info!("Successfully updated teacher id: {}", id);
+            HttpResponse::Ok().finish()
+        },
+        Ok(_) => {
+            log::debug!("Teacher not found with id: {}", id);
+            HttpResponse::NotFound().finish()
+        },
+        Err(e) => database_error("Failed to update teacher", e),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/teachers/{id}",
+    tag = "schedule",
+    params(("id" = i32, Path, description = "Teacher id")),
+    responses(
+        (status = 200, description = "The teacher was deleted"),
+        (status = 400, description = "The teacher is still used by the timetable", body = String),
+        (status = 404, response = NotFound),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn delete_teacher(id: web::Path<i32>, pool: web::Data<PgPool>) -> impl Responder {
+    log::debug!("Attempting to delete teacher with id: {}", id);
+    match sqlx::query("DELETE FROM teachers WHERE id = $1")
+        .bind(id.as_ref())
+        .execute(pool.get_ref())
+        .await
+    {
+        Ok(result) if result.rows_affected() > 0 => {
+            log::info!("Successfully deleted teacher with id: {}", id);
+            HttpResponse::Ok().finish()
+        },
+        Ok(_) => {
+            log::debug!("Teacher not found with id: {}", id);
+            HttpResponse::NotFound().finish()
+        },
+        Err(e) => database_error("Failed to delete teacher", e),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/rooms",
+    tag = "schedule",
+    responses(
+        (status = 200, description = "All rooms", body = Vec<Room>),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn get_rooms(pool: web::Data<PgPool>) -> impl Responder {
+    log::debug!("Fetching all rooms");
+    match sqlx::query_as::<_, Room>("SELECT id, name, capacity FROM rooms ORDER BY name")
+        .fetch_all(pool.get_ref())
+        .await
+    {
+        Ok(rooms) => {
+            log::info!("Successfully retrieved {} rooms", rooms.len());
+            HttpResponse::Ok().json(rooms)
+        },
+        Err(e) => database_error("Failed to fetch rooms", e),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/rooms",
+    tag = "schedule",
+    request_body = RoomInput,
+    responses(
+        (status = 200, description = "The created room", body = Room),
+        (status = 400, response = BadRequest),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn create_room(room: web::Json<RoomInput>, pool: web::Data<PgPool>) -> impl Responder {
+    log::debug!("Creating new room: {}", room.name);
+    if let Err(response) = require_non_empty(&room.name, "Room name") {
+        return response;
+    }
+    match sqlx::query_as::<_, Room>("INSERT INTO rooms (name, capacity) VALUES ($1, $2) RETURNING id, name, capacity")
+        .bind(room.name.trim())
+        .bind(room.capacity)
+        .fetch_one(pool.get_ref())
+        .await
+    {
+        Ok(room) => {
+            log::info!("Successfully created new room: {}", room.name);
+            HttpResponse::Ok().json(room)
+        },
+        Err(e) => database_error("Failed to create room", e),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/rooms/{id}",
+    tag = "schedule",
+    params(("id" = i32, Path, description = "Room id")),
+    request_body = RoomInput,
+    responses(
+        (status = 200, description = "The room was updated"),
+        (status = 400, response = BadRequest),
+        (status = 404, response = NotFound),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn update_room(
+    id: web::Path<i32>,
+    room: web::Json<RoomInput>,
+    pool: web::Data<PgPool>
+) -> impl Responder {
+    log::debug!("Updating room id: {}", id);
+    if let Err(response) = require_non_empty(&room.name, "Room name") {
+        return response;
+    }
+    match sqlx::query("UPDATE rooms SET name = $1, capacity = $2 WHERE id = $3")
+        .bind(room.name.trim())
+        .bind(room.capacity)
+        .bind(id.as_ref())
+        .execute(pool.get_ref())
+        .await
+    {
+        Ok(result) if result.rows_affected() > 0 => {
+            log::info!("Successfully updated room id: {}", id);
+            HttpResponse::Ok().finish()
+        },
+        Ok(_) => {
+            log::debug!("Room not found with id: {}", id);
+            HttpResponse::NotFound().finish()
+        },
+        Err(e) => database_error("Failed to update room", e),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/rooms/{id}",
+    tag = "schedule",
+    params(("id" = i32, Path, description = "Room id")),
+    responses(
+        (status = 200, description = "The room was deleted"),
+        (status = 400, description = "The room is still used by the timetable", body = String),
+        (status = 404, response = NotFound),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn delete_room(id: web::Path<i32>, pool: web::Data<PgPool>) -> impl Responder {
+    log::debug!("Attempting to delete room with id: {}", id);
+    match sqlx::query("DELETE FROM rooms WHERE id = $1")
+        .bind(id.as_ref())
+        .execute(pool.get_ref())
+        .await
+    {
+        Ok(result) if result.rows_affected() > 0 => {
+            log::info!("Successfully deleted room with id: {}", id);
+            HttpResponse::Ok().finish()
+        },
+        Ok(_) => {
+            log::debug!("Room not found with id: {}", id);
+            HttpResponse::NotFound().finish()
+        },
+        Err(e) => database_error("Failed to delete room", e),
+    }
+}
+
+const SLOT_COLUMNS: &str =
+    "id, group_id, subject_id, teacher_id, room_id, weekday, start_time, end_time, week_parity";
+
+#[utoipa::path(
+    get,
+    path = "/schedule",
+    tag = "schedule",
+    params(SlotFilter),
+    responses(
+        (status = 200, description = "Slots matching the filters", body = Vec<ScheduleSlot>),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn get_slots(filter: web::Query<SlotFilter>, pool: web::Data<PgPool>) -> impl Responder {
+    log::debug!("Fetching schedule slots");
+    let query = format!(
+        "SELECT {SLOT_COLUMNS} FROM schedule_slots
+         WHERE ($1::INT IS NULL OR group_id = $1)
+           AND ($2::INT IS NULL OR teacher_id = $2)
+           AND ($3::INT IS NULL OR room_id = $3)
+         ORDER BY weekday, start_time"
+    );
+    match sqlx::query_as::<_, ScheduleSlot>(&query)
+        .bind(filter.group_id)
+        .bind(filter.teacher_id)
+        .bind(filter.room_id)
+        .fetch_all(pool.get_ref())
+        .await
+    {
+        Ok(slots) => {
+            log::info!("Successfully retrieved {} schedule slots", slots.len());
+            HttpResponse::Ok().json(slots)
+        },
+        Err(e) => database_error("Failed to fetch schedule slots", e),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/schedule/{id}",
+    tag = "schedule",
+    params(("id" = i32, Path, description = "Slot id")),
+    responses(
+        (status = 200, description = "The slot", body = ScheduleSlot),
+        (status = 404, response = NotFound),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn get_slot(id: web::Path<i32>, pool: web::Data<PgPool>) -> impl Responder {
+    log::debug!("Fetching schedule slot with id: {}", id);
+    let query = format!("SELECT {SLOT_COLUMNS} FROM schedule_slots WHERE id = $1");
+    match sqlx::query_as::<_, ScheduleSlot>(&query)
+        .bind(id.as_ref())
+        .fetch_optional(pool.get_ref())
+        .await
+    {
+        Ok(Some(slot)) => HttpResponse::Ok().json(slot),
+        Ok(None) => {
+            log::debug!("Schedule slot not found with id: {}", id);
+            HttpResponse::NotFound().finish()
+        },
+        Err(e) => database_error("Failed to fetch schedule slot", e),
+    }
+}
+
+/// Finds slots that overlap `slot` in time and week parity and share its group,
+/// teacher or room. Must run inside the transaction that writes the slot.
+async fn find_conflicts(
+    conn: &mut PgConnection,
+    slot: &ScheduleSlotInput,
+    slot_id: Option<i32>
+) -> Result<Vec<SlotConflict>, sqlx::Error> {
+    // Serializes concurrent writers so two overlapping slots cannot both pass the check.
+    sqlx::query("LOCK TABLE schedule_slots IN SHARE ROW EXCLUSIVE MODE")
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query_as::<_, SlotConflict>(
+        "SELECT id AS slot_id, group_id = $6 AS same_group, teacher_id = $7 AS same_teacher, room_id = $8 AS same_room
+         FROM schedule_slots
+         WHERE id IS DISTINCT FROM $1
+           AND weekday = $2
+           AND start_time < $4 AND $3 < end_time
+           AND (week_parity = 'both' OR $5 = 'both' OR week_parity = $5)
+           AND (group_id = $6 OR teacher_id = $7 OR room_id = $8)
+         ORDER BY id"
+    )
+        .bind(slot_id)
+        .bind(slot.weekday)
+        .bind(slot.start_time)
+        .bind(slot.end_time)
+        .bind(slot.week_parity.as_str())
+        .bind(slot.group_id)
+        .bind(slot.teacher_id)
+        .bind(slot.room_id)
+        .fetch_all(&mut *conn)
+        .await
+}
+
+/// Inserts a new slot when `slot_id` is `None`, otherwise replaces the existing one.
+async fn save_slot(
+    pool: &PgPool,
+    group_store: &GroupStore,
+    slot: &ScheduleSlotInput,
+    slot_id: Option<i32>
+) -> HttpResponse {
+    if let Err(response) = slot.validate() {
+        return response;
+    }
+    match group_exists(group_store, slot.group_id).await {
+        Ok(true) => {},
+        Ok(false) => return HttpResponse::BadRequest().body(format!("Group {} does not exist", slot.group_id)),
+        Err(response) => return response,
+    }
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            log::error!("Failed to start transaction: {}", e);
+            return HttpResponse::InternalServerError()
+                .body(format!("Failed to start transaction: {}", e));
+        }
+    };
+
+    match find_conflicts(&mut tx, slot, slot_id).await {
+        Ok(conflicts) if !conflicts.is_empty() => {
+            log::warn!("Schedule slot conflicts with {} existing slot(s)", conflicts.len());
+            if let Err(rollback_err) = tx.rollback().await {
+                log::error!("Failed to rollback transaction: {}", rollback_err);
+            }
+            return HttpResponse::Conflict().json(conflicts);
+        },
+        Ok(_) => {},
+        Err(e) => {
+            if let Err(rollback_err) = tx.rollback().await {
+                log::error!("Failed to rollback transaction: {}", rollback_err);
+            }
+            return database_error("Failed to check schedule conflicts", e);
+        }
+    }
+
+    let query = match slot_id {
+        None => format!(
+            "INSERT INTO schedule_slots
+                 (group_id, subject_id, teacher_id, room_id, weekday, start_time, end_time, week_parity)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+             RETURNING {SLOT_COLUMNS}"
+        ),
+        Some(_) => format!(
+            "UPDATE schedule_slots
+             SET group_id = $1, subject_id = $2, teacher_id = $3, room_id = $4,
+                 weekday = $5, start_time = $6, end_time = $7, week_parity = $8
+             WHERE id = $9
+             RETURNING {SLOT_COLUMNS}"
+        ),
+    };
+    let mut statement = sqlx::query_as::<_, ScheduleSlot>(&query)
+        .bind(slot.group_id)
+        .bind(slot.subject_id)
+        .bind(slot.teacher_id)
+        .bind(slot.room_id)
+        .bind(slot.weekday)
+        .bind(slot.start_time)
+        .bind(slot.end_time)
+        .bind(slot.week_parity.as_str());
+    if let Some(slot_id) = slot_id {
+        statement = statement.bind(slot_id);
+    }
+    let result = statement.fetch_optional(&mut *tx).await;
+
+    match result {
+        Ok(Some(saved)) => {
+            if let Err(e) = tx.commit().await {
+                log::error!("Failed to commit transaction: {}", e);
+                return HttpResponse::InternalServerError()
+                    .body(format!("Failed to commit transaction: {}", e));
+            }
+            log::info!("Successfully saved schedule slot id: {}", saved.id);
+            HttpResponse::Ok().json(saved)
+        },
+        Ok(None) => {
+            log::debug!("Schedule slot not found with id: {:?}", slot_id);
+            HttpResponse::NotFound().finish()
+        },
+        Err(e) => {
+            if let Err(rollback_err) = tx.rollback().await {
+                log::error!("Failed to rollback transaction: {}", rollback_err);
+            }
+            database_error("Failed to save schedule slot", e)
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/schedule",
+    tag = "schedule",
+    request_body = ScheduleSlotInput,
+    responses(
+        (status = 200, description = "The created slot", body = ScheduleSlot),
+        (status = 400, response = BadRequest),
+        (status = 409, description = "The slot clashes with classes of the same group, teacher or room", body = Vec<SlotConflict>),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn create_slot(
+    slot: web::Json<ScheduleSlotInput>,
+    pool: web::Data<PgPool>,
+    group_store: web::Data<GroupStore>
+) -> impl Responder {
+    log::debug!("Creating schedule slot for group {}", slot.group_id);
+    save_slot(pool.get_ref(), group_store.get_ref(), &slot, None).await
+}
+
+#[utoipa::path(
+    put,
+    path = "/schedule/{id}",
+    tag = "schedule",
+    params(("id" = i32, Path, description = "Slot id")),
+    request_body = ScheduleSlotInput,
+    responses(
+        (status = 200, description = "The updated slot", body = ScheduleSlot),
+        (status = 400, response = BadRequest),
+        (status = 404, response = NotFound),
+        (status = 409, description = "The slot clashes with classes of the same group, teacher or room", body = Vec<SlotConflict>),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn update_slot(
+    id: web::Path<i32>,
+    slot: web::Json<ScheduleSlotInput>,
+    pool: web::Data<PgPool>,
+    group_store: web::Data<GroupStore>
+) -> impl Responder {
+    log::debug!("Updating schedule slot id: {}", id);
+    save_slot(pool.get_ref(), group_store.get_ref(), &slot, Some(*id)).await
+}
+
+#[utoipa::path(
+    delete,
+    path = "/schedule/{id}",
+    tag = "schedule",
+    params(("id" = i32, Path, description = "Slot id")),
+    responses(
+        (status = 200, description = "The slot was deleted"),
+        (status = 400, description = "Attendance was already recorded for the slot", body = String),
+        (status = 404, response = NotFound),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn delete_slot(id: web::Path<i32>, pool: web::Data<PgPool>) -> impl Responder {
+    log::debug!("Attempting to delete schedule slot with id: {}", id);
+    match sqlx::query("DELETE FROM schedule_slots WHERE id = $1")
+        .bind(id.as_ref())
+        .execute(pool.get_ref())
+        .await
+    {
+        Ok(result) if result.rows_affected() > 0 => {
+            log::info!("Successfully deleted schedule slot with id: {}", id);
+            HttpResponse::Ok().finish()
+        },
+        Ok(_) => {
+            log::debug!("Schedule slot not found with id: {}", id);
+            HttpResponse::NotFound().finish()
+        },
+        Err(e) => database_error("Failed to delete schedule slot", e),
+    }
+}
+
+/// Loads the timetable of a group, limited to the classes held in weeks of the given parity.
+pub(crate) async fn fetch_group_schedule(
+    pool: &PgPool,
+    group_id: i32,
+    parity: Option<WeekParity>
+) -> Result<Vec<ScheduleEntry>, sqlx::Error> {
+    sqlx::query_as::<_, ScheduleEntry>(
+        "SELECT s.id, s.group_id, s.weekday, s.start_time, s.end_time, s.week_parity,
+                s.subject_id, sub.name AS subject_name,
+                s.teacher_id, t.name AS teacher_name, t.surname AS teacher_surname,
+                s.room_id, r.name AS room_name
+         FROM schedule_slots s
+         JOIN subjects sub ON sub.id = s.subject_id
+         JOIN teachers t ON t.id = s.teacher_id
+         JOIN rooms r ON r.id = s.room_id
+         WHERE s.group_id = $1
+           AND ($2::VARCHAR IS NULL OR s.week_parity IN ('both', $2))
+         ORDER BY s.weekday, s.start_time"
+    )
+        .bind(group_id)
+        .bind(parity.map(WeekParity::as_str))
+        .fetch_all(pool)
+        .await
+}
+
+#[utoipa::path(
+    get,
+    path = "/groups/{id}/schedule",
+    tag = "schedule",
+    params(("id" = i32, Path, description = "Group id"), WeekQuery),
+    responses(
+        (status = 200, description = "Timetable of the group, limited to one week if `week` or `date` is given", body = Vec<ScheduleEntry>),
+        (status = 400, response = BadRequest),
+        (status = 404, response = NotFound),
+        (status = 500, response = ServerError)
+    )
+)]
+pub(crate) async fn get_group_schedule(
+    id: web::Path<i32>,
+    week: web::Query<WeekQuery>,
+    pool: web::Data<PgPool>,
+    group_store: web::Data<GroupStore>
+) -> impl Responder {
+    log::debug!("Fetching schedule of group id: {} for week {:?}", id, week.week);
+    let week_number = match (week.week, week.date) {
+        (Some(0), _) => return HttpResponse::BadRequest().body("Weeks are numbered from 1"),
+        (Some(_), Some(_)) => return HttpResponse::BadRequest().body("Specify either week or date, not both"),
+        (Some(week_number), None) => Some(week_number),
+        (None, Some(date)) => match SEMESTER.as_ref().map(|semester| semester.week_of(date)) {
+            Ok(Some(week_number)) => Some(week_number),
+            Ok(None) => return HttpResponse::BadRequest().body(format!("{date} is outside of the semester")),
+            Err(e) => {
+                log::error!("Semester is not configured: {}", e);
+                return HttpResponse::InternalServerError().body(format!("Semester is not configured: {e}"));
+            }
+        },
+        (None, None) => None,
+    };
+    match group_exists(group_store.get_ref(), *id).await {
+        Ok(true) => {},
+        Ok(false) => {
+            log::debug!("Group not found with id: {}", id);
+            return HttpResponse::NotFound().finish();
+        },
+        Err(response) => return response,
+    }
+
+    match fetch_group_schedule(pool.get_ref(), *id, week_number.map(WeekParity::of_week)).await {
+        Ok(entries) => {
+            log::info!("Successfully retrieved {} schedule entries for group id: {}", entries.len(), id);
+            HttpResponse::Ok().json(entries)
+        },
+        Err(e) => database_error("Failed to fetch group schedule", e),
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    get_subjects, create_subject, update_subject, delete_subject, get_teachers, create_teacher,
+    update_teacher, delete_teacher, get_rooms, create_room, update_room, delete_room, get_slots,
+    create_slot, get_slot, update_slot, delete_slot, get_group_schedule,
+))]
+pub(crate) struct ApiDoc;
+
+pub(crate) fn configure_routes(cfg: &mut web::ServiceConfig) {
+    cfg
+        .service(
+            web::scope("/subjects")
+                .route("", web::get().to(get_subjects))
+                .route("", web::post().to(create_subject))
+                .route("/{id}", web::put().to(update_subject))
+                .route("/{id}", web::delete().to(delete_subject))
+        )
+        .service(
+            web::scope("/teachers")
+                .route("", web::get().to(get_teachers))
+                .route("", web::post().to(create_teacher))
+                .route("/{id}", web::put().to(update_teacher))
+                .route("/{id}", web::delete().to(delete_teacher))
+        )
+        .service(
+            web::scope("/rooms")
+                .route("", web::get().to(get_rooms))
+                .route("", web::post().to(create_room))
+                .route("/{id}", web::put().to(update_room))
+                .route("/{id}", web::delete().to(delete_room))
+        )
+        .service(
+            web::scope("/schedule")
+                .route("", web::get().to(get_slots))
+                .route("", web::post().to(create_slot))
+                .route("/{id}", web::get().to(get_slot))
+                .route("/{id}", web::put().to(update_slot))
+                .route("/{id}", web::delete().to(delete_slot))
+        );
+}
@@ -0,0 +1,274 @@
+//! Records and queries shared by every version of the HTTP API. Handlers decide
+//! how a record is presented to clients; this module only decides where it lives.
+
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use utoipa::ToSchema;
+
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::profile::{StudentProfile, StudentStatus};
+use crate::translit::transliterate;
+
+pub(crate) enum StorageType {
+    Blob,
+    Filesystem
+}
+
+lazy_static! {
+    static ref STORAGE_TYPE_STR: String = std::env::var("STORAGE_TYPE").expect("STORAGE_TYPE must be set");
+    pub(crate) static ref STORAGE_TYPE: StorageType = match STORAGE_TYPE_STR.as_str() {
+        "blob" => StorageType::Blob,
+        "filesystem" => StorageType::Filesystem,
+        _ => panic!("Invalid storage type")
+    };
+    pub(crate) static ref STUDENT_TABLE_NAME: &'static str = {
+        match *STORAGE_TYPE {
+            StorageType::Blob => "students_blob",
+            StorageType::Filesystem => "students_fs",
+        }
+    };
+    pub(crate) static ref IMAGES_PATH: PathBuf = std::env::var("IMAGES_PATH").expect("IMAGES_PATH must be set").into();
+}
+
+#[derive(Serialize, Deserialize, Clone, sqlx::FromRow, ToSchema)]
+pub(crate) struct Student {
+    pub(crate) id: i32,
+    pub(crate) name: String,
+    pub(crate) surname: String,
+    /// Official Latin spelling of the name, derived from `name` on every write.
+    pub(crate) name_latin: Option<String>,
+    pub(crate) surname_latin: Option<String>,
+    /// Graduated and expelled students no longer belong to a group.
+    pub(crate) group_id: Option<i32>,
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub(crate) profile: StudentProfile,
+    pub(crate) status: String,
+}
+
+/// Columns to select for [`Student`].
+pub(crate) const STUDENT_COLUMNS: &str =
+    "id, name, surname, name_latin, surname_latin, group_id, patronymic, email, phone, birth_date, record_book_number, status";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, ToSchema, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "varchar", rename_all = "snake_case")]
+pub(crate) enum StudyForm {
+    FullTime,
+    PartTime,
+    Distance,
+}
+
+impl StudyForm {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            StudyForm::FullTime => "full_time",
+            StudyForm::PartTime => "part_time",
+            StudyForm::Distance => "distance",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, sqlx::FromRow, ToSchema)]
+pub struct Group {
+    pub(crate) id: i32,
+    pub(crate) name: String,
+    #[serde(default)]
+    pub(crate) leader_id: Option<i32>,
+    #[serde(default)]
+    pub(crate) faculty: Option<String>,
+    #[serde(default)]
+    pub(crate) specialty_code: Option<String>,
+    #[serde(default)]
+    pub(crate) admission_year: Option<i32>,
+    #[serde(default)]
+    pub(crate) study_form: Option<StudyForm>,
+    #[serde(default)]
+    pub(crate) max_capacity: Option<i32>,
+}
+
+/// Failure of a student or group store.
+#[derive(Debug)]
+pub(crate) enum StoreError {
+    Mongo(mongodb::error::Error),
+    Postgres(sqlx::Error),
+    /// The change would break a rule of the data, such as deleting a group
+    /// that students still belong to.
+    Rejected(String),
+    /// The record the change was meant for does not exist.
+    NotFound,
+    Other(String),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Mongo(e) => e.fmt(f),
+            StoreError::Postgres(e) => e.fmt(f),
+            StoreError::Rejected(message) | StoreError::Other(message) => f.write_str(message),
+            StoreError::NotFound => f.write_str("Not found"),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+impl From<mongodb::error::Error> for StoreError {
+    fn from(e: mongodb::error::Error) -> Self {
+        StoreError::Mongo(e)
+    }
+}
+
+impl From<sqlx::Error> for StoreError {
+    fn from(e: sqlx::Error) -> Self {
+        StoreError::Postgres(e)
+    }
+}
+
+/// Escapes the wildcards of a `LIKE` pattern.
+fn like_escape(term: &str) -> String {
+    term.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+/// Splits a search query into `ILIKE` patterns for the original and the
+/// transliterated spelling of every word.
+fn search_patterns(query: Option<&str>) -> (Vec<String>, Vec<String>) {
+    query
+        .unwrap_or_default()
+        .split_whitespace()
+        .map(|term| (format!("%{}%", like_escape(term)), format!("%{}%", like_escape(&transliterate(term)))))
+        .unzip()
+}
+
+/// Students with the given status whose name or surname contains every word of
+/// `query`, in Cyrillic or Latin.
+pub(crate) async fn find_students(
+    pool: &PgPool,
+    status: Option<StudentStatus>,
+    query: Option<&str>
+) -> Result<Vec<Student>, sqlx::Error> {
+    let sql = format!(
+        "SELECT {STUDENT_COLUMNS} FROM {} WHERE ($1::VARCHAR IS NULL OR status = $1)
+         AND NOT EXISTS (
+             SELECT 1 FROM UNNEST($2::TEXT[], $3::TEXT[]) AS term(original, latin)
+             WHERE NOT (name ILIKE term.original OR surname ILIKE term.original
+                 OR name_latin ILIKE term.latin OR surname_latin ILIKE term.latin)
+         )",
+        *STUDENT_TABLE_NAME
+    );
+    let (patterns, latin_patterns) = search_patterns(query);
+    sqlx::query_as::<_, Student>(&sql)
+        .bind(status.map(StudentStatus::as_str))
+        .bind(&patterns)
+        .bind(&latin_patterns)
+        .fetch_all(pool)
+        .await
+}
+
+pub(crate) async fn find_student(pool: &PgPool, id: i32) -> Result<Option<Student>, sqlx::Error> {
+    let query = format!("SELECT {STUDENT_COLUMNS} FROM {} WHERE id = $1", *STUDENT_TABLE_NAME);
+    sqlx::query_as::<_, Student>(&query)
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+}
+
+pub(crate) async fn find_students_by_id(pool: &PgPool, ids: &[i32]) -> Result<Vec<Student>, sqlx::Error> {
+    let query = format!("SELECT {STUDENT_COLUMNS} FROM {} WHERE id = ANY($1)", *STUDENT_TABLE_NAME);
+    sqlx::query_as::<_, Student>(&query)
+        .bind(ids)
+        .fetch_all(pool)
+        .await
+}
+
+/// Ids and photo MIME types of those of `ids` that have a photo.
+pub(crate) async fn find_photo_types(pool: &PgPool, ids: &[i32]) -> Result<Vec<(i32, String)>, sqlx::Error> {
+    let image_column = match *STORAGE_TYPE {
+        StorageType::Blob => "image_data",
+        StorageType::Filesystem => "image_path",
+    };
+    let query = format!(
+        "SELECT id, image_type FROM {} WHERE id = ANY($1) AND {image_column} IS NOT NULL AND image_type IS NOT NULL",
+        *STUDENT_TABLE_NAME
+    );
+    sqlx::query_as::<_, (i32, String)>(&query)
+        .bind(ids)
+        .fetch_all(pool)
+        .await
+}
+
+/// Members of any of `group_ids`, ordered by id.
+pub(crate) async fn find_group_members(pool: &PgPool, group_ids: &[i32]) -> Result<Vec<Student>, sqlx::Error> {
+    let query = format!(
+        "SELECT {STUDENT_COLUMNS} FROM {} WHERE group_id = ANY($1) ORDER BY id",
+        *STUDENT_TABLE_NAME
+    );
+    sqlx::query_as::<_, Student>(&query)
+        .bind(group_ids)
+        .fetch_all(pool)
+        .await
+}
+
+/// One page of the members of `group_id` ordered by id; all of them when `limit` is `None`.
+pub(crate) async fn find_group_members_page(
+    pool: &PgPool,
+    group_id: i32,
+    limit: Option<i64>,
+    offset: i64
+) -> Result<Vec<Student>, sqlx::Error> {
+    let query = format!(
+        "SELECT {STUDENT_COLUMNS} FROM {} WHERE group_id = $1 ORDER BY id LIMIT $2 OFFSET $3",
+        *STUDENT_TABLE_NAME
+    );
+    sqlx::query_as::<_, Student>(&query)
+        .bind(group_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool)
+        .await
+}
+
+pub(crate) async fn count_group_members<'e, E: sqlx::PgExecutor<'e>>(
+    executor: E,
+    group_id: i32
+) -> Result<i64, sqlx::Error> {
+    let query = format!("SELECT COUNT(*) FROM {} WHERE group_id = $1", *STUDENT_TABLE_NAME);
+    sqlx::query_scalar::<_, i64>(&query)
+        .bind(group_id)
+        .fetch_one(executor)
+        .await
+}
+
+/// Group of a student; `None` when the student does not exist.
+pub(crate) async fn student_group<'e, E: sqlx::PgExecutor<'e>>(
+    executor: E,
+    id: i32
+) -> Result<Option<Option<i32>>, sqlx::Error> {
+    let query = format!("SELECT group_id FROM {} WHERE id = $1", *STUDENT_TABLE_NAME);
+    sqlx::query_scalar::<_, Option<i32>>(&query)
+        .bind(id)
+        .fetch_optional(executor)
+        .await
+}
+
+/// Counts the members of `group_id` other than `student_id`, and tells whether
+/// `student_id` itself is one of them.
+pub(crate) async fn group_occupancy<'e, E: sqlx::PgExecutor<'e>>(
+    executor: E,
+    group_id: i32,
+    student_id: Option<i32>
+) -> Result<(i64, bool), sqlx::Error> {
+    let query = format!(
+        "SELECT COUNT(*) FILTER (WHERE id IS DISTINCT FROM $2), COALESCE(BOOL_OR(id IS NOT DISTINCT FROM $2), FALSE) \
+         FROM {} WHERE group_id = $1",
+        *STUDENT_TABLE_NAME
+    );
+    sqlx::query_as::<_, (i64, bool)>(&query)
+        .bind(group_id)
+        .bind(student_id)
+        .fetch_one(executor)
+        .await
+}
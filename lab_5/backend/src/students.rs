@@ -0,0 +1,454 @@
+//! Where students live. Postgres keeps them in the table `STORAGE_TYPE` names,
+//! photos included, next to the outbox that carries their webhooks.
+
+use async_trait::async_trait;
+use sqlx::postgres::PgArguments;
+use sqlx::query::Query;
+use sqlx::{PgPool, Postgres, Row};
+
+use std::future::Future;
+
+use crate::events::{Event, EventKind};
+use crate::groups::GroupStore;
+use crate::profile::StudentStatus;
+use crate::routes::StudentForm;
+use crate::saga::{self, Saga};
+use crate::storage::{self, StorageType, StoreError, Student, IMAGES_PATH, STORAGE_TYPE, STUDENT_TABLE_NAME};
+use crate::translit::transliterate;
+use crate::webhooks;
+
+/// The student store the server was started with.
+pub(crate) type StudentStore = dyn StudentRepository;
+
+#[async_trait]
+pub(crate) trait StudentRepository: Send + Sync {
+    /// Students with the given status whose name or surname contains every word
+    /// of `query`, in Cyrillic or Latin.
+    async fn find_all(&self, status: Option<StudentStatus>, query: Option<&str>) -> Result<Vec<Student>, StoreError>;
+
+    async fn find(&self, id: i32) -> Result<Option<Student>, StoreError>;
+
+    async fn find_many(&self, ids: &[i32]) -> Result<Vec<Student>, StoreError>;
+
+    /// Members of any of `group_ids`, ordered by id.
+    async fn find_group_members(&self, group_ids: &[i32]) -> Result<Vec<Student>, StoreError>;
+
+    /// One page of the members of `group_id` ordered by id; all of them when `limit` is `None`.
+    async fn find_group_members_page(
+        &self,
+        group_id: i32,
+        limit: Option<i64>,
+        offset: i64
+    ) -> Result<Vec<Student>, StoreError>;
+
+    async fn count_group_members(&self, group_id: i32) -> Result<i64, StoreError>;
+
+    /// Counts the members of `group_id` other than `student_id`, and tells whether
+    /// `student_id` itself is one of them.
+    async fn group_occupancy(&self, group_id: i32, student_id: Option<i32>) -> Result<(i64, bool), StoreError>;
+
+    /// Group of a student; `None` when the student does not exist.
+    async fn student_group(&self, id: i32) -> Result<Option<Option<i32>>, StoreError>;
+
+    /// Photo of a student and its MIME type; `None` when the student does not
+    /// exist or has no photo.
+    async fn load_photo(&self, id: i32) -> Result<Option<(Vec<u8>, String)>, StoreError>;
+
+    /// Ids and photo MIME types of those of `ids` that have a photo.
+    async fn find_photo_types(&self, ids: &[i32]) -> Result<Vec<(i32, String)>, StoreError>;
+
+    /// Stores a new student together with their photo. Like the other writes
+    /// below, it queues the webhooks of the change along with it and returns the
+    /// event, which is left to publish.
+    async fn insert(&self, form: &StudentForm, groups: &GroupStore) -> Result<Event, StoreError>;
+
+    /// Stores the changes to a student. The photo is only replaced when the form
+    /// carries a new one, and groups the student left no longer keep them as leader.
+    async fn update(&self, id: i32, form: &StudentForm, groups: &GroupStore) -> Result<Event, StoreError>;
+
+    /// Deletes a student with their photo and records, and releases the groups they led.
+    async fn delete(&self, id: i32, groups: &GroupStore) -> Result<Event, StoreError>;
+
+    /// Deletes a group from `groups` unless students, classes or assessments
+    /// refer to it, which is reported as [`StoreError::Rejected`].
+    async fn delete_group(&self, group_id: i32, groups: &GroupStore) -> Result<Event, StoreError>;
+
+    /// Queues the webhooks of an event about a change made outside this store.
+    async fn queue_webhooks(&self, event: &Event) -> Result<(), StoreError>;
+}
+
+/// Columns written from a [`StudentForm`], bound as `$1`..`$10` by [`bind_student_form`].
+/// The status is left out on purpose: it only changes through `POST /api/v1/students/{id}/status`.
+const STUDENT_FORM_COLUMNS: &str =
+    "name, surname, group_id, patronymic, email, phone, birth_date, record_book_number, name_latin, surname_latin";
+const STUDENT_FORM_VALUES: &str = "$1, $2, $3, $4, $5, $6, $7, $8, $9, $10";
+/// Group and profile columns named in `$11`, the form's `kept_fields`, keep their value.
+pub(crate) const STUDENT_FORM_ASSIGNMENTS: &str = "name = $1, surname = $2, \
+    group_id = CASE WHEN 'group_id' = ANY($11) THEN group_id ELSE $3 END, \
+    patronymic = CASE WHEN 'patronymic' = ANY($11) THEN patronymic ELSE $4 END, \
+    email = CASE WHEN 'email' = ANY($11) THEN email ELSE $5 END, \
+    phone = CASE WHEN 'phone' = ANY($11) THEN phone ELSE $6 END, \
+    birth_date = CASE WHEN 'birth_date' = ANY($11) THEN birth_date ELSE $7 END, \
+    record_book_number = CASE WHEN 'record_book_number' = ANY($11) THEN record_book_number ELSE $8 END, \
+    name_latin = $9, surname_latin = $10";
+
+pub(crate) fn bind_student_form<'q>(
+    query: Query<'q, Postgres, PgArguments>,
+    form: &'q StudentForm
+) -> Query<'q, Postgres, PgArguments> {
+    query
+        .bind(&form.name)
+        .bind(&form.surname)
+        .bind(form.group_id)
+        .bind(&form.profile.patronymic)
+        .bind(&form.profile.email)
+        .bind(&form.profile.phone)
+        .bind(form.profile.birth_date)
+        .bind(&form.profile.record_book_number)
+        .bind(transliterate(&form.name))
+        .bind(transliterate(&form.surname))
+}
+
+/// A fresh file name for a photo in `IMAGES_PATH`.
+fn new_image_path() -> String {
+    IMAGES_PATH.join(uuid::Uuid::new_v4().to_string()).to_string_lossy().into_owned()
+}
+
+#[derive(sqlx::FromRow)]
+struct ImageBlobData {
+    image_data: Option<Vec<u8>>,
+    image_type: Option<String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct ImageFilesystemData {
+    image_path: Option<String>,
+    image_type: Option<String>,
+}
+
+pub(crate) struct PostgresStudents(pub(crate) PgPool);
+
+impl PostgresStudents {
+    /// Records `sagas`, runs the Postgres part of the operation they belong to
+    /// and then resolves them, whether the operation succeeded or not.
+    async fn with_sagas<T>(
+        &self,
+        groups: &GroupStore,
+        sagas: Vec<Saga>,
+        operation: impl Future<Output = Result<T, StoreError>>
+    ) -> Result<T, StoreError> {
+        if sagas.is_empty() {
+            return operation.await;
+        }
+        let mut conn = self.0.acquire().await?;
+        let pending = saga::begin_all(&mut conn, sagas).await?;
+        drop(conn);
+        let result = operation.await;
+        saga::finish_all(pending, &self.0, groups).await;
+        result
+    }
+
+    async fn image_path(&self, id: i32) -> Result<Option<String>, StoreError> {
+        let query = format!("SELECT image_path FROM {} WHERE id = $1", *STUDENT_TABLE_NAME);
+        let image_path: Option<Option<String>> = sqlx::query_scalar(&query)
+            .bind(id)
+            .fetch_optional(&self.0)
+            .await?;
+        Ok(image_path.flatten())
+    }
+}
+
+#[async_trait]
+impl StudentRepository for PostgresStudents {
+    async fn find_all(&self, status: Option<StudentStatus>, query: Option<&str>) -> Result<Vec<Student>, StoreError> {
+        Ok(storage::find_students(&self.0, status, query).await?)
+    }
+
+    async fn find(&self, id: i32) -> Result<Option<Student>, StoreError> {
+        Ok(storage::find_student(&self.0, id).await?)
+    }
+
+    async fn find_many(&self, ids: &[i32]) -> Result<Vec<Student>, StoreError> {
+        Ok(storage::find_students_by_id(&self.0, ids).await?)
+    }
+
+    async fn find_group_members(&self, group_ids: &[i32]) -> Result<Vec<Student>, StoreError> {
+        Ok(storage::find_group_members(&self.0, group_ids).await?)
+    }
+
+    async fn find_group_members_page(
+        &self,
+        group_id: i32,
+        limit: Option<i64>,
+        offset: i64
+    ) -> Result<Vec<Student>, StoreError> {
+        Ok(storage::find_group_members_page(&self.0, group_id, limit, offset).await?)
+    }
+
+    async fn count_group_members(&self, group_id: i32) -> Result<i64, StoreError> {
+        Ok(storage::count_group_members(&self.0, group_id).await?)
+    }
+
+    async fn group_occupancy(&self, group_id: i32, student_id: Option<i32>) -> Result<(i64, bool), StoreError> {
+        Ok(storage::group_occupancy(&self.0, group_id, student_id).await?)
+    }
+
+    async fn student_group(&self, id: i32) -> Result<Option<Option<i32>>, StoreError> {
+        Ok(storage::student_group(&self.0, id).await?)
+    }
+
+    async fn load_photo(&self, id: i32) -> Result<Option<(Vec<u8>, String)>, StoreError> {
+        match *STORAGE_TYPE {
+            StorageType::Blob => {
+                let query = format!("SELECT image_data, image_type FROM {} WHERE id = $1", *STUDENT_TABLE_NAME);
+                let row = sqlx::query_as::<_, ImageBlobData>(&query)
+                    .bind(id)
+                    .fetch_optional(&self.0)
+                    .await?;
+                Ok(row.and_then(|row| row.image_data.zip(row.image_type)))
+            },
+            StorageType::Filesystem => {
+                let query = format!("SELECT image_path, image_type FROM {} WHERE id = $1", *STUDENT_TABLE_NAME);
+                let row = sqlx::query_as::<_, ImageFilesystemData>(&query)
+                    .bind(id)
+                    .fetch_optional(&self.0)
+                    .await?;
+                let Some((image_path, image_type)) = row.and_then(|row| row.image_path.zip(row.image_type)) else {
+                    return Ok(None);
+                };
+                match tokio::fs::read(image_path).await {
+                    Ok(image_data) => Ok(Some((image_data, image_type))),
+                    Err(e) => Err(StoreError::Other(format!("Failed to read image file: {e}"))),
+                }
+            }
+        }
+    }
+
+    async fn find_photo_types(&self, ids: &[i32]) -> Result<Vec<(i32, String)>, StoreError> {
+        Ok(storage::find_photo_types(&self.0, ids).await?)
+    }
+
+    async fn insert(&self, form: &StudentForm, groups: &GroupStore) -> Result<Event, StoreError> {
+        let new_path = match (&*STORAGE_TYPE, &form.image_data) {
+            (StorageType::Filesystem, Some(_)) => Some(new_image_path()),
+            _ => None,
+        };
+        let sagas = new_path
+            .iter()
+            .map(|path| Saga::ReplaceImage { new_path: Some(path.clone()), old_path: None })
+            .collect();
+
+        self.with_sagas(groups, sagas, async {
+            let mut tx = self.0.begin().await?;
+            let row = match (&form.image_data, &new_path) {
+                (Some((image_data, image_type)), Some(file_path)) => {
+                    let row = bind_student_form(sqlx::query(&format!(
+                        "INSERT INTO {} ({STUDENT_FORM_COLUMNS}, image_path, image_type)
+                         VALUES ({STUDENT_FORM_VALUES}, $11, $12) RETURNING id",
+                        *STUDENT_TABLE_NAME
+                    )), form)
+                        .bind(file_path)
+                        .bind(image_type)
+                        .fetch_one(&mut *tx)
+                        .await?;
+
+                    // The row is only committed once the file is on disk; the saga
+                    // deletes the file again if the commit fails.
+                    if let Err(e) = tokio::fs::write(file_path, image_data).await {
+                        return Err(StoreError::Other(format!("Failed to write image file: {e}")));
+                    }
+                    row
+                },
+                (Some((image_data, image_type)), None) => {
+                    log::debug!("Creating student with blob image, type: {}", image_type);
+                    bind_student_form(sqlx::query(&format!(
+                        "INSERT INTO {} ({STUDENT_FORM_COLUMNS}, image_data, image_type)
+                         VALUES ({STUDENT_FORM_VALUES}, $11, $12) RETURNING id",
+                        *STUDENT_TABLE_NAME
+                    )), form)
+                        .bind(image_data)
+                        .bind(image_type)
+                        .fetch_one(&mut *tx)
+                        .await?
+                },
+                (None, _) => {
+                    log::debug!("Creating student without image");
+                    bind_student_form(sqlx::query(&format!(
+                        "INSERT INTO {} ({STUDENT_FORM_COLUMNS}) VALUES ({STUDENT_FORM_VALUES}) RETURNING id",
+                        *STUDENT_TABLE_NAME
+                    )), form)
+                        .fetch_one(&mut *tx)
+                        .await?
+                },
+            };
+
+            let event = Event::student(EventKind::StudentCreated, row.get("id"), [form.group_id]);
+            webhooks::enqueue(&mut *tx, std::slice::from_ref(&event)).await?;
+            tx.commit().await?;
+            Ok(event)
+        }).await
+    }
+
+    async fn update(&self, id: i32, form: &StudentForm, groups: &GroupStore) -> Result<Event, StoreError> {
+        let mut sagas = vec![Saga::ReleaseLeader { student_id: id }];
+        let new_path = match (&*STORAGE_TYPE, &form.image_data) {
+            (StorageType::Filesystem, Some(_)) => {
+                let old_path = self.image_path(id).await?;
+                let new_path = new_image_path();
+                sagas.push(Saga::ReplaceImage { new_path: Some(new_path.clone()), old_path });
+                Some(new_path)
+            },
+            _ => None,
+        };
+
+        self.with_sagas(groups, sagas, async {
+            let mut tx = self.0.begin().await?;
+            let previous_group = storage::student_group(&mut *tx, id).await?.flatten();
+            let updated = match (&form.image_data, &new_path) {
+                (Some((image_data, image_type)), Some(file_path)) => {
+                    let updated = bind_student_form(sqlx::query(&format!(
+                        "UPDATE {} SET {STUDENT_FORM_ASSIGNMENTS}, image_path = $12, image_type = $13 WHERE id = $14",
+                        *STUDENT_TABLE_NAME
+                    )), form)
+                        .bind(&form.kept_fields)
+                        .bind(file_path)
+                        .bind(image_type)
+                        .bind(id)
+                        .execute(&mut *tx)
+                        .await?
+                        .rows_affected();
+
+                    // The update is only committed once the new file is on disk. The
+                    // saga deletes whichever of the old and new file ends up unused.
+                    if updated > 0 {
+                        if let Err(e) = tokio::fs::write(file_path, image_data).await {
+                            return Err(StoreError::Other(format!("Failed to save new image file: {e}")));
+                        }
+                    }
+                    updated
+                },
+                (Some((image_data, image_type)), None) => {
+                    log::debug!("Updating student with new blob image, type: {}", image_type);
+                    bind_student_form(sqlx::query(&format!(
+                        "UPDATE {} SET {STUDENT_FORM_ASSIGNMENTS}, image_data = $12, image_type = $13 WHERE id = $14",
+                        *STUDENT_TABLE_NAME
+                    )), form)
+                        .bind(&form.kept_fields)
+                        .bind(image_data)
+                        .bind(image_type)
+                        .bind(id)
+                        .execute(&mut *tx)
+                        .await?
+                        .rows_affected()
+                },
+                (None, _) => {
+                    log::debug!("Updating student without changing image");
+                    bind_student_form(sqlx::query(&format!(
+                        "UPDATE {} SET {STUDENT_FORM_ASSIGNMENTS} WHERE id = $12",
+                        *STUDENT_TABLE_NAME
+                    )), form)
+                        .bind(&form.kept_fields)
+                        .bind(id)
+                        .execute(&mut *tx)
+                        .await?
+                        .rows_affected()
+                },
+            };
+            if updated == 0 {
+                return Err(StoreError::NotFound);
+            }
+
+            let event = Event::student(EventKind::StudentUpdated, id, [previous_group, form.group_id]);
+            webhooks::enqueue(&mut *tx, std::slice::from_ref(&event)).await?;
+            tx.commit().await?;
+            Ok(event)
+        }).await
+    }
+
+    async fn delete(&self, id: i32, groups: &GroupStore) -> Result<Event, StoreError> {
+        let mut sagas = vec![Saga::ReleaseLeader { student_id: id }];
+        if let StorageType::Filesystem = *STORAGE_TYPE {
+            if let Some(path) = self.image_path(id).await? {
+                sagas.push(Saga::ReplaceImage { new_path: None, old_path: Some(path) });
+            }
+        }
+
+        self.with_sagas(groups, sagas, async {
+            let mut tx = self.0.begin().await?;
+            let previous_group = storage::student_group(&mut *tx, id).await?.flatten();
+            let query = format!("DELETE FROM {} WHERE id = $1", *STUDENT_TABLE_NAME);
+            if sqlx::query(&query).bind(id).execute(&mut *tx).await?.rows_affected() == 0 {
+                return Err(StoreError::NotFound);
+            }
+
+            let event = Event::student(EventKind::StudentDeleted, id, [previous_group]);
+            webhooks::enqueue(&mut *tx, std::slice::from_ref(&event)).await?;
+            tx.commit().await?;
+            Ok(event)
+        }).await
+    }
+
+    async fn delete_group(&self, group_id: i32, groups: &GroupStore) -> Result<Event, StoreError> {
+        let mut tx = self.0.begin().await?;
+
+        // Students, classes and assessments that join the group meanwhile wait for
+        // this lock, and are rejected once the deletion below is recorded.
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext('group'), $1)")
+            .bind(group_id)
+            .execute(&mut *tx)
+            .await?;
+
+        // Both student tables count: the foreign keys to `groups` cover the one
+        // `STORAGE_TYPE` does not name as well.
+        let count: i64 = sqlx::query_scalar(
+            "SELECT (SELECT COUNT(*) FROM students_blob WHERE group_id = $1)
+                 + (SELECT COUNT(*) FROM students_fs WHERE group_id = $1)"
+        )
+            .bind(group_id)
+            .fetch_one(&mut *tx)
+            .await?;
+        log::debug!("Found {} students in group {}", count, group_id);
+        if count > 0 {
+            log::warn!("Cannot delete group {} as it contains {} students", group_id, count);
+            return Err(StoreError::Rejected("Cannot delete group with existing students".to_string()));
+        }
+
+        let slots: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM schedule_slots WHERE group_id = $1")
+            .bind(group_id)
+            .fetch_one(&mut *tx)
+            .await?;
+        if slots > 0 {
+            log::warn!("Cannot delete group {} as it has {} scheduled classes", group_id, slots);
+            return Err(StoreError::Rejected("Cannot delete group with scheduled classes".to_string()));
+        }
+
+        let assessments: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM assessments WHERE group_id = $1")
+            .bind(group_id)
+            .fetch_one(&mut *tx)
+            .await?;
+        if assessments > 0 {
+            log::warn!("Cannot delete group {} as it has {} assessments", group_id, assessments);
+            return Err(StoreError::Rejected("Cannot delete group with existing assessments".to_string()));
+        }
+
+        let event = Event::group(EventKind::GroupDeleted, group_id);
+        if groups.delete_in_postgres(&mut tx, group_id).await? {
+            webhooks::enqueue(&mut *tx, std::slice::from_ref(&event)).await?;
+            tx.commit().await?;
+            return Ok(event);
+        }
+
+        let deletion = saga::begin(&mut *tx, Saga::DeleteGroup { group_id }).await?;
+        tx.commit().await?;
+        deletion.finish(&self.0, groups).await.map_err(|e| {
+            StoreError::Other(format!("Failed to delete group, the deletion will be retried: {e}"))
+        })?;
+        // The group store is outside the transaction, so its webhooks wait for the deletion to go through.
+        webhooks::enqueue(&self.0, std::slice::from_ref(&event)).await?;
+        Ok(event)
+    }
+
+    async fn queue_webhooks(&self, event: &Event) -> Result<(), StoreError> {
+        Ok(webhooks::enqueue(&self.0, std::slice::from_ref(event)).await?)
+    }
+}
@@ -0,0 +1,195 @@
+use sqlx::PgPool;
+
+/// Latin spelling of a Cyrillic letter per the 2010 national transliteration
+/// standard (CMU resolution No. 55): the first form is used at the start of a
+/// word, the second everywhere else. Russian-only letters are mapped by analogy
+/// so that names typed on a Russian keyboard still transliterate.
+fn latin(letter: char) -> Option<(&'static str, &'static str)> {
+    let pair = |form| (form, form);
+    Some(match letter {
+        'а' => pair("a"),
+        'б' => pair("b"),
+        'в' => pair("v"),
+        'г' => pair("h"),
+        'ґ' => pair("g"),
+        'д' => pair("d"),
+        'е' | 'э' => pair("e"),
+        'є' => ("ye", "ie"),
+        'ё' => ("yo", "io"),
+        'ж' => pair("zh"),
+        'з' => pair("z"),
+        'и' | 'ы' => pair("y"),
+        'і' => pair("i"),
+        'ї' => ("yi", "i"),
+        'й' => ("y", "i"),
+        'к' => pair("k"),
+        'л' => pair("l"),
+        'м' => pair("m"),
+        'н' => pair("n"),
+        'о' => pair("o"),
+        'п' => pair("p"),
+        'р' => pair("r"),
+        'с' => pair("s"),
+        'т' => pair("t"),
+        'у' => pair("u"),
+        'ф' => pair("f"),
+        'х' => pair("kh"),
+        'ц' => pair("ts"),
+        'ч' => pair("ch"),
+        'ш' => pair("sh"),
+        'щ' => pair("shch"),
+        'ю' => ("yu", "iu"),
+        'я' => ("ya", "ia"),
+        'ь' | 'ъ' => pair(""),
+        _ => return None,
+    })
+}
+
+fn is_apostrophe(c: char) -> bool {
+    matches!(c, '\'' | '`' | '’' | 'ʼ' | '‘')
+}
+
+/// Transliterates Ukrainian text into Latin letters. Apostrophes and soft signs
+/// are dropped, "зг" becomes "zgh", and characters that are not Cyrillic are
+/// copied as they are. A capital letter capitalizes the first Latin letter of
+/// its transliteration.
+pub(crate) fn transliterate(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut word_start = true;
+    let mut previous = None;
+
+    for c in text.chars() {
+        if is_apostrophe(c) {
+            continue;
+        }
+        let lower = c.to_lowercase().next().unwrap_or(c);
+        match latin(lower) {
+            Some((initial, medial)) => {
+                let form = if lower == 'г' && previous == Some('з') {
+                    "gh"
+                } else if word_start {
+                    initial
+                } else {
+                    medial
+                };
+                let mut letters = form.chars();
+                if let Some(first) = letters.next() {
+                    if c.is_uppercase() {
+                        result.extend(first.to_uppercase());
+                    } else {
+                        result.push(first);
+                    }
+                    result.extend(letters);
+                }
+                word_start = false;
+            },
+            None => {
+                result.push(c);
+                word_start = !c.is_alphanumeric();
+            },
+        }
+        previous = Some(lower);
+    }
+    result
+}
+
+/// Lowercases a name, drops apostrophes and collapses runs of whitespace.
+pub(crate) fn normalize_name(name: &str) -> String {
+    name.split_whitespace()
+        .map(|word| word.chars().filter(|c| !is_apostrophe(*c)).collect::<String>().to_lowercase())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Spelling-insensitive form of a name for duplicate matching. The name is
+/// transliterated and reduced to ASCII letters and digits, `x` is spelled out,
+/// `y` is folded into `i` and doubled letters are collapsed, so "Іван", "Иван",
+/// "Ivan" and "Yvan" share one key, as do "Олексій" and "Oleksiy".
+pub(crate) fn match_key(name: &str) -> String {
+    let latin = transliterate(&normalize_name(name)).replace('x', "ks");
+    let mut key = String::with_capacity(latin.len());
+    for c in latin.chars().filter(char::is_ascii_alphanumeric) {
+        let c = if c == 'y' { 'i' } else { c };
+        if !key.ends_with(c) {
+            key.push(c);
+        }
+    }
+    key
+}
+
+/// Fills in the Latin names of students written before the columns existed,
+/// or restored from an older backup. Both student tables are covered so that
+/// switching `STORAGE_TYPE` does not bring back blank names.
+pub(crate) async fn backfill_latin_names(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let mut updated = 0;
+    for table in ["students_blob", "students_fs"] {
+        let rows: Vec<(i32, String, String)> = sqlx::query_as(&format!(
+            "SELECT id, name, surname FROM {table} WHERE name_latin IS NULL OR surname_latin IS NULL"
+        ))
+            .fetch_all(pool)
+            .await?;
+        if rows.is_empty() {
+            continue;
+        }
+
+        let ids: Vec<i32> = rows.iter().map(|(id, _, _)| *id).collect();
+        let names: Vec<String> = rows.iter().map(|(_, name, _)| transliterate(name)).collect();
+        let surnames: Vec<String> = rows.iter().map(|(_, _, surname)| transliterate(surname)).collect();
+        updated += sqlx::query(&format!(
+            "UPDATE {table} SET name_latin = latin.name, surname_latin = latin.surname
+             FROM UNNEST($1::INT[], $2::VARCHAR[], $3::VARCHAR[]) AS latin(id, name, surname)
+             WHERE {table}.id = latin.id"
+        ))
+            .bind(&ids)
+            .bind(&names)
+            .bind(&surnames)
+            .execute(pool)
+            .await?
+            .rows_affected();
+    }
+    Ok(updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iotated_letters_depend_on_their_place_in_the_word() {
+        let cases = [
+            ("Єнакієве", "Yenakiieve"),
+            ("Їжакевич", "Yizhakevych"),
+            ("Кадиївка", "Kadyivka"),
+            ("Йосипівка", "Yosypivka"),
+            ("Стрий", "Stryi"),
+            ("Юрій", "Yurii"),
+            ("Крюківка", "Kriukivka"),
+            ("Яготин", "Yahotyn"),
+            ("Костянтин", "Kostiantyn"),
+        ];
+        for (cyrillic, latin) in cases {
+            assert_eq!(transliterate(cyrillic), latin, "{cyrillic}");
+        }
+    }
+
+    #[test]
+    fn every_word_starts_afresh() {
+        assert_eq!(transliterate("Олена Ющенко-Яковенко"), "Olena Yushchenko-Yakovenko");
+    }
+
+    #[test]
+    fn zg_becomes_zgh() {
+        assert_eq!(transliterate("Згурський"), "Zghurskyi");
+        assert_eq!(transliterate("Розгон"), "Rozghon");
+        assert_eq!(transliterate("Збаразький"), "Zbarazkyi");
+    }
+
+    #[test]
+    fn apostrophes_and_soft_signs_are_dropped() {
+        for apostrophe in ['\'', '’', 'ʼ', '`'] {
+            assert_eq!(transliterate(&format!("В{apostrophe}ячеслав")), "Viacheslav");
+            assert_eq!(transliterate(&format!("Знам{apostrophe}янка")), "Znamianka");
+        }
+        assert_eq!(transliterate("Гайдамацький"), "Haidamatskyi");
+    }
+}
@@ -0,0 +1,457 @@
+use actix_web::{web, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sqlx::types::Json;
+use sqlx::{PgExecutor, PgPool};
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+use std::time::Duration;
+
+use crate::events::{Event, EventKind};
+use crate::openapi::{BadRequest, NotFound, ServerError};
+use crate::routes::database_error;
+
+/// A delivery is given up on after this many failed attempts.
+const MAX_ATTEMPTS: i32 = 8;
+/// Delay after the first failed attempt; it doubles with every further one.
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(30);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often the dispatcher looks for due deliveries when it has nothing to send.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// Deliveries claimed by the dispatcher at once.
+const CLAIM_BATCH_SIZE: i64 = 20;
+/// Claimed deliveries are left alone by other dispatchers for this long, which
+/// covers sending the whole batch. If the server stops meanwhile they are retried.
+const CLAIM_LEASE: Duration = Duration::from_secs(300);
+const MIN_SECRET_LENGTH: usize = 16;
+const DEFAULT_LIMIT: i64 = 100;
+const MAX_LIMIT: i64 = 1000;
+
+pub(crate) const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+pub(crate) const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+const EVENT_HEADER: &str = "X-Webhook-Event";
+const DELIVERY_HEADER: &str = "X-Webhook-Delivery";
+
+#[derive(Serialize, sqlx::FromRow, ToSchema)]
+struct Subscription {
+    id: i32,
+    url: String,
+    /// Event types delivered to the URL; empty for every event.
+    events: Vec<String>,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct SubscriptionInput {
+    /// `http` or `https` URL that receives a `POST` per event.
+    url: String,
+    /// Event types to deliver, such as `student.updated`; every event when empty.
+    #[serde(default)]
+    events: Vec<String>,
+    /// Key of the HMAC-SHA256 signature, at least 16 characters. It is never returned.
+    secret: String,
+}
+
+impl SubscriptionInput {
+    fn validate(&self) -> Result<(), HttpResponse> {
+        match reqwest::Url::parse(&self.url) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => {},
+            _ => return Err(HttpResponse::BadRequest().body("URL must be an absolute http or https URL")),
+        }
+        for event in &self.events {
+            event.parse::<EventKind>().map_err(|e| HttpResponse::BadRequest().body(e))?;
+        }
+        if self.secret.chars().count() < MIN_SECRET_LENGTH {
+            return Err(HttpResponse::BadRequest()
+                .body(format!("Secret must be at least {MIN_SECRET_LENGTH} characters long")));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, sqlx::FromRow, ToSchema)]
+struct Delivery {
+    id: i32,
+    subscription_id: i32,
+    event_type: String,
+    /// The body that is sent, an event as streamed by `/events`.
+    #[schema(value_type = Object)]
+    payload: Json<serde_json::Value>,
+    /// `pending`, `delivered` or `failed`.
+    status: String,
+    attempts: i32,
+    /// When a pending delivery is tried next.
+    next_attempt_at: DateTime<Utc>,
+    /// HTTP status of the last response, if one arrived.
+    last_status_code: Option<i32>,
+    /// Why the last attempt failed.
+    last_error: Option<String>,
+    created_at: DateTime<Utc>,
+    delivered_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct DeliveryFilter {
+    /// `pending`, `delivered` or `failed`.
+    status: Option<String>,
+    /// At most 1000, 100 by default.
+    limit: Option<i64>,
+}
+
+/// Queues a delivery of every event to every subscription that wants it. Run it
+/// in the transaction of the change, so deliveries exist exactly when the change does.
+pub(crate) async fn enqueue<'e, E: PgExecutor<'e>>(executor: E, events: &[Event]) -> Result<(), sqlx::Error> {
+    if events.is_empty() {
+        return Ok(());
+    }
+    let event_types: Vec<&str> = events.iter().map(|event| event.kind.as_str()).collect();
+    let payloads: Vec<String> = events
+        .iter()
+        .map(|event| serde_json::to_string(event).expect("events serialize to JSON"))
+        .collect();
+    sqlx::query(
+        "INSERT INTO webhook_deliveries (subscription_id, event_type, payload)
+         SELECT subscription.id, event.event_type, event.payload::JSONB
+         FROM UNNEST($1::VARCHAR[], $2::TEXT[]) WITH ORDINALITY AS event(event_type, payload, position)
+         JOIN webhook_subscriptions AS subscription
+             ON CARDINALITY(subscription.events) = 0 OR event.event_type = ANY(subscription.events)
+         ORDER BY event.position, subscription.id"
+    )
+        .bind(&event_types)
+        .bind(&payloads)
+        .execute(executor)
+        .await
+        .map(|_| ())
+}
+
+/// `sha256=` followed by the hex HMAC-SHA256 of `{timestamp}.{body}`.
+pub(crate) fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Delay before the next attempt once `attempts` attempts have failed.
+fn retry_delay(attempts: i32) -> Duration {
+    FIRST_RETRY_DELAY * 2u32.pow((attempts - 1).clamp(0, 16) as u32)
+}
+
+#[derive(sqlx::FromRow)]
+struct DueDelivery {
+    id: i32,
+    event_type: String,
+    payload: String,
+    attempts: i32,
+    url: String,
+    secret: String,
+}
+
+/// Posts a signed payload. Returns the HTTP status of the response, or why none arrived.
+async fn send(
+    client: &reqwest::Client,
+    url: &str,
+    secret: &str,
+    delivery_id: i32,
+    event_type: &str,
+    payload: String
+) -> Result<u16, String> {
+    let timestamp = Utc::now().timestamp();
+    let signature = sign(secret, timestamp, payload.as_bytes());
+    client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(SIGNATURE_HEADER, signature)
+        .header(TIMESTAMP_HEADER, timestamp)
+        .header(EVENT_HEADER, event_type)
+        .header(DELIVERY_HEADER, delivery_id)
+        .body(payload)
+        .send()
+        .await
+        .map(|response| response.status().as_u16())
+        .map_err(|e| e.to_string())
+}
+
+async fn claim_due_deliveries(pool: &PgPool) -> Result<Vec<DueDelivery>, sqlx::Error> {
+    sqlx::query_as::<_, DueDelivery>(
+        "UPDATE webhook_deliveries AS delivery
+         SET next_attempt_at = NOW() + $2 * INTERVAL '1 second'
+         FROM webhook_subscriptions AS subscription
+         WHERE subscription.id = delivery.subscription_id AND delivery.id IN (
+             SELECT id FROM webhook_deliveries
+             WHERE status = 'pending' AND next_attempt_at <= NOW()
+             ORDER BY next_attempt_at, id
+             LIMIT $1
+             FOR UPDATE SKIP LOCKED
+         )
+         RETURNING delivery.id, delivery.event_type, delivery.payload::TEXT AS payload, delivery.attempts,
+             subscription.url, subscription.secret"
+    )
+        .bind(CLAIM_BATCH_SIZE)
+        .bind(CLAIM_LEASE.as_secs_f64())
+        .fetch_all(pool)
+        .await
+}
+
+async fn attempt_delivery(pool: &PgPool, client: &reqwest::Client, delivery: DueDelivery) {
+    let attempts = delivery.attempts + 1;
+    let outcome = send(
+        client, &delivery.url, &delivery.secret, delivery.id, &delivery.event_type, delivery.payload
+    ).await;
+    let (status_code, error) = match outcome {
+        Ok(status_code) if (200..300).contains(&status_code) => (Some(status_code), None),
+        Ok(status_code) => (Some(status_code), Some(format!("Receiver responded with status {status_code}"))),
+        Err(e) => (None, Some(e)),
+    };
+    let status = match (&error, attempts >= MAX_ATTEMPTS) {
+        (None, _) => "delivered",
+        (Some(_), false) => "pending",
+        (Some(_), true) => "failed",
+    };
+    match &error {
+        None => log::info!("Delivered webhook {} to {}", delivery.id, delivery.url),
+        Some(e) => log::warn!("Webhook {} to {} failed on attempt {}: {}", delivery.id, delivery.url, attempts, e),
+    }
+
+    if let Err(e) = sqlx::query(
+        "UPDATE webhook_deliveries
+         SET status = $2, attempts = $3, next_attempt_at = NOW() + $4 * INTERVAL '1 second',
+             last_status_code = $5, last_error = $6,
+             delivered_at = CASE WHEN $2 = 'delivered' THEN NOW() END
+         WHERE id = $1"
+    )
+        .bind(delivery.id)
+        .bind(status)
+        .bind(attempts)
+        .bind(retry_delay(attempts).as_secs_f64())
+        .bind(status_code.map(i32::from))
+        .bind(error)
+        .execute(pool)
+        .await
+    {
+        log::error!("Failed to record the outcome of webhook {}: {}", delivery.id, e);
+    }
+}
+
+/// Sends due deliveries from the outbox for as long as the server runs. Several
+/// servers may dispatch from the same database; each delivery is claimed by one.
+pub(crate) async fn dispatch_deliveries(pool: PgPool) {
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .expect("HTTP client can be built");
+    loop {
+        match claim_due_deliveries(&pool).await {
+            Ok(due) if due.is_empty() => actix_web::rt::time::sleep(POLL_INTERVAL).await,
+            Ok(due) => {
+                for delivery in due {
+                    attempt_delivery(&pool, &client, delivery).await;
+                }
+            },
+            Err(e) => {
+                log::error!("Failed to claim due webhook deliveries: {}", e);
+                actix_web::rt::time::sleep(POLL_INTERVAL).await;
+            }
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/webhooks",
+    tag = "webhooks",
+    responses(
+        (status = 200, description = "Every subscription, without its secret", body = Vec<Subscription>),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn get_subscriptions(pool: web::Data<PgPool>) -> impl Responder {
+    log::debug!("Fetching webhook subscriptions");
+    match sqlx::query_as::<_, Subscription>("SELECT id, url, events, created_at FROM webhook_subscriptions ORDER BY id")
+        .fetch_all(pool.get_ref())
+        .await
+    {
+        Ok(subscriptions) => HttpResponse::Ok().json(subscriptions),
+        Err(e) => database_error("Failed to fetch webhook subscriptions", e),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/webhooks",
+    tag = "webhooks",
+    request_body = SubscriptionInput,
+    responses(
+        (status = 200, description = "The subscription; events are delivered from now on", body = Subscription),
+        (status = 400, response = BadRequest),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn create_subscription(subscription: web::Json<SubscriptionInput>, pool: web::Data<PgPool>) -> impl Responder {
+    if let Err(response) = subscription.validate() {
+        return response;
+    }
+    match sqlx::query_as::<_, Subscription>(
+        "INSERT INTO webhook_subscriptions (url, events, secret) VALUES ($1, $2, $3)
+         RETURNING id, url, events, created_at"
+    )
+        .bind(&subscription.url)
+        .bind(&subscription.events)
+        .bind(&subscription.secret)
+        .fetch_one(pool.get_ref())
+        .await
+    {
+        Ok(created) => {
+            log::info!("Created webhook subscription {} for {}", created.id, created.url);
+            HttpResponse::Ok().json(created)
+        },
+        Err(e) => database_error("Failed to create webhook subscription", e),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/webhooks/{id}",
+    tag = "webhooks",
+    params(("id" = i32, Path, description = "Subscription id")),
+    responses(
+        (status = 200, description = "The subscription and its delivery log were deleted"),
+        (status = 404, response = NotFound),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn delete_subscription(id: web::Path<i32>, pool: web::Data<PgPool>) -> impl Responder {
+    match sqlx::query("DELETE FROM webhook_subscriptions WHERE id = $1")
+        .bind(*id)
+        .execute(pool.get_ref())
+        .await
+    {
+        Ok(result) if result.rows_affected() > 0 => {
+            log::info!("Deleted webhook subscription {}", id);
+            HttpResponse::Ok().finish()
+        },
+        Ok(_) => HttpResponse::NotFound().finish(),
+        Err(e) => database_error("Failed to delete webhook subscription", e),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/webhooks/{id}/deliveries",
+    tag = "webhooks",
+    params(("id" = i32, Path, description = "Subscription id"), DeliveryFilter),
+    responses(
+        (status = 200, description = "Deliveries of the subscription, most recent first", body = Vec<Delivery>),
+        (status = 400, response = BadRequest),
+        (status = 500, response = ServerError)
+    )
+)]
+async fn get_deliveries(
+    id: web::Path<i32>,
+    filter: web::Query<DeliveryFilter>,
+    pool: web::Data<PgPool>
+) -> impl Responder {
+    log::debug!("Fetching deliveries of webhook subscription {}", id);
+    let limit = filter.limit.unwrap_or(DEFAULT_LIMIT);
+    if !(1..=MAX_LIMIT).contains(&limit) {
+        return HttpResponse::BadRequest().body(format!("Limit must be between 1 and {MAX_LIMIT}"));
+    }
+
+    match sqlx::query_as::<_, Delivery>(
+        "SELECT id, subscription_id, event_type, payload, status, attempts, next_attempt_at, last_status_code,
+             last_error, created_at, delivered_at
+         FROM webhook_deliveries
+         WHERE subscription_id = $1 AND ($2::VARCHAR IS NULL OR status = $2)
+         ORDER BY id DESC LIMIT $3"
+    )
+        .bind(*id)
+        .bind(filter.status.as_deref())
+        .bind(limit)
+        .fetch_all(pool.get_ref())
+        .await
+    {
+        Ok(deliveries) => HttpResponse::Ok().json(deliveries),
+        Err(e) => database_error("Failed to fetch webhook deliveries", e),
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    get_subscriptions, create_subscription, delete_subscription, get_deliveries,
+))]
+pub(crate) struct ApiDoc;
+
+pub(crate) fn configure_routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/webhooks", web::get().to(get_subscriptions))
+        .route("/webhooks", web::post().to(create_subscription))
+        .route("/webhooks/{id}", web::delete().to(delete_subscription))
+        .route("/webhooks/{id}/deliveries", web::get().to(get_deliveries));
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{App, HttpRequest, HttpServer};
+    use futures::channel::mpsc;
+    use futures::StreamExt;
+
+    use super::*;
+
+    const SECRET: &str = "a-secret-of-sufficient-length";
+
+    /// Starts a local receiver that answers every request with `status` and
+    /// reports whether its signature was valid, along with the body.
+    async fn stand_in(status: u16) -> (String, mpsc::UnboundedReceiver<(bool, String)>) {
+        let (sender, receiver) = mpsc::unbounded();
+        let server = HttpServer::new(move || {
+            let sender = sender.clone();
+            App::new().default_service(web::to(move |request: HttpRequest, body: web::Bytes| {
+                let sender = sender.clone();
+                async move {
+                    let header = |name: &str| request.headers().get(name).and_then(|value| value.to_str().ok());
+                    let valid = match (header(SIGNATURE_HEADER), header(TIMESTAMP_HEADER).and_then(|t| t.parse().ok())) {
+                        (Some(signature), Some(timestamp)) => signature == sign(SECRET, timestamp, &body),
+                        _ => false,
+                    };
+                    sender.unbounded_send((valid, String::from_utf8_lossy(&body).into_owned())).ok();
+                    HttpResponse::build(actix_web::http::StatusCode::from_u16(status).unwrap()).finish()
+                }
+            }))
+        })
+            .workers(1)
+            .bind(("127.0.0.1", 0))
+            .expect("stand-in binds to a free port");
+        let url = format!("http://{}/hook", server.addrs()[0]);
+        actix_web::rt::spawn(server.run());
+        (url, receiver)
+    }
+
+    #[actix_web::test]
+    async fn delivery_is_signed() {
+        let (url, mut received) = stand_in(204).await;
+        let payload = r#"{"type":"student.updated","id":1,"groups":[1,2]}"#;
+        let status = send(&reqwest::Client::new(), &url, SECRET, 1, "student.updated", payload.to_string()).await;
+        assert_eq!(status, Ok(204));
+        assert_eq!(received.next().await, Some((true, payload.to_string())));
+    }
+
+    #[actix_web::test]
+    async fn failures_are_reported() {
+        let (url, _received) = stand_in(503).await;
+        let status = send(&reqwest::Client::new(), &url, SECRET, 1, "group.deleted", "{}".to_string()).await;
+        assert_eq!(status, Ok(503));
+
+        let unreachable = send(&reqwest::Client::new(), "http://127.0.0.1:1/hook", SECRET, 1, "group.deleted", "{}".to_string()).await;
+        assert!(unreachable.is_err());
+    }
+
+    #[test]
+    fn retries_back_off_exponentially() {
+        assert_eq!(retry_delay(1), FIRST_RETRY_DELAY);
+        assert_eq!(retry_delay(2), FIRST_RETRY_DELAY * 2);
+        assert_eq!(retry_delay(MAX_ATTEMPTS - 1), FIRST_RETRY_DELAY * 64);
+    }
+}
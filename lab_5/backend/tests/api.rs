@@ -0,0 +1,367 @@
+//! Drives the HTTP API of the backend binary against throwaway databases, with
+//! photos stored in both `STORAGE_TYPE` modes and groups in both group stores.
+
+mod common;
+
+use reqwest::blocking::multipart::{Form, Part};
+use reqwest::blocking::Response;
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+use std::io::Cursor;
+
+use common::{Mongo, Postgres, Server};
+
+fn encode(format: image::ImageFormat) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    image::DynamicImage::new_rgb8(2, 2)
+        .write_to(&mut Cursor::new(&mut bytes), format)
+        .expect("the image can be encoded");
+    bytes
+}
+
+fn student_form(name: &str, surname: &str, group_id: i32, photo: Option<(&[u8], &str)>) -> Form {
+    let form = Form::new()
+        .text("studentName", name.to_string())
+        .text("studentSurname", surname.to_string())
+        .text("studentGroup", group_id.to_string());
+    match photo {
+        Some((data, content_type)) => form.part(
+            "studentPhoto",
+            Part::bytes(data.to_vec()).file_name("photo").mime_str(content_type).unwrap(),
+        ),
+        None => form,
+    }
+}
+
+fn text(response: Response) -> (StatusCode, String) {
+    let status = response.status();
+    (status, response.text().unwrap_or_default())
+}
+
+impl Server {
+    fn get(&self, path: &str) -> Response {
+        self.client.get(format!("{}{path}", self.url)).send().unwrap()
+    }
+
+    fn get_json(&self, path: &str) -> Value {
+        let response = self.get(path);
+        assert_eq!(response.status(), StatusCode::OK, "GET {path}");
+        response.json().unwrap()
+    }
+
+    fn create_student(&self, form: Form) -> Response {
+        self.client.post(format!("{}/students", self.url)).multipart(form).send().unwrap()
+    }
+
+    fn update_student(&self, id: i64, form: Form) -> Response {
+        self.client.put(format!("{}/students/{id}", self.url)).multipart(form).send().unwrap()
+    }
+
+    fn delete(&self, path: &str) -> Response {
+        self.client.delete(format!("{}{path}", self.url)).send().unwrap()
+    }
+
+    /// Id of the only student whose name matches `query`.
+    fn student_id(&self, query: &str) -> i64 {
+        let students = self.get_json(&format!("/students?q={query}"));
+        assert_eq!(students.as_array().unwrap().len(), 1, "students matching {query}: {students}");
+        students[0]["id"].as_i64().unwrap()
+    }
+
+    fn photo(&self, id: i64) -> Option<(String, Vec<u8>)> {
+        let response = self.get(&format!("/students/image/{id}"));
+        match response.status() {
+            StatusCode::NOT_FOUND => None,
+            StatusCode::OK => {
+                let content_type = response.headers()["content-type"].to_str().unwrap().to_string();
+                Some((content_type, response.bytes().unwrap().to_vec()))
+            },
+            status => panic!("unexpected status {status} for the photo of student {id}"),
+        }
+    }
+
+    fn image_files(&self) -> usize {
+        std::fs::read_dir(self.images_dir()).unwrap().count()
+    }
+}
+
+/// Creates, changes and deletes a student with a photo, and checks that bad
+/// photos are turned away without leaving anything behind.
+fn exercise_student_photos(server: &Server) {
+    let png = encode(image::ImageFormat::Png);
+    let jpeg = encode(image::ImageFormat::Jpeg);
+
+    let form = student_form("Олена", "Шевченко", 1, Some((&png, "image/png")))
+        .text("studentEmail", "olena@example.com");
+    assert_eq!(server.create_student(form).status(), StatusCode::OK);
+    let id = server.student_id("Shevchenko");
+    assert_eq!(server.photo(id), Some(("image/png".to_string(), png.clone())));
+
+    let student = server.get_json(&format!("/students/{id}"));
+    assert_eq!((&student["name_latin"], &student["group_id"]), (&json!("Olena"), &json!(1)));
+
+    // A photo, like the group and the profile fields, is only replaced when the update carries a new one.
+    let response = server.update_student(id, student_form("Олена", "Коваль", 2, None));
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(server.get_json(&format!("/students/{id}"))["email"], "olena@example.com");
+    assert_eq!(server.photo(id), Some(("image/png".to_string(), png)));
+    let form = Form::new().text("studentName", "Олена").text("studentSurname", "Коваль");
+    assert_eq!(server.update_student(id, form).status(), StatusCode::OK);
+    assert_eq!(server.get_json(&format!("/students/{id}"))["group_id"], 2);
+    let response = server.update_student(id, student_form("Олена", "Коваль", 2, Some((&jpeg, "image/jpeg"))));
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(server.photo(id), Some(("image/jpeg".to_string(), jpeg.clone())));
+
+    let bad_photos: [(&[u8], &str, &str); 3] = [
+        (b"not an image", "image/png", "Invalid image data"),
+        (&jpeg, "image/png", "Image format doesn't match the specified content type"),
+        (b"GIF89a", "image/gif", "Invalid image format. Only JPEG and PNG are supported."),
+    ];
+    for (data, content_type, message) in bad_photos {
+        let photo = Some((data, content_type));
+        let (status, body) = text(server.create_student(student_form("Петро", "Іваненко", 1, photo)));
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{content_type} photo was accepted");
+        assert!(body.starts_with(message), "unexpected error for a bad {content_type} photo: {body}");
+        let (status, _) = text(server.update_student(id, student_form("Олена", "Коваль", 2, photo)));
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+    assert!(server.get_json("/students?q=Іваненко").as_array().unwrap().is_empty());
+    assert_eq!(server.photo(id), Some(("image/jpeg".to_string(), jpeg)));
+
+    let (status, body) = text(server.create_student(student_form("Петро", "Іваненко", 99, None)));
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body, "Failed to create student: references a record that does not exist or is still referenced");
+
+    assert_eq!(server.delete(&format!("/students/{id}")).status(), StatusCode::OK);
+    assert_eq!(server.get(&format!("/students/{id}")).status(), StatusCode::NOT_FOUND);
+    assert_eq!(server.photo(id), None);
+}
+
+/// Exports the students of a group and the archive of everything.
+fn exercise_exports(server: &Server) {
+    let png = encode(image::ImageFormat::Png);
+    let response = server.create_student(student_form("Олена", "Шевченко", 1, Some((&png, "image/png"))));
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = server.get("/export?format=csv&group_id=1");
+    assert_eq!(response.status(), StatusCode::OK);
+    let disposition = response.headers()["content-disposition"].to_str().unwrap().to_string();
+    assert!(disposition.contains("filename=\"students_IP-12.csv\""), "{disposition}");
+    assert!(disposition.contains("filename*=UTF-8''students_%D0%86%D0%9F%2D12.csv"), "{disposition}");
+    let csv = response.text().unwrap();
+    assert!(csv.starts_with("id,name,surname,"), "{csv}");
+    assert_eq!(csv.lines().count(), 2);
+
+    let response = server.get("/export/archive");
+    assert_eq!(response.status(), StatusCode::OK);
+    let mut archive = zip::ZipArchive::new(Cursor::new(response.bytes().unwrap())).expect("the archive is a ZIP file");
+    let mut names: Vec<&str> = archive.file_names().collect();
+    names.sort_unstable();
+    let photo_entry = |name: &str| name.starts_with("photos/") && name.ends_with(".png");
+    assert!(
+        matches!(names.as_slice(), ["groups.json", photo, "students.csv"] if photo_entry(photo)),
+        "unexpected archive entries: {names:?}"
+    );
+    let mut students = String::new();
+    std::io::Read::read_to_string(&mut archive.by_name("students.csv").unwrap(), &mut students).unwrap();
+    assert_eq!(students.lines().count(), 3, "the seeded student, the new one and the header: {students}");
+}
+
+/// Records scores of the student `exercise_exports` leaves in group 1.
+fn exercise_scores(server: &Server, postgres: &Postgres) {
+    let id = server.student_id("Shevchenko");
+    postgres.query("INSERT INTO subjects (name) VALUES ('Бази даних')");
+    let assessment = postgres.query(
+        "INSERT INTO assessments (subject_id, group_id, name, weight, max_score)
+         SELECT id, 1, 'Модульна контрольна', 1, 100 FROM subjects WHERE name = 'Бази даних' RETURNING id"
+    );
+    let put_scores = |scores: Value| {
+        server.client.put(format!("{}/assessments/{assessment}/scores", server.url)).json(&scores).send().unwrap()
+    };
+
+    let scores = json!({ "scores": [{ "student_id": id, "score": 80 }, { "student_id": id, "score": 90 }] });
+    let (status, body) = text(put_scores(scores));
+    assert_eq!((status, body), (StatusCode::BAD_REQUEST, format!("Student {id} appears more than once")));
+    assert_eq!(put_scores(json!({ "scores": [{ "student_id": id, "score": 90 }] })).status(), StatusCode::OK);
+    assert_eq!(postgres.query("SELECT string_agg(score::TEXT, ',') FROM scores"), "90");
+}
+
+/// Creates and changes a student in batches; an update keeps the profile fields it leaves out,
+/// and a failed operation explains itself without the database's own wording.
+fn exercise_batch(server: &Server) {
+    let batch = |operations: Value| {
+        server.client.post(format!("{}/students/batch", server.url))
+            .json(&json!({ "operations": operations }))
+            .send()
+            .unwrap()
+    };
+
+    let create = json!({
+        "op": "create", "name": "Марія", "surname": "Бондар", "group_id": 1,
+        "email": "maria@example.com", "phone": "+380441234567", "record_book_number": "КВ-0001",
+    });
+    let response = batch(json!([create]));
+    assert_eq!(response.status(), StatusCode::OK);
+    let id = response.json::<Value>().unwrap()["results"][0]["id"].as_i64().unwrap();
+
+    let update = json!({ "op": "update", "id": id, "name": "Марія", "surname": "Коваль", "group_id": 1, "phone": null });
+    assert_eq!(batch(json!([update])).status(), StatusCode::OK);
+    let student = server.get_json(&format!("/students/{id}"));
+    assert_eq!((&student["surname"], &student["email"]), (&json!("Коваль"), &json!("maria@example.com")));
+    assert_eq!(student["phone"], Value::Null);
+
+    let response = batch(json!([create]));
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let error = &response.json::<Value>().unwrap()["results"][0]["error"];
+    assert_eq!(error, "Failed to create student: conflicts with an existing record");
+}
+
+/// Checks an import against a group with a single free place: a row refused for a record book
+/// number already in use leaves that place to the valid row after it.
+fn exercise_import(server: &Server) {
+    let response = server.client.post(format!("{}/groups", server.url))
+        .json(&json!({ "name": "ІП-16", "max_capacity": 1 }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // КВ-0001 belongs to the student created by `exercise_batch`.
+    let csv = "name,surname,group,record_book_number\nІрина,Мельник,ІП-16,КВ-0001\nОлег,Ткаченко,ІП-16,КВ-0002\n";
+    let form = Form::new().part("file", Part::bytes(csv.as_bytes().to_vec()).file_name("students.csv"));
+    let response = server.client.post(format!("{}/students/import?dry_run=true", server.url))
+        .multipart(form)
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let report = response.json::<Value>().unwrap();
+    assert_eq!((&report["total_rows"], &report["valid_rows"]), (&json!(2), &json!(1)), "{report}");
+    assert_eq!(report["errors"], json!([{ "row": 2, "message": "Record book number КВ-0001 is already in use" }]));
+}
+
+/// Creates, changes and deletes groups, and checks that a group is only
+/// deleted once no student belongs to it.
+fn exercise_groups(server: &Server) {
+    let groups = server.get_json("/groups");
+    let names: Vec<&str> = groups.as_array().unwrap().iter().map(|group| group["name"].as_str().unwrap()).collect();
+    assert_eq!(names, ["ІП-11", "ІП-12", "ІП-13", "ІП-15"], "the groups of the seed data");
+
+    let response = server.client.post(format!("{}/groups", server.url))
+        .json(&json!({ "name": "ІП-14", "faculty": "ФІОТ", "max_capacity": 1 }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let group = server.get_json("/groups/4");
+    assert_eq!((&group["name"], &group["max_capacity"]), (&json!("ІП-14"), &json!(1)));
+
+    let response = server.create_student(student_form("Олена", "Шевченко", 4, None));
+    assert_eq!(response.status(), StatusCode::OK);
+    let id = server.student_id("Шевченко");
+    let (status, body) = text(server.create_student(student_form("Петро", "Іваненко", 4, None)));
+    assert_eq!((status, body.as_str()), (StatusCode::BAD_REQUEST, "Group is at full capacity (1 students)"));
+
+    let response = server.client.put(format!("{}/groups/4/leader", server.url))
+        .json(&json!({ "student_id": id }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(server.get_json("/groups/4")["leader_id"], json!(id));
+
+    let roster = server.get_json("/groups/4/students");
+    assert_eq!(roster["student_count"], 1);
+    assert_eq!(server.get_json("/groups?expand=students")[4]["students"][0]["id"], json!(id));
+
+    // Group 3 holds the student of the seed data.
+    for group_id in [3, 4] {
+        let (status, body) = text(server.delete(&format!("/groups/{group_id}")));
+        assert_eq!((status, body.as_str()), (StatusCode::BAD_REQUEST, "Cannot delete group with existing students"));
+        assert_eq!(server.get(&format!("/groups/{group_id}")).status(), StatusCode::OK);
+    }
+
+    // Moving the leader to another group releases the leadership.
+    assert_eq!(server.update_student(id, student_form("Олена", "Шевченко", 0, None)).status(), StatusCode::OK);
+    assert_eq!(server.get_json("/groups/4")["leader_id"], Value::Null);
+
+    let response = server.client.put(format!("{}/groups/4", server.url))
+        .json(&json!({ "name": "ІП-14м" }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    // Fields the update leaves out keep their value.
+    let group = server.get_json("/groups/4");
+    assert_eq!(group["name"], "ІП-14м");
+    assert_eq!((&group["faculty"], &group["max_capacity"]), (&json!("ФІОТ"), &json!(1)));
+
+    assert_eq!(server.delete("/groups/4").status(), StatusCode::OK);
+    assert_eq!(server.get("/groups/4").status(), StatusCode::NOT_FOUND);
+    assert_eq!(server.delete("/groups/4").status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+#[ignore = "needs the Postgres binaries, run with --ignored"]
+fn photos_stored_as_blobs() {
+    let postgres = Postgres::start();
+    let server = Server::start(&postgres, None, "blob");
+    exercise_student_photos(&server);
+    assert_eq!(server.image_files(), 0);
+    exercise_exports(&server);
+    exercise_scores(&server, &postgres);
+    exercise_batch(&server);
+    exercise_import(&server);
+    // Only the student of the seed data is kept in the other table.
+    assert_eq!(postgres.query("SELECT COUNT(*) FROM students_fs"), "1");
+}
+
+#[test]
+#[ignore = "needs the Postgres binaries, run with --ignored"]
+fn photos_stored_as_files() {
+    let postgres = Postgres::start();
+    let server = Server::start(&postgres, None, "filesystem");
+    postgres.query("INSERT INTO webhook_subscriptions (url, secret) VALUES ('http://127.0.0.1:9/', 'a test secret')");
+    // Changes to a student that does not exist are not announced.
+    let png = encode(image::ImageFormat::Png);
+    let response = server.update_student(999, student_form("Олена", "Шевченко", 1, Some((&png, "image/png"))));
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(server.delete("/students/999").status(), StatusCode::NOT_FOUND);
+    assert_eq!(postgres.query("SELECT COUNT(*) FROM webhook_deliveries"), "0");
+    exercise_student_photos(&server);
+    // Replaced photos, rejected uploads and deleted students leave no files behind.
+    assert_eq!(server.image_files(), 0);
+    assert_eq!(postgres.query("SELECT COUNT(*) FROM students_blob"), "1");
+    assert_eq!(postgres.query("SELECT COUNT(*) FROM sagas WHERE status = 'pending'"), "0");
+    // Webhooks are queued along with the writes that went through, and only with them.
+    let deliveries = postgres.query(
+        "SELECT string_agg(event_type || ':' || (payload->>'id'), ',' ORDER BY id) FROM webhook_deliveries"
+    );
+    assert_eq!(deliveries, "student.created:2,student.updated:2,student.updated:2,student.updated:2,student.deleted:2");
+}
+
+#[test]
+#[ignore = "needs the Postgres binaries, run with --ignored"]
+fn groups_in_postgres() {
+    let postgres = Postgres::start();
+    let server = Server::start(&postgres, None, "blob");
+    exercise_groups(&server);
+    assert_eq!(postgres.query("SELECT name FROM groups ORDER BY id DESC LIMIT 1"), "ІП-15");
+
+    // Students of the table `STORAGE_TYPE` does not name keep their group too.
+    postgres.query("INSERT INTO students_fs (name, surname, group_id) VALUES ('Петро', 'Іваненко', 2)");
+    let (status, body) = text(server.delete("/groups/2"));
+    assert_eq!((status, body.as_str()), (StatusCode::BAD_REQUEST, "Cannot delete group with existing students"));
+    postgres.query("DELETE FROM students_fs WHERE group_id = 2");
+    assert_eq!(server.delete("/groups/2").status(), StatusCode::OK);
+    assert_eq!(server.get("/groups/2").status(), StatusCode::NOT_FOUND);
+    // Groups in Postgres are deleted in the transaction that checks them.
+    assert_eq!(postgres.query("SELECT COUNT(*) FROM sagas WHERE kind = 'delete_group'"), "0");
+}
+
+#[test]
+#[ignore = "needs the Postgres and Mongo binaries, run with --ignored"]
+fn groups_in_mongo() {
+    let postgres = Postgres::start();
+    let mongo = Mongo::start();
+    let server = Server::start(&postgres, Some(&mongo), "filesystem");
+    exercise_groups(&server);
+    // The groups table of `setup.sql` is left alone while groups live in Mongo.
+    assert_eq!(postgres.query("SELECT COUNT(*) FROM groups"), "4");
+}
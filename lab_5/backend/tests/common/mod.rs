@@ -0,0 +1,248 @@
+//! Throwaway Postgres and Mongo instances started from local binaries, and the
+//! backend binary serving them. Everything lives in temporary directories and is
+//! stopped when dropped.
+//!
+//! The binaries are looked up in `PG_BIN` and `MONGO_BIN` when set, on `PATH`
+//! otherwise. A test that needs missing binaries fails instead of passing without
+//! having run.
+
+use reqwest::blocking::Client;
+use tempfile::TempDir;
+
+use std::fs::File;
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long a database or the backend may take to accept connections.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+const DATABASE: &str = "labdb";
+const MONGO_COLLECTION: &str = "groups";
+
+fn databases_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../databases")
+}
+
+fn binary(dir_variable: &str, name: &str) -> PathBuf {
+    match std::env::var_os(dir_variable) {
+        Some(dir) => Path::new(&dir).join(name),
+        None => PathBuf::from(name),
+    }
+}
+
+fn is_installed(binary: &Path) -> bool {
+    Command::new(binary).arg("--version").stdout(Stdio::null()).stderr(Stdio::null()).status().is_ok()
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0").expect("a local port is free").local_addr().unwrap().port()
+}
+
+fn wait_for_port(port: u16, what: &str) {
+    let started = Instant::now();
+    while TcpStream::connect(("127.0.0.1", port)).is_err() {
+        assert!(started.elapsed() < STARTUP_TIMEOUT, "{what} did not start listening on port {port}");
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+fn run(command: &mut Command) {
+    let output = command.output().unwrap_or_else(|e| panic!("failed to run {command:?}: {e}"));
+    assert!(
+        output.status.success(),
+        "{command:?} failed: {}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+pub struct Postgres {
+    dir: TempDir,
+    pub port: u16,
+}
+
+impl Postgres {
+    /// Starts a fresh cluster with the schema of `setup.sql`. Panics when Postgres
+    /// is not installed or cannot run as the current user.
+    pub fn start() -> Postgres {
+        assert!(
+            is_installed(&binary("PG_BIN", "initdb")),
+            "initdb was not found, set PG_BIN to the directory of the Postgres binaries"
+        );
+        let uid = Command::new("id").arg("-u").output().map(|output| output.stdout).unwrap_or_default();
+        assert!(uid.trim_ascii() != b"0", "Postgres refuses to run as root, run the tests as another user");
+
+        let dir = tempfile::tempdir().expect("a temporary directory can be created");
+        let data = dir.path().join("data");
+        let port = free_port();
+        run(Command::new(binary("PG_BIN", "initdb"))
+            .args(["--username=postgres", "--auth=trust", "--encoding=UTF8", "--no-locale", "--pgdata"])
+            .arg(&data));
+        run(Command::new(binary("PG_BIN", "pg_ctl"))
+            .arg("--pgdata").arg(&data)
+            .arg("--log").arg(dir.path().join("postgres.log"))
+            .arg("--options")
+            .arg(format!(
+                "-p {port} -k {} -c listen_addresses=127.0.0.1 -c fsync=off",
+                dir.path().display()
+            ))
+            .args(["--wait", "start"]));
+        let postgres = Postgres { dir, port };
+
+        run(postgres.psql().args(["--dbname=postgres", "--command"]).arg(format!("CREATE DATABASE {DATABASE}")));
+        run(postgres.psql()
+            .arg(format!("--dbname={DATABASE}"))
+            .arg("--file").arg(databases_dir().join("postgresql/setup.sql")));
+        postgres
+    }
+
+    fn psql(&self) -> Command {
+        let mut command = Command::new(binary("PG_BIN", "psql"));
+        command
+            .args(["--host=127.0.0.1", "--username=postgres", "--quiet", "--set=ON_ERROR_STOP=1"])
+            .arg(format!("--port={}", self.port))
+            .stdout(Stdio::null());
+        command
+    }
+
+    /// Runs a query and returns its output as unaligned text, one row per line.
+    pub fn query(&self, sql: &str) -> String {
+        let output = self.psql()
+            .args([&format!("--dbname={DATABASE}"), "--tuples-only", "--no-align", "--command", sql])
+            .stdout(Stdio::piped())
+            .output()
+            .expect("psql runs");
+        assert!(output.status.success(), "{sql} failed: {}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    }
+}
+
+impl Drop for Postgres {
+    fn drop(&mut self) {
+        Command::new(binary("PG_BIN", "pg_ctl"))
+            .arg("--pgdata").arg(self.dir.path().join("data"))
+            .args(["--mode=immediate", "stop"])
+            .stdout(Stdio::null())
+            .status()
+            .ok();
+    }
+}
+
+pub struct Mongo {
+    _dir: TempDir,
+    process: Child,
+    pub port: u16,
+}
+
+impl Mongo {
+    /// Starts a fresh `mongod` seeded by `init.js`. Panics when `mongod` or
+    /// `mongosh` is not installed.
+    pub fn start() -> Mongo {
+        assert!(
+            is_installed(&binary("MONGO_BIN", "mongod")) && is_installed(&binary("MONGO_BIN", "mongosh")),
+            "mongod or mongosh was not found, set MONGO_BIN to the directory of the Mongo binaries"
+        );
+
+        let dir = tempfile::tempdir().expect("a temporary directory can be created");
+        let port = free_port();
+        let process = Command::new(binary("MONGO_BIN", "mongod"))
+            .arg("--dbpath").arg(dir.path())
+            .args(["--bind_ip", "127.0.0.1", "--port", &port.to_string(), "--quiet"])
+            .stdout(Stdio::null())
+            .spawn()
+            .expect("mongod starts");
+        let mongo = Mongo { _dir: dir, process, port };
+        wait_for_port(port, "mongod");
+
+        run(Command::new(binary("MONGO_BIN", "mongosh"))
+            .arg(format!("mongodb://127.0.0.1:{port}/"))
+            .arg("--quiet")
+            .arg(databases_dir().join("mongo/init.js"))
+            .env("MONGO_INITDB_DATABASE", DATABASE)
+            .env("MONGO_COLLECTION", MONGO_COLLECTION));
+        mongo
+    }
+}
+
+impl Drop for Mongo {
+    fn drop(&mut self) {
+        self.process.kill().ok();
+        self.process.wait().ok();
+    }
+}
+
+/// The backend binary, serving HTTP on a free port.
+pub struct Server {
+    process: Child,
+    dir: TempDir,
+    pub url: String,
+    pub client: Client,
+}
+
+impl Server {
+    /// Starts the backend with photos stored as `storage_type`. Groups are kept
+    /// in `mongo` when given, in Postgres otherwise.
+    pub fn start(postgres: &Postgres, mongo: Option<&Mongo>, storage_type: &str) -> Server {
+        let dir = tempfile::tempdir().expect("a temporary directory can be created");
+        std::fs::create_dir(dir.path().join("images")).unwrap();
+        let log = File::create(dir.path().join("server.log")).unwrap();
+        let port = free_port();
+
+        let process = Command::new(env!("CARGO_BIN_EXE_server"))
+            .env("STORAGE_TYPE", storage_type)
+            .env("IMAGES_PATH", dir.path().join("images"))
+            .env("GROUP_STORE", if mongo.is_some() { "mongo" } else { "postgres" })
+            .env("POSTGRES_HOST", "127.0.0.1")
+            .env("POSTGRES_PORT", postgres.port.to_string())
+            .env("POSTGRES_USER", "postgres")
+            .env("POSTGRES_PASSWORD", "postgres")
+            .env("POSTGRES_DB", DATABASE)
+            .env("MONGO_HOST", "127.0.0.1")
+            .env("MONGO_PORT", mongo.map_or(0, |mongo| mongo.port).to_string())
+            .env("MONGO_INITDB_DATABASE", DATABASE)
+            .env("MONGO_COLLECTION", MONGO_COLLECTION)
+            .env("BACKEND_PORT", port.to_string())
+            .env("GRPC_PORT", free_port().to_string())
+            .stdout(log.try_clone().unwrap())
+            .stderr(log)
+            .spawn()
+            .expect("the backend starts");
+        let mut server = Server {
+            process,
+            dir,
+            url: format!("http://127.0.0.1:{port}/api/v1"),
+            client: Client::new(),
+        };
+
+        let started = Instant::now();
+        while TcpStream::connect(("127.0.0.1", port)).is_err() {
+            if let Ok(Some(status)) = server.process.try_wait() {
+                panic!("the backend exited with {status}:\n{}", server.log());
+            }
+            assert!(started.elapsed() < STARTUP_TIMEOUT, "the backend did not start:\n{}", server.log());
+            thread::sleep(Duration::from_millis(100));
+        }
+        server
+    }
+
+    pub fn images_dir(&self) -> PathBuf {
+        self.dir.path().join("images")
+    }
+
+    /// Everything the backend has logged so far.
+    pub fn log(&self) -> String {
+        std::fs::read_to_string(self.dir.path().join("server.log")).unwrap_or_default()
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.process.kill().ok();
+        self.process.wait().ok();
+        if thread::panicking() {
+            eprintln!("backend log:\n{}", self.log());
+        }
+    }
+}
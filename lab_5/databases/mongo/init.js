@@ -15,9 +15,17 @@ if (!db.getCollectionNames().includes(collectionName)) {
     db.createCollection(collectionName);
 }
 
+const fiot121 = {
+    faculty: "ФІОТ",
+    specialty_code: "121",
+    admission_year: 2021,
+    study_form: "full_time",
+    max_capacity: 35,
+};
+
 db[collectionName].insertMany([
-    { id: 0, name: "ІП-11" },
-    { id: 1, name: "ІП-12" },
-    { id: 2, name: "ІП-13" },
-    { id: 3, name: "ІП-15" },
+    { id: 0, name: "ІП-11", ...fiot121 },
+    { id: 1, name: "ІП-12", ...fiot121 },
+    { id: 2, name: "ІП-13", ...fiot121 },
+    { id: 3, name: "ІП-15", ...fiot121 },
 ]);
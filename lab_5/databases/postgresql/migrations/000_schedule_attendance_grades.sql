@@ -0,0 +1,82 @@
+-- Class schedule, attendance and grades, which the later migrations build on.
+-- setup.sql already contains these tables; apply this only to databases created before them. Tables a database
+-- already has are left alone, so it is safe on one created while they were being added.
+BEGIN;
+
+CREATE TABLE IF NOT EXISTS subjects (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(255) NOT NULL UNIQUE CHECK (LENGTH(name) > 0)
+);
+
+CREATE TABLE IF NOT EXISTS teachers (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(255) NOT NULL CHECK (LENGTH(name) > 0),
+    surname VARCHAR(255) NOT NULL CHECK (LENGTH(surname) > 0)
+);
+
+CREATE TABLE IF NOT EXISTS rooms (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(64) NOT NULL UNIQUE CHECK (LENGTH(name) > 0),
+    capacity INT CHECK (capacity > 0)
+);
+
+CREATE TABLE IF NOT EXISTS schedule_slots (
+    id SERIAL PRIMARY KEY,
+    group_id INT NOT NULL CHECK (group_id >= 0),
+    subject_id INT NOT NULL REFERENCES subjects(id),
+    teacher_id INT NOT NULL REFERENCES teachers(id),
+    room_id INT NOT NULL REFERENCES rooms(id),
+    weekday SMALLINT NOT NULL CHECK (weekday BETWEEN 1 AND 7),
+    start_time TIME NOT NULL,
+    end_time TIME NOT NULL,
+    week_parity VARCHAR(4) NOT NULL CHECK (week_parity IN ('odd', 'even', 'both')),
+    CONSTRAINT schedule_slot_time_check CHECK (start_time < end_time)
+);
+
+CREATE INDEX IF NOT EXISTS schedule_slots_weekday_idx ON schedule_slots (weekday);
+
+CREATE TABLE IF NOT EXISTS attendance (
+    student_id INT NOT NULL,
+    date DATE NOT NULL,
+    slot_id INT NOT NULL REFERENCES schedule_slots(id),
+    status VARCHAR(8) NOT NULL CHECK (status IN ('present', 'absent', 'excused', 'late')),
+    PRIMARY KEY (student_id, date, slot_id)
+);
+
+CREATE INDEX IF NOT EXISTS attendance_slot_date_idx ON attendance (slot_id, date);
+
+CREATE TABLE IF NOT EXISTS assessments (
+    id SERIAL PRIMARY KEY,
+    subject_id INT NOT NULL REFERENCES subjects(id),
+    group_id INT NOT NULL CHECK (group_id >= 0),
+    name VARCHAR(255) NOT NULL CHECK (LENGTH(name) > 0),
+    weight DOUBLE PRECISION NOT NULL CHECK (weight > 0),
+    max_score DOUBLE PRECISION NOT NULL CHECK (max_score > 0),
+    UNIQUE (subject_id, group_id, name)
+);
+
+CREATE TABLE IF NOT EXISTS scores (
+    assessment_id INT NOT NULL REFERENCES assessments(id) ON DELETE CASCADE,
+    student_id INT NOT NULL,
+    score DOUBLE PRECISION NOT NULL CHECK (score >= 0),
+    PRIMARY KEY (assessment_id, student_id)
+);
+
+-- Students live in one of two tables, so dependent rows are cleaned up by a trigger instead of a foreign key.
+CREATE OR REPLACE FUNCTION delete_student_dependents() RETURNS TRIGGER AS $$
+BEGIN
+    DELETE FROM attendance WHERE student_id = OLD.id;
+    DELETE FROM scores WHERE student_id = OLD.id;
+    RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS students_blob_delete_dependents ON students_blob;
+CREATE TRIGGER students_blob_delete_dependents AFTER DELETE ON students_blob
+    FOR EACH ROW EXECUTE FUNCTION delete_student_dependents();
+
+DROP TRIGGER IF EXISTS students_fs_delete_dependents ON students_fs;
+CREATE TRIGGER students_fs_delete_dependents AFTER DELETE ON students_fs
+    FOR EACH ROW EXECUTE FUNCTION delete_student_dependents();
+
+COMMIT;
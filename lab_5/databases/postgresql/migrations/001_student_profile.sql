@@ -0,0 +1,23 @@
+-- Adds contact details, date of birth, record book number and enrollment status to students.
+-- setup.sql already contains these columns; apply this only to databases created before them.
+BEGIN;
+
+ALTER TABLE students_blob
+    ADD COLUMN patronymic VARCHAR(255),
+    ADD COLUMN email VARCHAR(254),
+    ADD COLUMN phone VARCHAR(32),
+    ADD COLUMN birth_date DATE,
+    ADD COLUMN record_book_number VARCHAR(32) UNIQUE,
+    ADD COLUMN status VARCHAR(16) NOT NULL DEFAULT 'active'
+        CHECK (status IN ('active', 'academic_leave', 'expelled', 'graduated'));
+
+ALTER TABLE students_fs
+    ADD COLUMN patronymic VARCHAR(255),
+    ADD COLUMN email VARCHAR(254),
+    ADD COLUMN phone VARCHAR(32),
+    ADD COLUMN birth_date DATE,
+    ADD COLUMN record_book_number VARCHAR(32) UNIQUE,
+    ADD COLUMN status VARCHAR(16) NOT NULL DEFAULT 'active'
+        CHECK (status IN ('active', 'academic_leave', 'expelled', 'graduated'));
+
+COMMIT;
@@ -0,0 +1,37 @@
+-- Lets graduated and expelled students leave their group and records every status change.
+BEGIN;
+
+ALTER TABLE students_blob ALTER COLUMN group_id DROP NOT NULL;
+ALTER TABLE students_fs ALTER COLUMN group_id DROP NOT NULL;
+
+UPDATE students_blob SET group_id = NULL WHERE status IN ('expelled', 'graduated');
+UPDATE students_fs SET group_id = NULL WHERE status IN ('expelled', 'graduated');
+
+ALTER TABLE students_blob ADD CONSTRAINT student_group_status_check
+    CHECK ((group_id IS NULL) = (status IN ('expelled', 'graduated')));
+ALTER TABLE students_fs ADD CONSTRAINT student_group_status_check
+    CHECK ((group_id IS NULL) = (status IN ('expelled', 'graduated')));
+
+CREATE TABLE student_status_history (
+    id SERIAL PRIMARY KEY,
+    student_id INT NOT NULL,
+    from_status VARCHAR(16) NOT NULL,
+    to_status VARCHAR(16) NOT NULL,
+    reason TEXT NOT NULL CHECK (LENGTH(reason) > 0),
+    effective_date DATE NOT NULL,
+    group_id INT,
+    recorded_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX student_status_history_student_idx ON student_status_history (student_id);
+
+CREATE OR REPLACE FUNCTION delete_student_dependents() RETURNS TRIGGER AS $$
+BEGIN
+    DELETE FROM attendance WHERE student_id = OLD.id;
+    DELETE FROM scores WHERE student_id = OLD.id;
+    DELETE FROM student_status_history WHERE student_id = OLD.id;
+    RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
+
+COMMIT;
@@ -0,0 +1,11 @@
+-- Append-only trail of administrative changes such as student merges.
+CREATE TABLE audit_log (
+    id SERIAL PRIMARY KEY,
+    action VARCHAR(64) NOT NULL,
+    entity_type VARCHAR(32) NOT NULL,
+    entity_id INT NOT NULL,
+    details JSONB NOT NULL DEFAULT '{}',
+    recorded_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX audit_log_entity_idx ON audit_log (entity_type, entity_id);
@@ -0,0 +1,13 @@
+-- Latin spellings of student names. Existing rows are transliterated by the backend on its next start.
+-- The columns have no length limit: one Cyrillic letter can take up to four Latin ones ("щ" is "shch"),
+-- so the transliteration of a 255-character name does not always fit in 255 characters.
+BEGIN;
+
+ALTER TABLE students_blob
+    ADD COLUMN name_latin TEXT,
+    ADD COLUMN surname_latin TEXT;
+ALTER TABLE students_fs
+    ADD COLUMN name_latin TEXT,
+    ADD COLUMN surname_latin TEXT;
+
+COMMIT;
@@ -0,0 +1,50 @@
+-- Sagas that keep Postgres, Mongo and image files consistent, and the guard that keeps students out of deleted groups.
+BEGIN;
+
+-- Operations that span Postgres, Mongo and image files. A pending saga is finished by the request that
+-- started it or, after a crash, by the backend's reconciler.
+CREATE TABLE sagas (
+    id SERIAL PRIMARY KEY,
+    kind VARCHAR(32) NOT NULL,
+    payload JSONB NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'completed', 'compensated')),
+    attempts INT NOT NULL DEFAULT 0,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX sagas_pending_idx ON sagas (updated_at) WHERE status = 'pending';
+CREATE INDEX sagas_group_deletion_idx ON sagas (((payload->>'group_id')::INT))
+    WHERE status = 'pending' AND kind = 'delete_group';
+
+-- Groups live in Mongo, so nothing may start referring to a group while it is being deleted. The shared lock
+-- waits for a deletion that is still checking the group for members.
+CREATE FUNCTION guard_group_reference() RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.group_id IS NOT NULL THEN
+        PERFORM pg_advisory_xact_lock_shared(hashtext('group'), NEW.group_id);
+        IF EXISTS (
+            SELECT 1 FROM sagas
+            WHERE status = 'pending' AND kind = 'delete_group' AND (payload->>'group_id')::INT = NEW.group_id
+        ) THEN
+            RAISE EXCEPTION 'Group % is being deleted', NEW.group_id USING ERRCODE = 'object_in_use';
+        END IF;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER students_blob_guard_group BEFORE INSERT OR UPDATE OF group_id ON students_blob
+    FOR EACH ROW EXECUTE FUNCTION guard_group_reference();
+
+CREATE TRIGGER students_fs_guard_group BEFORE INSERT OR UPDATE OF group_id ON students_fs
+    FOR EACH ROW EXECUTE FUNCTION guard_group_reference();
+
+CREATE TRIGGER schedule_slots_guard_group BEFORE INSERT OR UPDATE OF group_id ON schedule_slots
+    FOR EACH ROW EXECUTE FUNCTION guard_group_reference();
+
+CREATE TRIGGER assessments_guard_group BEFORE INSERT OR UPDATE OF group_id ON assessments
+    FOR EACH ROW EXECUTE FUNCTION guard_group_reference();
+
+COMMIT;
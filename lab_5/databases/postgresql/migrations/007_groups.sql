@@ -0,0 +1,16 @@
+-- Groups in Postgres, used when the backend runs with GROUP_STORE=postgres.
+BEGIN;
+
+-- Filled by `server migrate-groups postgres`, which also adds the foreign keys from the group_id columns.
+CREATE TABLE groups (
+    id INT PRIMARY KEY CHECK (id >= 0),
+    name VARCHAR(255) NOT NULL,
+    leader_id INT,
+    faculty VARCHAR(255),
+    specialty_code VARCHAR(32),
+    admission_year INT,
+    study_form VARCHAR(16) CHECK (study_form IN ('full_time', 'part_time', 'distance')),
+    max_capacity INT CHECK (max_capacity > 0)
+);
+
+COMMIT;
@@ -1,10 +1,40 @@
+-- Groups are kept here with GROUP_STORE=postgres, and in Mongo otherwise. The backend adds foreign keys from
+-- the group_id columns when it starts with groups in Postgres.
+CREATE TABLE groups (
+    id INT PRIMARY KEY CHECK (id >= 0),
+    name VARCHAR(255) NOT NULL,
+    leader_id INT,
+    faculty VARCHAR(255),
+    specialty_code VARCHAR(32),
+    admission_year INT,
+    study_form VARCHAR(16) CHECK (study_form IN ('full_time', 'part_time', 'distance')),
+    max_capacity INT CHECK (max_capacity > 0)
+);
+
+INSERT INTO groups (id, name, faculty, specialty_code, admission_year, study_form, max_capacity) VALUES
+    (0, 'ІП-11', 'ФІОТ', '121', 2021, 'full_time', 35),
+    (1, 'ІП-12', 'ФІОТ', '121', 2021, 'full_time', 35),
+    (2, 'ІП-13', 'ФІОТ', '121', 2021, 'full_time', 35),
+    (3, 'ІП-15', 'ФІОТ', '121', 2021, 'full_time', 35);
+
 CREATE TABLE students_blob (
     id SERIAL PRIMARY KEY,
     name VARCHAR(255) NOT NULL CHECK (LENGTH(name) > 0),
     surname VARCHAR(255) NOT NULL CHECK (LENGTH(surname) > 0),
-    group_id INT NOT NULL CHECK (group_id >= 0),
+    -- KMU 2010 transliteration, written by the backend together with the name.
+    name_latin TEXT,
+    surname_latin TEXT,
+    group_id INT CHECK (group_id >= 0),
+    patronymic VARCHAR(255),
+    email VARCHAR(254),
+    phone VARCHAR(32),
+    birth_date DATE,
+    record_book_number VARCHAR(32) UNIQUE,
+    status VARCHAR(16) NOT NULL DEFAULT 'active'
+        CHECK (status IN ('active', 'academic_leave', 'expelled', 'graduated')),
     image_data BYTEA,
     image_type VARCHAR(30) CHECK (image_type IN ('image/jpeg', 'image/png', 'image/jpg')),
+    CONSTRAINT student_group_status_check CHECK ((group_id IS NULL) = (status IN ('expelled', 'graduated'))),
     CONSTRAINT image_data_type_check CHECK (
         (image_data IS NULL AND image_type IS NULL) OR 
         (image_data IS NOT NULL AND image_type IS NOT NULL)
@@ -15,11 +45,196 @@ CREATE TABLE students_fs (
     id SERIAL PRIMARY KEY,
     name VARCHAR(255) NOT NULL CHECK (LENGTH(name) > 0),
     surname VARCHAR(255) NOT NULL CHECK (LENGTH(surname) > 0),
-    group_id INT NOT NULL CHECK (group_id >= 0),
+    -- KMU 2010 transliteration, written by the backend together with the name.
+    name_latin TEXT,
+    surname_latin TEXT,
+    group_id INT CHECK (group_id >= 0),
+    patronymic VARCHAR(255),
+    email VARCHAR(254),
+    phone VARCHAR(32),
+    birth_date DATE,
+    record_book_number VARCHAR(32) UNIQUE,
+    status VARCHAR(16) NOT NULL DEFAULT 'active'
+        CHECK (status IN ('active', 'academic_leave', 'expelled', 'graduated')),
     image_path VARCHAR(512),
-    image_type VARCHAR(30) CHECK (image_type IN ('image/jpeg', 'image/png', 'image/jpg'))
+    image_type VARCHAR(30) CHECK (image_type IN ('image/jpeg', 'image/png', 'image/jpg')),
+    CONSTRAINT student_group_status_check CHECK ((group_id IS NULL) = (status IN ('expelled', 'graduated')))
+);
+
+CREATE TABLE subjects (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(255) NOT NULL UNIQUE CHECK (LENGTH(name) > 0)
+);
+
+CREATE TABLE teachers (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(255) NOT NULL CHECK (LENGTH(name) > 0),
+    surname VARCHAR(255) NOT NULL CHECK (LENGTH(surname) > 0)
+);
+
+CREATE TABLE rooms (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(64) NOT NULL UNIQUE CHECK (LENGTH(name) > 0),
+    capacity INT CHECK (capacity > 0)
+);
+
+CREATE TABLE schedule_slots (
+    id SERIAL PRIMARY KEY,
+    group_id INT NOT NULL CHECK (group_id >= 0),
+    subject_id INT NOT NULL REFERENCES subjects(id),
+    teacher_id INT NOT NULL REFERENCES teachers(id),
+    room_id INT NOT NULL REFERENCES rooms(id),
+    weekday SMALLINT NOT NULL CHECK (weekday BETWEEN 1 AND 7),
+    start_time TIME NOT NULL,
+    end_time TIME NOT NULL,
+    week_parity VARCHAR(4) NOT NULL CHECK (week_parity IN ('odd', 'even', 'both')),
+    CONSTRAINT schedule_slot_time_check CHECK (start_time < end_time)
+);
+
+CREATE INDEX schedule_slots_weekday_idx ON schedule_slots (weekday);
+
+CREATE TABLE attendance (
+    student_id INT NOT NULL,
+    date DATE NOT NULL,
+    slot_id INT NOT NULL REFERENCES schedule_slots(id),
+    status VARCHAR(8) NOT NULL CHECK (status IN ('present', 'absent', 'excused', 'late')),
+    PRIMARY KEY (student_id, date, slot_id)
+);
+
+CREATE INDEX attendance_slot_date_idx ON attendance (slot_id, date);
+
+CREATE TABLE assessments (
+    id SERIAL PRIMARY KEY,
+    subject_id INT NOT NULL REFERENCES subjects(id),
+    group_id INT NOT NULL CHECK (group_id >= 0),
+    name VARCHAR(255) NOT NULL CHECK (LENGTH(name) > 0),
+    weight DOUBLE PRECISION NOT NULL CHECK (weight > 0),
+    max_score DOUBLE PRECISION NOT NULL CHECK (max_score > 0),
+    UNIQUE (subject_id, group_id, name)
 );
 
+CREATE TABLE scores (
+    assessment_id INT NOT NULL REFERENCES assessments(id) ON DELETE CASCADE,
+    student_id INT NOT NULL,
+    score DOUBLE PRECISION NOT NULL CHECK (score >= 0),
+    PRIMARY KEY (assessment_id, student_id)
+);
+
+CREATE TABLE student_status_history (
+    id SERIAL PRIMARY KEY,
+    student_id INT NOT NULL,
+    from_status VARCHAR(16) NOT NULL,
+    to_status VARCHAR(16) NOT NULL,
+    reason TEXT NOT NULL CHECK (LENGTH(reason) > 0),
+    effective_date DATE NOT NULL,
+    group_id INT,
+    recorded_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX student_status_history_student_idx ON student_status_history (student_id);
+
+-- Kept when the student is deleted: the trail has to outlive the records it describes.
+CREATE TABLE audit_log (
+    id SERIAL PRIMARY KEY,
+    action VARCHAR(64) NOT NULL,
+    entity_type VARCHAR(32) NOT NULL,
+    entity_id INT NOT NULL,
+    details JSONB NOT NULL DEFAULT '{}',
+    recorded_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX audit_log_entity_idx ON audit_log (entity_type, entity_id);
+
+-- An empty event list subscribes to every event.
+CREATE TABLE webhook_subscriptions (
+    id SERIAL PRIMARY KEY,
+    url TEXT NOT NULL,
+    events VARCHAR(32)[] NOT NULL DEFAULT '{}',
+    secret TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Outbox of webhook deliveries, written together with the change they announce and
+-- kept after delivery as the delivery log.
+CREATE TABLE webhook_deliveries (
+    id SERIAL PRIMARY KEY,
+    subscription_id INT NOT NULL REFERENCES webhook_subscriptions (id) ON DELETE CASCADE,
+    event_type VARCHAR(32) NOT NULL,
+    payload JSONB NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'delivered', 'failed')),
+    attempts INT NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_status_code INT,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    delivered_at TIMESTAMPTZ
+);
+
+CREATE INDEX webhook_deliveries_subscription_idx ON webhook_deliveries (subscription_id);
+CREATE INDEX webhook_deliveries_due_idx ON webhook_deliveries (next_attempt_at) WHERE status = 'pending';
+
+-- Operations that span Postgres, Mongo and image files. A pending saga is finished by the request that
+-- started it or, after a crash, by the backend's reconciler.
+CREATE TABLE sagas (
+    id SERIAL PRIMARY KEY,
+    kind VARCHAR(32) NOT NULL,
+    payload JSONB NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'completed', 'compensated')),
+    attempts INT NOT NULL DEFAULT 0,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX sagas_pending_idx ON sagas (updated_at) WHERE status = 'pending';
+CREATE INDEX sagas_group_deletion_idx ON sagas (((payload->>'group_id')::INT))
+    WHERE status = 'pending' AND kind = 'delete_group';
+
+-- Students live in one of two tables, so dependent rows are cleaned up by a trigger instead of a foreign key.
+CREATE FUNCTION delete_student_dependents() RETURNS TRIGGER AS $$
+BEGIN
+    DELETE FROM attendance WHERE student_id = OLD.id;
+    DELETE FROM scores WHERE student_id = OLD.id;
+    DELETE FROM student_status_history WHERE student_id = OLD.id;
+    RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER students_blob_delete_dependents AFTER DELETE ON students_blob
+    FOR EACH ROW EXECUTE FUNCTION delete_student_dependents();
+
+CREATE TRIGGER students_fs_delete_dependents AFTER DELETE ON students_fs
+    FOR EACH ROW EXECUTE FUNCTION delete_student_dependents();
+
+-- Groups may live in Mongo, so nothing may start referring to a group while it is being deleted. The shared lock
+-- waits for a deletion that is still checking the group for members.
+CREATE FUNCTION guard_group_reference() RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.group_id IS NOT NULL THEN
+        PERFORM pg_advisory_xact_lock_shared(hashtext('group'), NEW.group_id);
+        IF EXISTS (
+            SELECT 1 FROM sagas
+            WHERE status = 'pending' AND kind = 'delete_group' AND (payload->>'group_id')::INT = NEW.group_id
+        ) THEN
+            RAISE EXCEPTION 'Group % is being deleted', NEW.group_id USING ERRCODE = 'object_in_use';
+        END IF;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER students_blob_guard_group BEFORE INSERT OR UPDATE OF group_id ON students_blob
+    FOR EACH ROW EXECUTE FUNCTION guard_group_reference();
+
+CREATE TRIGGER students_fs_guard_group BEFORE INSERT OR UPDATE OF group_id ON students_fs
+    FOR EACH ROW EXECUTE FUNCTION guard_group_reference();
+
+CREATE TRIGGER schedule_slots_guard_group BEFORE INSERT OR UPDATE OF group_id ON schedule_slots
+    FOR EACH ROW EXECUTE FUNCTION guard_group_reference();
+
+CREATE TRIGGER assessments_guard_group BEFORE INSERT OR UPDATE OF group_id ON assessments
+    FOR EACH ROW EXECUTE FUNCTION guard_group_reference();
+
 WITH student_data AS (
     INSERT INTO students_blob (name, surname, group_id) VALUES
         -- ('Сергій', 'Панченко', 0),
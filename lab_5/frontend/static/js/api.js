@@ -17,41 +17,63 @@ const API = {
 
     groups: {
         getAll: async () => {
-            return API.fetchJson('/api/groups');
+            return API.fetchJson('/api/v1/groups');
         },
         get: async (id) => {
-            return API.fetchJson(`/api/groups/${id}`);
+            return API.fetchJson(`/api/v1/groups/${id}`);
+        },
+        getAllWithStudents: async () => {
+            return API.fetchJson('/api/v1/groups?expand=students');
+        },
+        getStudents: async (id) => {
+            return API.fetchJson(`/api/v1/groups/${id}/students`);
         },
         create: async (data) => {
-            return API.fetchJson('/api/groups', {
+            return API.fetchJson('/api/v1/groups', {
                 method: 'POST',
                 headers: { 'Content-Type': 'application/json' },
                 body: JSON.stringify(data)
             });
         },
         update: async (id, data) => {
-            return API.fetchJson(`/api/groups/${id}`, {
+            return API.fetchJson(`/api/v1/groups/${id}`, {
                 method: 'PUT',
                 headers: { 'Content-Type': 'application/json' },
                 body: JSON.stringify(data)
             });
         },
         delete: async (id) => {
-            return API.fetchJson(`/api/groups/${id}`, {
+            return API.fetchJson(`/api/v1/groups/${id}`, {
+                method: 'DELETE'
+            });
+        },
+        setLeader: async (id, studentId) => {
+            return API.fetchJson(`/api/v1/groups/${id}/leader`, {
+                method: 'PUT',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({ student_id: studentId })
+            });
+        },
+        clearLeader: async (id) => {
+            return API.fetchJson(`/api/v1/groups/${id}/leader`, {
                 method: 'DELETE'
             });
         }
     },
 
     students: {
-        getAll: async () => {
-            return API.fetchJson('/api/students');
+        getAll: async (status = '', search = '') => {
+            const params = new URLSearchParams();
+            if (status) params.set('status', status);
+            if (search) params.set('q', search);
+            const query = params.toString() ? `?${params}` : '';
+            return API.fetchJson(`/api/v1/students${query}`);
         },
         get: async (id) => {
-            return API.fetchJson(`/api/students/${id}`);
+            return API.fetchJson(`/api/v1/students/${id}`);
         },
         create: async (formData) => {
-            const response = await fetch(`${API.base_url}/api/students`, {
+            const response = await fetch(`${API.base_url}/api/v1/students`, {
                 method: 'POST',
                 body: formData
             });
@@ -61,7 +83,7 @@ const API = {
             return response;
         },
         update: async (id, formData) => {
-            const response = await fetch(`${API.base_url}/api/students/${id}`, {
+            const response = await fetch(`${API.base_url}/api/v1/students/${id}`, {
                 method: 'PUT',
                 body: formData
             });
@@ -71,13 +93,30 @@ const API = {
             return response;
         },
         delete: async (id) => {
-            return API.fetchJson(`/api/students/${id}`, {
+            return API.fetchJson(`/api/v1/students/${id}`, {
                 method: 'DELETE'
             });
         },
+        getStatus: async (id) => {
+            return API.fetchJson(`/api/v1/students/${id}/status`);
+        },
+        changeStatus: async (id, change) => {
+            return API.fetchJson(`/api/v1/students/${id}/status`, {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify(change)
+            });
+        },
+        batch: async (operations, continueOnError = false) => {
+            return API.fetchJson('/api/v1/students/batch', {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({ operations, continue_on_error: continueOnError })
+            });
+        },
         async getImage(id) {
             try {
-                const response = await fetch(`${API.base_url}/api/students/image/${id}`);
+                const response = await fetch(`${API.base_url}/api/v1/students/image/${id}`);
                 
                 if (!response.ok) {
                     console.warn(`No image found for student ${id}, status: ${response.status}`);
@@ -104,5 +143,18 @@ const API = {
                 return null;
             }
         }
+    },
+
+    events: {
+        // Calls handler(event) for events of the given types, only those of groupId when given,
+        // and for 'resync' after missed events. Call close() on the result to stop listening.
+        subscribe(types, handler, groupId = null) {
+            const query = groupId ? `?group_id=${groupId}` : '';
+            const source = new EventSource(`${API.base_url}/api/v1/events${query}`);
+            [...types, 'resync'].forEach(type => {
+                source.addEventListener(type, message => handler(JSON.parse(message.data)));
+            });
+            return source;
+        }
     }
 };
\ No newline at end of file
@@ -2,6 +2,8 @@ class GroupManager {
     constructor() {
         this.bindEvents();
         this.loadGroups();
+        // Keeps the list current while other tabs change groups.
+        API.events.subscribe(['group.created', 'group.updated', 'group.deleted'], () => this.loadGroups());
     }
 
     bindEvents() {
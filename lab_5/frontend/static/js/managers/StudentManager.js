@@ -10,6 +10,10 @@ class StudentManager {
             .addEventListener('submit', this.handleAddStudent.bind(this));
         document.getElementById('filterGroup')
             .addEventListener('change', this.handleFilterChange.bind(this));
+        document.getElementById('filterStatus')
+            .addEventListener('change', this.handleFilterChange.bind(this));
+        document.getElementById('filterQuery')
+            .addEventListener('change', this.handleFilterChange.bind(this));
         document.getElementById('studentPhoto')
             .addEventListener('change', (e) => PhotoValidator.validateFile(e.target.files[0]));
     }
@@ -29,15 +33,22 @@ class StudentManager {
         }
     }
 
-    async loadStudents(groupId = null) {
+    async loadStudents() {
         try {
-            const students = await API.students.getAll();
-            let filteredStudents = students;
+            const groupId = document.getElementById('filterGroup').value;
+            const status = document.getElementById('filterStatus').value;
+            const query = document.getElementById('filterQuery').value.trim();
+            let filteredStudents;
 
-            if (groupId && groupId !== 'all') {
-                filteredStudents = students.filter(student => 
-                    student.group_id.toString() === groupId.toString()
-                );
+            if (query) {
+                const students = await API.students.getAll(status, query);
+                filteredStudents = students.filter(student =>
+                    !groupId || groupId === 'all' || String(student.group_id) === groupId);
+            } else if (groupId && groupId !== 'all') {
+                const roster = await API.groups.getStudents(groupId);
+                filteredStudents = roster.students.filter(student => !status || student.status === status);
+            } else {
+                filteredStudents = await API.students.getAll(status);
             }
 
             // Clean up previous blob URLs if they exist
@@ -82,9 +93,12 @@ class StudentManager {
                         width="160"
                         height="120">
                 </td>
-                <td>${student.name}</td>
-                <td>${student.surname}</td>
+                <td>${student.name}<br><small>${student.name_latin ?? ''}</small></td>
+                <td>${student.surname}<br><small>${student.surname_latin ?? ''}</small></td>
+                <td>${student.patronymic ?? ''}</td>
                 <td>${student.group_id}</td>
+                <td>${student.record_book_number ?? ''}</td>
+                <td>${student.status}</td>
                 <td>
                     <a href="/update-student?id=${student.id}" class="edit-btn">Edit</a>
                     <button onclick="studentManager.deleteStudent(${student.id})" class="delete-btn">Delete</button>
@@ -145,7 +159,7 @@ class StudentManager {
         }
     }
 
-    async handleFilterChange(event) {
-        await this.loadStudents(event.target.value);
+    async handleFilterChange() {
+        await this.loadStudents();
     }
 }
\ No newline at end of file
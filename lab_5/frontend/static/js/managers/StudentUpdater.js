@@ -14,6 +14,7 @@ class StudentUpdater {
         this.bindEvents();
         await this.loadGroups();
         await this.loadStudentData();
+        await this.loadStatus();
     }
 
     bindEvents() {
@@ -21,6 +22,8 @@ class StudentUpdater {
             .addEventListener('submit', this.handleUpdateStudent.bind(this));
         document.getElementById('studentPhoto')
             .addEventListener('change', (e) => PhotoValidator.validateFile(e.target.files[0]));
+        document.getElementById('statusForm')
+            .addEventListener('submit', this.handleChangeStatus.bind(this));
     }
 
     async loadGroups() {
@@ -45,7 +48,12 @@ class StudentUpdater {
         document.getElementById('studentId').value = student.id;
         document.getElementById('studentName').value = student.name;
         document.getElementById('studentSurname').value = student.surname;
-        document.getElementById('studentGroup').value = student.group_id;
+        document.getElementById('studentGroup').value = student.group_id ?? '';
+        document.getElementById('studentPatronymic').value = student.patronymic ?? '';
+        document.getElementById('studentEmail').value = student.email ?? '';
+        document.getElementById('studentPhone').value = student.phone ?? '';
+        document.getElementById('studentBirthDate').value = student.birth_date ?? '';
+        document.getElementById('studentRecordBook').value = student.record_book_number ?? '';
     
         // Clean up previous blob URL if it exists
         if (this.cleanup) {
@@ -100,6 +108,47 @@ class StudentUpdater {
         }
     }
 
+    async loadStatus() {
+        try {
+            const status = await API.students.getStatus(this.studentId);
+            document.getElementById('currentStatus').textContent = status.status;
+            document.getElementById('statusHistory').innerHTML = status.history.map(entry => `
+                <tr>
+                    <td>${entry.effective_date}</td>
+                    <td>${entry.from_status}</td>
+                    <td>${entry.to_status}</td>
+                    <td>${entry.reason}</td>
+                </tr>
+            `).join('');
+        } catch (error) {
+            Utils.handleApiError(error, 'load student status');
+        }
+    }
+
+    async handleChangeStatus(event) {
+        event.preventDefault();
+        const formData = new FormData(event.target);
+        const change = {
+            status: formData.get('status'),
+            reason: formData.get('reason'),
+            effective_date: formData.get('effective_date')
+        };
+        const groupId = document.getElementById('studentGroup').value;
+        if (change.status === 'active' && groupId) {
+            change.group_id = Number(groupId);
+        }
+
+        try {
+            await API.students.changeStatus(this.studentId, change);
+            Utils.showAlert('Status changed successfully');
+            event.target.reset();
+            await this.loadStudentData();
+            await this.loadStatus();
+        } catch (error) {
+            Utils.handleApiError(error, 'change student status');
+        }
+    }
+
     // Cleanup method to be called when component is destroyed
     destroy() {
         if (this.cleanup) {